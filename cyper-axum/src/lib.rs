@@ -440,7 +440,11 @@ where
                 let close_rx = close_rx.clone();
 
                 compio::runtime::spawn(async move {
-                    let builder = Builder::new(CompioExecutor);
+                    #[allow(unused_mut)]
+                    let mut builder = Builder::new(CompioExecutor);
+                    // CONNECT protocol needed for HTTP/2 websockets
+                    #[cfg(feature = "http2")]
+                    builder.http2().enable_connect_protocol();
                     let conn = builder.serve_connection_with_upgrades(
                         Box::pin(io),
                         ServiceSendWrapper::new(hyper_service),
@@ -0,0 +1,195 @@
+use std::{
+    collections::VecDeque,
+    future::poll_fn,
+    io,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Poll, Waker},
+};
+
+use compio::{
+    BufResult,
+    buf::{IoBuf, IoBufMut},
+    io::{AsyncRead, AsyncWrite, util::Splittable},
+};
+
+/// Creates a pair of connected in-memory streams, e.g. to connect a client to
+/// a server without sockets. The data written to one stream is read from the
+/// other.
+///
+/// Each direction buffers at most `max_buf_size` bytes, and the writes wait
+/// until the data is read.
+///
+/// # Panics
+///
+/// Panics if `max_buf_size` is zero.
+pub fn duplex(max_buf_size: usize) -> (DuplexStream, DuplexStream) {
+    assert!(max_buf_size > 0, "the buffer size should not be zero");
+    let a = Arc::new(Mutex::new(Pipe::new(max_buf_size)));
+    let b = Arc::new(Mutex::new(Pipe::new(max_buf_size)));
+    (
+        DuplexStream {
+            read: DuplexReadHalf(a.clone()),
+            write: DuplexWriteHalf(b.clone()),
+        },
+        DuplexStream {
+            read: DuplexReadHalf(b),
+            write: DuplexWriteHalf(a),
+        },
+    )
+}
+
+/// One direction of a duplex stream.
+#[derive(Debug)]
+struct Pipe {
+    buf: VecDeque<u8>,
+    max_buf_size: usize,
+    /// Whether either end is closed.
+    closed: bool,
+    read_waker: Option<Waker>,
+    write_waker: Option<Waker>,
+}
+
+impl Pipe {
+    fn new(max_buf_size: usize) -> Self {
+        Self {
+            buf: VecDeque::new(),
+            max_buf_size,
+            closed: false,
+            read_waker: None,
+            write_waker: None,
+        }
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        self.wake();
+    }
+
+    fn wake(&mut self) {
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+        if let Some(waker) = self.write_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+fn lock(pipe: &Mutex<Pipe>) -> MutexGuard<'_, Pipe> {
+    pipe.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// An in-memory stream created by [`duplex`].
+///
+/// Dropping or shutting down a stream closes it, and the other stream reads
+/// the end of file after the buffered data.
+#[derive(Debug)]
+pub struct DuplexStream {
+    read: DuplexReadHalf,
+    write: DuplexWriteHalf,
+}
+
+/// The read half of a [`DuplexStream`].
+#[derive(Debug)]
+pub struct DuplexReadHalf(Arc<Mutex<Pipe>>);
+
+/// The write half of a [`DuplexStream`].
+#[derive(Debug)]
+pub struct DuplexWriteHalf(Arc<Mutex<Pipe>>);
+
+impl AsyncRead for DuplexReadHalf {
+    async fn read<B: IoBufMut>(&mut self, mut buf: B) -> BufResult<usize, B> {
+        let len = poll_fn(|cx| {
+            let mut pipe = lock(&self.0);
+            let dst = buf.as_uninit();
+            if pipe.buf.is_empty() && !dst.is_empty() {
+                if pipe.closed {
+                    return Poll::Ready(0);
+                }
+                pipe.read_waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+            let len = dst.len().min(pipe.buf.len());
+            for (dst, src) in dst.iter_mut().zip(pipe.buf.drain(..len)) {
+                dst.write(src);
+            }
+            pipe.wake();
+            Poll::Ready(len)
+        })
+        .await;
+        unsafe { buf.advance_to(len) };
+        BufResult(Ok(len), buf)
+    }
+}
+
+impl AsyncWrite for DuplexWriteHalf {
+    async fn write<T: IoBuf>(&mut self, buf: T) -> BufResult<usize, T> {
+        let res = poll_fn(|cx| {
+            let data = buf.as_init();
+            let mut pipe = lock(&self.0);
+            if pipe.closed {
+                return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+            }
+            let len = data.len().min(pipe.max_buf_size - pipe.buf.len());
+            if len == 0 && !data.is_empty() {
+                pipe.write_waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+            pipe.buf.extend(&data[..len]);
+            pipe.wake();
+            Poll::Ready(Ok(len))
+        })
+        .await;
+        BufResult(res, buf)
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        lock(&self.0).close();
+        Ok(())
+    }
+}
+
+impl Drop for DuplexReadHalf {
+    fn drop(&mut self) {
+        lock(&self.0).close();
+    }
+}
+
+impl Drop for DuplexWriteHalf {
+    fn drop(&mut self) {
+        lock(&self.0).close();
+    }
+}
+
+impl AsyncRead for DuplexStream {
+    async fn read<B: IoBufMut>(&mut self, buf: B) -> BufResult<usize, B> {
+        self.read.read(buf).await
+    }
+}
+
+impl AsyncWrite for DuplexStream {
+    async fn write<T: IoBuf>(&mut self, buf: T) -> BufResult<usize, T> {
+        self.write.write(buf).await
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        self.write.flush().await
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        self.write.shutdown().await
+    }
+}
+
+impl Splittable for DuplexStream {
+    type ReadHalf = DuplexReadHalf;
+    type WriteHalf = DuplexWriteHalf;
+
+    fn split(self) -> (Self::ReadHalf, Self::WriteHalf) {
+        (self.read, self.write)
+    }
+}
@@ -8,3 +8,6 @@ pub use executor::*;
 
 mod stream;
 pub use stream::*;
+
+mod duplex;
+pub use duplex::*;
@@ -1,19 +1,42 @@
-//! Vendored and modified from `altsvc` crate.
+//! Alt-Svc ([RFC 7838]) parsing and caching.
+//!
+//! [RFC 7838]: https://www.rfc-editor.org/rfc/rfc7838
 
-use std::{collections::HashMap, time::Instant};
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{Duration, SystemTime},
+};
 
+use http::uri::Authority;
 use thiserror::Error;
 
-use crate::sync::{mutex_blocking::Mutex, shared::Shared};
+use crate::{
+    Result,
+    http3::AltEndpoint,
+    sync::{mutex_blocking::Mutex, shared::Shared},
+    util::{format_expiry, parse_expiry},
+};
+
+/// Protocol IDs of the alternative services the client could use.
+const SUPPORTED_PROTOCOLS: &[&str] = &["h3", "h3-29"];
+
+/// The default freshness lifetime of an alternative service.
+const DEFAULT_MAX_AGE: u64 = 86400; // 24 hours
+
+/// The upper bound of `ma`, about 100 years.
+const MAX_AGE_LIMIT: u64 = 100 * 365 * 86400;
 
 #[derive(Error, Debug)]
 pub enum ParseError {
-    #[error("invalid parameter: {0}")]
-    Parameter(String),
+    #[error("unexpected character at {0}")]
+    Unexpected(usize),
+    #[error("unterminated quoted string")]
+    UnterminatedQuote,
+    #[error("invalid protocol id: {0}")]
+    ProtocolId(String),
     #[error("invalid value of 'ma': {0}")]
     MaValue(String),
-    #[error("invalid value of 'persist': {0}")]
-    PersistValue(String),
     #[error("invalid value of 'alt-authority': {0}")]
     AltAuthorityValue(String),
     #[error("invalid port number: {0}")]
@@ -26,7 +49,7 @@ pub enum AltService {
     Services(Vec<Service>),
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq, Eq)]
 pub struct Service {
     id: String,
     authority: AltAuthority,
@@ -34,84 +57,277 @@ pub struct Service {
     persist: bool,
 }
 
-#[derive(Debug, Default)]
+impl Service {
+    fn port(&self) -> u16 {
+        self.authority.port
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
 pub struct AltAuthority {
+    /// The alternative host, empty if it is the same as the origin.
     pub host: String,
     pub port: u16,
 }
 
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), ParseError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(ParseError::Unexpected(self.pos))
+        }
+    }
+
+    fn skip_ows(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn token(&mut self) -> Result<&'a str, ParseError> {
+        let start = self.pos;
+        while self.peek().is_some_and(is_tchar) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(ParseError::Unexpected(self.pos));
+        }
+        Ok(&self.input[start..self.pos])
+    }
+
+    fn quoted_string(&mut self) -> Result<String, ParseError> {
+        self.expect(b'"')?;
+        let mut value = Vec::new();
+        loop {
+            match self.peek() {
+                None => return Err(ParseError::UnterminatedQuote),
+                Some(b'"') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let c = self.peek().ok_or(ParseError::UnterminatedQuote)?;
+                    value.push(c);
+                    self.pos += 1;
+                }
+                Some(c) => {
+                    value.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        // The input is a `str`, and only ASCII bytes are unescaped.
+        Ok(String::from_utf8(value).expect("quoted string should be valid UTF-8"))
+    }
+
+    /// Parses `token / quoted-string`.
+    fn value(&mut self) -> Result<String, ParseError> {
+        if self.peek() == Some(b'"') {
+            self.quoted_string()
+        } else {
+            self.token().map(str::to_string)
+        }
+    }
+
+    /// Skips the rest of the current alt-value, including the trailing comma.
+    fn skip_alt_value(&mut self) {
+        let mut quoted = false;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                b'"' => quoted = !quoted,
+                b'\\' if quoted => self.pos += 1,
+                b',' if !quoted => break,
+                _ => {}
+            }
+        }
+    }
+
+    /// Parses `alternative *( OWS ";" OWS parameter )`.
+    fn alt_value(&mut self) -> Result<Service, ParseError> {
+        let id = percent_decode(self.token()?)?;
+        self.expect(b'=')?;
+        let authority = parse_authority(&self.value()?)?;
+        let mut svc = Service {
+            id,
+            authority,
+            ..Default::default()
+        };
+        loop {
+            self.skip_ows();
+            if !self.eat(b';') {
+                break;
+            }
+            self.skip_ows();
+            if matches!(self.peek(), None | Some(b',' | b';')) {
+                continue;
+            }
+            let name = self.token()?;
+            self.skip_ows();
+            self.expect(b'=')?;
+            self.skip_ows();
+            let value = self.value()?;
+            if name.eq_ignore_ascii_case("ma") {
+                svc.max_age = Some(value.parse().map_err(|_| ParseError::MaValue(value))?);
+            } else if name.eq_ignore_ascii_case("persist") {
+                // Clients MUST ignore "persist" parameters with values other than "1".
+                svc.persist = value == "1";
+            }
+        }
+        Ok(svc)
+    }
+}
+
+fn is_tchar(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c)
+}
+
+fn percent_decode(s: &str) -> Result<String, ParseError> {
+    let err = || ParseError::ProtocolId(s.to_string());
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3).ok_or_else(err)?;
+            decoded.push(u8::from_str_radix(hex, 16).map_err(|_| err())?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| err())
+}
+
+/// Parses `[ uri-host ] ":" port`.
+fn parse_authority(s: &str) -> Result<AltAuthority, ParseError> {
+    let (host, port) = s
+        .rsplit_once(':')
+        .ok_or_else(|| ParseError::AltAuthorityValue(s.to_string()))?;
+    if !host.is_empty() && Authority::try_from(host).is_err() {
+        return Err(ParseError::AltAuthorityValue(s.to_string()));
+    }
+    let port = port
+        .parse()
+        .map_err(|_| ParseError::PortNumber(port.to_string()))?;
+    Ok(AltAuthority {
+        host: host.to_string(),
+        port,
+    })
+}
+
+/// Parses the value of an `Alt-Svc` header. Malformed alternatives are
+/// skipped, and an error is returned only if none of them could be parsed.
 pub fn parse(s: &str) -> Result<AltService, ParseError> {
+    let s = s.trim_matches([' ', '\t']);
     if s == "clear" {
         return Ok(AltService::Clear);
     }
 
+    let mut parser = Parser { input: s, pos: 0 };
     let mut ret = Vec::new();
-    let services = s.split(",");
-    for svc_string in services {
-        let svc_string = svc_string.trim();
-        if svc_string.is_empty() {
+    let mut err = None;
+    loop {
+        parser.skip_ows();
+        if parser.peek().is_none() {
+            break;
+        }
+        if parser.eat(b',') {
             continue;
         }
-        let mut svc = Service::default();
-        let params = svc_string.split(";");
-        for kv in params {
-            let raw_kv = kv.trim();
-            if raw_kv.is_empty() {
-                continue;
-            }
-            let kv_split: Vec<&str> = raw_kv.split("=").collect();
-            if kv_split.len() != 2 {
-                return Err(ParseError::Parameter(raw_kv.to_string()));
-            }
-            let k = kv_split[0];
-            let v = kv_split[1];
-            match k {
-                "ma" => {
-                    let ma = v.parse().map_err(|_| ParseError::MaValue(v.to_string()))?;
-                    svc.max_age = Some(ma);
-                }
-                "persist" => {
-                    let persist = v
-                        .parse::<i32>()
-                        .map_err(|_| ParseError::PersistValue(v.to_string()))?;
-                    if persist != 1 {
-                        continue;
-                    }
-                    svc.persist = true;
-                }
-                _ => {
-                    let raw_value = v.trim_matches('"');
-                    let alt_auth_split: Vec<&str> = raw_value.split(":").collect();
-                    if alt_auth_split.len() != 2 {
-                        return Err(ParseError::AltAuthorityValue(raw_value.to_string()));
-                    }
-                    svc.id = k.to_string();
-                    let host = alt_auth_split[0].to_string();
-                    let port = alt_auth_split[1].to_string();
-                    let port = port
-                        .parse()
-                        .map_err(|_| ParseError::PortNumber(port.to_string()))?;
-                    svc.authority = AltAuthority { host, port };
+        match parser.alt_value() {
+            Ok(svc) => {
+                ret.push(svc);
+                parser.skip_ows();
+                if !parser.eat(b',') && parser.peek().is_some() {
+                    err = Some(ParseError::Unexpected(parser.pos));
+                    parser.skip_alt_value();
                 }
             }
+            Err(e) => {
+                err = Some(e);
+                parser.skip_alt_value();
+            }
         }
-        ret.push(svc);
     }
-    Ok(AltService::Services(ret))
+    match err {
+        Some(e) if ret.is_empty() => Err(e),
+        _ => Ok(AltService::Services(ret)),
+    }
 }
 
-#[derive(Debug)]
-struct AltHostEntry {
-    insert_time: Instant,
-    max_age: u64,
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Origin {
+    host: String,
+    port: u16,
 }
 
+/// A cached alternative service of an origin.
 #[derive(Debug, Clone)]
-pub struct KnownHosts {
-    map: Shared<Mutex<HashMap<String, AltHostEntry>>>,
+pub(crate) struct Alternative {
+    protocol: String,
+    host: String,
+    port: u16,
+    expires: SystemTime,
+    persist: bool,
 }
 
-impl Default for KnownHosts {
+impl Alternative {
+    /// The value of the `Alt-Used` header.
+    pub fn alt_used(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    /// The endpoint to connect to, or `None` if it is the origin itself.
+    pub fn endpoint(&self, host: &str, port: u16) -> Option<AltEndpoint> {
+        let draft29 = self.protocol == "h3-29";
+        if !draft29 && self.host == host && self.port == port {
+            return None;
+        }
+        Some(AltEndpoint {
+            authority: Authority::try_from(self.alt_used()).ok()?,
+            draft29,
+        })
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires <= now
+    }
+}
+
+/// A cache of alternative services advertised by origins via the `Alt-Svc`
+/// header.
+///
+/// The cache is shared between the clones. It could be saved to and loaded
+/// from a file, so that a client could start on HTTP/3 after a restart. The
+/// file format is the same as the one used by curl.
+#[derive(Debug, Clone)]
+pub struct AltSvcCache {
+    map: Shared<Mutex<HashMap<Origin, Vec<Alternative>>>>,
+}
+
+impl Default for AltSvcCache {
     fn default() -> Self {
         Self {
             map: Shared::new(Mutex::new(HashMap::new())),
@@ -119,36 +335,294 @@ impl Default for KnownHosts {
     }
 }
 
-impl KnownHosts {
-    pub fn try_insert(&self, host: &str, srv: &crate::altsvc::Service) -> bool {
-        if srv.id == "h3"
-            && (srv.authority.host.is_empty() || srv.authority.host == host)
-            && srv.authority.port == 443
-        {
-            self.map.lock().insert(
-                host.to_string(),
-                AltHostEntry {
-                    insert_time: Instant::now(),
-                    max_age: srv.max_age.unwrap_or(86400), // 24 hours
-                },
-            );
-            return true;
+impl AltSvcCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the cache from a file. A missing file results in an empty cache,
+    /// and malformed or expired entries are ignored.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let cache = Self::new();
+        match compio::fs::read(path).await {
+            Ok(content) => cache.read_from(&String::from_utf8_lossy(&content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(cache)
+    }
+
+    /// Saves the unexpired entries to a file.
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let content = self.write_to();
+        compio::fs::write(path, content).await.0?;
+        Ok(())
+    }
+
+    /// Removes all entries.
+    pub fn clear(&self) {
+        self.map.lock().clear();
+    }
+
+    /// Notifies the cache that the network configuration has changed. The
+    /// entries not marked as `persist=1` are removed, as recommended by
+    /// RFC 7838.
+    pub fn network_changed(&self) {
+        let mut map = self.map.lock();
+        map.retain(|_, alts| {
+            alts.retain(|alt| alt.persist);
+            !alts.is_empty()
+        });
+    }
+
+    /// Finds the first fresh alternative of the origin accepted by `usable`.
+    pub(crate) fn find(
+        &self,
+        host: &str,
+        port: u16,
+        mut usable: impl FnMut(&Alternative) -> bool,
+    ) -> Option<Alternative> {
+        let now = SystemTime::now();
+        let mut map = self.map.lock();
+        let origin = Origin {
+            host: host.to_string(),
+            port,
+        };
+        let alts = map.get_mut(&origin)?;
+        alts.retain(|alt| !alt.is_expired(now));
+        if alts.is_empty() {
+            map.remove(&origin);
+            return None;
+        }
+        alts.iter().find(|alt| usable(alt)).cloned()
+    }
+
+    /// Replaces the alternatives of the origin with the received ones.
+    pub(crate) fn update(&self, host: &str, port: u16, services: AltService) {
+        let origin = Origin {
+            host: host.to_string(),
+            port,
+        };
+        let alts = match services {
+            AltService::Clear => Vec::new(),
+            AltService::Services(services) => {
+                let now = SystemTime::now();
+                services
+                    .into_iter()
+                    .filter(|svc| SUPPORTED_PROTOCOLS.contains(&svc.id.as_str()) && svc.port() != 0)
+                    .map(|svc| Alternative {
+                        host: if svc.authority.host.is_empty() {
+                            host.to_string()
+                        } else {
+                            svc.authority.host
+                        },
+                        port: svc.authority.port,
+                        // Long enough for any practical purpose, without
+                        // overflowing the expiry time.
+                        expires: now
+                            + Duration::from_secs(
+                                svc.max_age.unwrap_or(DEFAULT_MAX_AGE).min(MAX_AGE_LIMIT),
+                            ),
+                        persist: svc.persist,
+                        protocol: svc.id,
+                    })
+                    .collect()
+            }
+        };
+        let mut map = self.map.lock();
+        if alts.is_empty() {
+            map.remove(&origin);
+        } else {
+            map.insert(origin, alts);
         }
-        false
     }
 
-    pub fn find(&self, host: &str) -> bool {
+    fn read_from(&self, content: &str) {
+        let now = SystemTime::now();
         let mut map = self.map.lock();
-        if let Some((host, entry)) = map.remove_entry(host)
-            && (Instant::now() - entry.insert_time).as_secs() <= entry.max_age
-        {
-            map.insert(host, entry);
-            return true;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((origin, alt)) = parse_line(line) else {
+                continue;
+            };
+            if !alt.is_expired(now) && SUPPORTED_PROTOCOLS.contains(&alt.protocol.as_str()) {
+                map.entry(origin).or_default().push(alt);
+            }
         }
-        false
     }
 
-    pub fn clear(&self, host: &str) {
-        self.map.lock().remove(host);
+    fn write_to(&self) -> String {
+        let now = SystemTime::now();
+        let map = self.map.lock();
+        let mut content = String::from("# Alt-Svc cache file\n");
+        for (origin, alts) in map.iter() {
+            for alt in alts.iter().filter(|alt| !alt.is_expired(now)) {
+                content.push_str(&format!(
+                    "h2 {} {} {} {} {} \"{}\" {} 0\n",
+                    origin.host,
+                    origin.port,
+                    alt.protocol,
+                    alt.host,
+                    alt.port,
+                    format_expiry(alt.expires),
+                    u8::from(alt.persist),
+                ));
+            }
+        }
+        content
     }
 }
+
+/// Parses a line of `src-alpn src-host src-port dst-alpn dst-host dst-port
+/// "YYYYMMDD HH:MM:SS" persist priority`.
+fn parse_line(line: &str) -> Option<(Origin, Alternative)> {
+    let (head, rest) = line.split_once('"')?;
+    let (expires, tail) = rest.split_once('"')?;
+    let head = head.split_whitespace().collect::<Vec<_>>();
+    let tail = tail.split_whitespace().collect::<Vec<_>>();
+    let [_, host, port, protocol, alt_host, alt_port] = head[..] else {
+        return None;
+    };
+    let persist = *tail.first()?;
+    Some((
+        Origin {
+            host: host.to_string(),
+            port: port.parse().ok()?,
+        },
+        Alternative {
+            protocol: protocol.to_string(),
+            host: alt_host.to_string(),
+            port: alt_port.parse().ok()?,
+            expires: parse_expiry(expires)?,
+            persist: persist == "1",
+        },
+    ))
+}
+
+#[test]
+fn test_parse_quoted() {
+    let AltService::Services(services) =
+        parse(r#"h3="alt.example.com:8443"; ma=3600; persist=1, h3-29=":443""#).unwrap()
+    else {
+        panic!("expected services");
+    };
+    assert_eq!(
+        services,
+        [
+            Service {
+                id: "h3".into(),
+                authority: AltAuthority {
+                    host: "alt.example.com".into(),
+                    port: 8443,
+                },
+                max_age: Some(3600),
+                persist: true,
+            },
+            Service {
+                id: "h3-29".into(),
+                authority: AltAuthority {
+                    host: String::new(),
+                    port: 443,
+                },
+                max_age: None,
+                persist: false,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_parse_tricky() {
+    // Quoted parameter values with separators, IPv6 hosts, percent-encoded
+    // protocol IDs, unknown parameters and malformed alternatives.
+    let AltService::Services(services) = parse(
+        r#"w=x:y#z, h3="[::1]:443";foo="a,b;c=d";ma="60", h2=":bad", h3%2D29=":8443";persist=0"#,
+    )
+    .unwrap() else {
+        panic!("expected services");
+    };
+    let ids = services
+        .iter()
+        .map(|svc| (svc.id.as_str(), svc.authority.host.as_str(), svc.port()))
+        .collect::<Vec<_>>();
+    assert_eq!(ids, [("h3", "[::1]", 443), ("h3-29", "", 8443)]);
+    assert_eq!(services[0].max_age, Some(60));
+    assert!(!services[1].persist);
+
+    assert!(matches!(parse(" clear "), Ok(AltService::Clear)));
+    assert!(parse(r#"h3=":443"#).is_err());
+}
+
+#[test]
+fn test_cache_update() {
+    let cache = AltSvcCache::new();
+    let services = parse(r#"h2=":443", h3="alt.example.com:443", h3=":443""#).unwrap();
+    cache.update("example.com", 443, services);
+    let alt = cache.find("example.com", 443, |_| true).unwrap();
+    assert_eq!(alt.alt_used(), "alt.example.com:443");
+    assert!(alt.endpoint("example.com", 443).is_some());
+
+    cache.update("example.com", 443, parse(r#"h3=":443""#).unwrap());
+    let alt = cache.find("example.com", 443, |_| true).unwrap();
+    assert!(alt.endpoint("example.com", 443).is_none());
+    assert!(cache.find("example.com", 8443, |_| true).is_none());
+
+    cache.update("example.com", 443, parse("clear").unwrap());
+    assert!(cache.find("example.com", 443, |_| true).is_none());
+
+    cache.update("example.com", 443, parse(r#"h3=":443"; ma=0"#).unwrap());
+    assert!(cache.find("example.com", 443, |_| true).is_none());
+
+    // The lifetime is capped instead of overflowing.
+    let services = parse(r#"h3=":443"; ma=18446744073709551615"#).unwrap();
+    cache.update("example.com", 443, services);
+    assert!(cache.find("example.com", 443, |_| true).is_some());
+}
+
+#[test]
+fn test_cache_network_changed() {
+    let cache = AltSvcCache::new();
+    cache.update("a.com", 443, parse(r#"h3=":443"; persist=1"#).unwrap());
+    cache.update("b.com", 443, parse(r#"h3=":443""#).unwrap());
+    cache.network_changed();
+    assert!(cache.find("a.com", 443, |_| true).is_some());
+    assert!(cache.find("b.com", 443, |_| true).is_none());
+}
+
+#[test]
+fn test_cache_file() {
+    let cache = AltSvcCache::new();
+    cache.update(
+        "example.com",
+        443,
+        parse(r#"h3="alt.example.com:8443"; persist=1, h3-29=":443""#).unwrap(),
+    );
+    let content = cache.write_to();
+
+    let loaded = AltSvcCache::new();
+    loaded.read_from(&content);
+    loaded.read_from("h2 example.org 443 h3 example.org 443 \"20000101 00:00:00\" 0 0\ngarbage");
+    loaded
+        .read_from("h2 example.org 443 h3 example.org 443 \"20000101 99999999999999999:0:0\" 0 0");
+    let alts = loaded.map.lock().clone();
+    assert_eq!(alts.len(), 1);
+    let alts = &alts[&Origin {
+        host: "example.com".into(),
+        port: 443,
+    }];
+    assert_eq!(alts.len(), 2);
+    assert_eq!(alts[0].alt_used(), "alt.example.com:8443");
+    assert!(alts[0].persist);
+    assert_eq!(alts[1].protocol, "h3-29");
+
+    // Expiry times are stored in seconds.
+    let original = cache.find("example.com", 443, |_| true).unwrap().expires;
+    let diff = original
+        .duration_since(alts[0].expires)
+        .unwrap_or_else(|e| e.duration());
+    assert!(diff < Duration::from_secs(1));
+}
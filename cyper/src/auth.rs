@@ -0,0 +1,422 @@
+//! Pluggable authentication.
+//!
+//! An [`Authenticator`] set with
+//! [`ClientBuilder::authenticator`](crate::ClientBuilder::authenticator)
+//! provides the `Authorization` header of every request sent by the client,
+//! and may refresh the credentials and retry once when the server responds
+//! with `401 Unauthorized`.
+
+use std::fmt::Debug;
+
+use futures_util::{FutureExt, TryFutureExt, future::LocalBoxFuture};
+use http::{HeaderValue, StatusCode, header::AUTHORIZATION};
+use send_wrapper::SendWrapper;
+
+use crate::{Client, Request, Response, Result, sync::shared::Shared};
+
+#[allow(async_fn_in_trait)]
+/// Trait for providing the credentials of requests.
+///
+/// The [`Client`] passed to the methods sends requests without the
+/// authenticator, e.g. to fetch a token.
+pub trait Authenticator {
+    /// The error type when the credentials are not available.
+    type Err: Into<crate::Error>;
+
+    /// Returns the `Authorization` header value of the request.
+    ///
+    /// It is called before each request without an `Authorization` header.
+    async fn authorize(
+        &self,
+        client: &Client,
+        request: &Request,
+    ) -> Result<Option<HeaderValue>, Self::Err>;
+
+    /// Notified of a `401 Unauthorized` response, with the
+    /// `WWW-Authenticate` challenges in its headers. Return `true` to send
+    /// the request again once, with the header from
+    /// [`authorize`](Self::authorize).
+    ///
+    /// The request isn't retried if its body cannot be cloned. The default
+    /// implementation returns `false`.
+    async fn challenge(
+        &self,
+        client: &Client,
+        request: &Request,
+        response: &Response,
+    ) -> Result<bool, Self::Err> {
+        let _ = (client, request, response);
+        Ok(false)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct SharedAuthenticator(SendWrapper<Shared<dyn TypeErasedAuthenticator>>);
+
+impl SharedAuthenticator {
+    pub(crate) fn new<A: Authenticator + 'static>(authenticator: A) -> Self {
+        Self(SendWrapper::new(Shared::new(authenticator)))
+    }
+}
+
+impl Debug for SharedAuthenticator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_tuple("SharedAuthenticator").finish()
+    }
+}
+
+trait TypeErasedAuthenticator {
+    fn type_erased_authorize<'a>(
+        &'a self,
+        client: &'a Client,
+        request: &'a Request,
+    ) -> LocalBoxFuture<'a, Result<Option<HeaderValue>>>;
+
+    fn type_erased_challenge<'a>(
+        &'a self,
+        client: &'a Client,
+        request: &'a Request,
+        response: &'a Response,
+    ) -> LocalBoxFuture<'a, Result<bool>>;
+}
+
+impl<T: Authenticator> TypeErasedAuthenticator for T {
+    fn type_erased_authorize<'a>(
+        &'a self,
+        client: &'a Client,
+        request: &'a Request,
+    ) -> LocalBoxFuture<'a, Result<Option<HeaderValue>>> {
+        self.authorize(client, request)
+            .map_err(Into::into)
+            .boxed_local()
+    }
+
+    fn type_erased_challenge<'a>(
+        &'a self,
+        client: &'a Client,
+        request: &'a Request,
+        response: &'a Response,
+    ) -> LocalBoxFuture<'a, Result<bool>> {
+        self.challenge(client, request, response)
+            .map_err(Into::into)
+            .boxed_local()
+    }
+}
+
+/// Send the request with the credentials of the authenticator, and retry
+/// once if the authenticator accepts the challenge.
+pub(crate) async fn execute(
+    authenticator: &SharedAuthenticator,
+    client: &Client,
+    mut request: Request,
+) -> Result<Response> {
+    if request.headers().contains_key(AUTHORIZATION) {
+        return client.execute_request(request).await;
+    }
+    if let Some(value) = authenticator
+        .0
+        .type_erased_authorize(client, &request)
+        .await?
+    {
+        request.headers_mut().insert(AUTHORIZATION, value);
+    }
+    let retry = request.try_clone();
+    let res = client.execute_request(request).await?;
+    if res.status() != StatusCode::UNAUTHORIZED {
+        return Ok(res);
+    }
+    let Some(mut retry) = retry else {
+        return Ok(res);
+    };
+    if !authenticator
+        .0
+        .type_erased_challenge(client, &retry, &res)
+        .await?
+    {
+        return Ok(res);
+    }
+    retry.headers_mut().remove(AUTHORIZATION);
+    if let Some(value) = authenticator
+        .0
+        .type_erased_authorize(client, &retry)
+        .await?
+    {
+        retry.headers_mut().insert(AUTHORIZATION, value);
+    }
+    let mut res = client.execute_request(retry).await?;
+    res.retries += 1;
+    Ok(res)
+}
+
+#[cfg(feature = "json")]
+mod oauth2 {
+    use std::time::{Duration, Instant};
+
+    use futures_util::lock::Mutex;
+    use http::HeaderValue;
+    use serde_json::Value;
+    use url::{Origin, Url};
+
+    use super::Authenticator;
+    use crate::{Client, Error, IntoUrl, Request, Response, Result};
+
+    /// Refresh the token a bit earlier than it expires.
+    const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+    #[derive(Debug)]
+    struct Token {
+        access_token: String,
+        expires_at: Option<Instant>,
+    }
+
+    #[derive(Debug, Default)]
+    struct State {
+        token: Option<Token>,
+        refresh_token: Option<String>,
+    }
+
+    /// An OAuth 2.0 [`Authenticator`], which requests bearer tokens with the
+    /// client credentials grant or the refresh token grant (RFC 6749).
+    ///
+    /// The token is only sent to the origins added with
+    /// [`origin`](Self::origin), and never to the token endpoint. It is cached
+    /// until it expires, or until the server rejects it with
+    /// `401 Unauthorized`. The token endpoint is requested with the client
+    /// itself, and the client ID and secret are sent with HTTP basic
+    /// authentication.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cyper::auth::OAuth2;
+    ///
+    /// # async fn run() -> cyper::Result<()> {
+    /// let client = cyper::Client::builder()
+    ///     .authenticator(
+    ///         OAuth2::client_credentials("https://auth.example.com/token", "id", "secret")?
+    ///             .origin("https://api.example.com")?
+    ///             .scopes(["read", "write"]),
+    ///     )
+    ///     .build()?;
+    /// let res = client.get("https://api.example.com/me")?.send().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `json` feature enabled.
+    pub struct OAuth2 {
+        token_url: Url,
+        client_id: String,
+        client_secret: Option<String>,
+        scopes: Vec<String>,
+        origins: Vec<Origin>,
+        client_credentials: bool,
+        state: Mutex<State>,
+    }
+
+    impl OAuth2 {
+        /// Request tokens with the client credentials grant.
+        pub fn client_credentials(
+            token_url: impl IntoUrl,
+            client_id: impl Into<String>,
+            client_secret: impl Into<String>,
+        ) -> Result<Self> {
+            Ok(Self {
+                token_url: token_url.into_url()?,
+                client_id: client_id.into(),
+                client_secret: Some(client_secret.into()),
+                scopes: vec![],
+                origins: vec![],
+                client_credentials: true,
+                state: Mutex::new(State::default()),
+            })
+        }
+
+        /// Request tokens with the refresh token grant. The refresh token is
+        /// replaced if the server issues a new one.
+        pub fn refresh_token(
+            token_url: impl IntoUrl,
+            client_id: impl Into<String>,
+            refresh_token: impl Into<String>,
+        ) -> Result<Self> {
+            Ok(Self {
+                token_url: token_url.into_url()?,
+                client_id: client_id.into(),
+                client_secret: None,
+                scopes: vec![],
+                origins: vec![],
+                client_credentials: false,
+                state: Mutex::new(State {
+                    token: None,
+                    refresh_token: Some(refresh_token.into()),
+                }),
+            })
+        }
+
+        /// Set the client secret, for confidential clients using the refresh
+        /// token grant.
+        pub fn client_secret(mut self, client_secret: impl Into<String>) -> Self {
+            self.client_secret = Some(client_secret.into());
+            self
+        }
+
+        /// Set the scopes to request.
+        pub fn scopes<I, S>(mut self, scopes: I) -> Self
+        where
+            I: IntoIterator<Item = S>,
+            S: Into<String>,
+        {
+            self.scopes = scopes.into_iter().map(Into::into).collect();
+            self
+        }
+
+        /// Add an origin of the resource servers accepting the tokens. The
+        /// path of the URL is ignored.
+        pub fn origin(mut self, url: impl IntoUrl) -> Result<Self> {
+            self.origins.push(url.into_url()?.origin());
+            Ok(self)
+        }
+
+        /// Whether the token should be sent with the request.
+        fn in_scope(&self, url: &Url) -> bool {
+            let is_token_url =
+                url.origin() == self.token_url.origin() && url.path() == self.token_url.path();
+            !is_token_url && self.origins.contains(&url.origin())
+        }
+
+        async fn request_token(&self, client: &Client, state: &mut State) -> Result<Token> {
+            let scope = self.scopes.join(" ");
+            let mut params = vec![];
+            match &state.refresh_token {
+                Some(refresh_token) => {
+                    params.push(("grant_type", "refresh_token"));
+                    params.push(("refresh_token", refresh_token));
+                }
+                None => params.push(("grant_type", "client_credentials")),
+            }
+            if !scope.is_empty() {
+                params.push(("scope", &scope));
+            }
+            let res = client
+                .post(self.token_url.clone())?
+                .basic_auth(&self.client_id, self.client_secret.as_ref())?
+                .header(http::header::ACCEPT, "application/json")?
+                .form(&params)?
+                .send()
+                .await?;
+            let status = res.status();
+            let body = res.json::<Value>().await;
+            if !status.is_success() {
+                let error = body
+                    .ok()
+                    .and_then(|body| Some(body.get("error")?.as_str()?.to_string()))
+                    .unwrap_or_else(|| status.to_string());
+                return Err(Error::Auth(format!("token request failed: {error}").into()));
+            }
+            let body = body?;
+            let access_token = body
+                .get("access_token")
+                .and_then(Value::as_str)
+                .ok_or_else(|| Error::Auth("missing access_token in the token response".into()))?
+                .to_string();
+            if let Some(token_type) = body.get("token_type").and_then(Value::as_str)
+                && !token_type.eq_ignore_ascii_case("bearer")
+            {
+                return Err(Error::Auth(
+                    format!("unsupported token type: {token_type}").into(),
+                ));
+            }
+            if let Some(refresh_token) = body.get("refresh_token").and_then(Value::as_str) {
+                state.refresh_token = Some(refresh_token.to_string());
+            }
+            let expires_at = body.get("expires_in").and_then(Value::as_u64).map(|secs| {
+                Instant::now() + Duration::from_secs(secs).saturating_sub(EXPIRY_MARGIN)
+            });
+            Ok(Token {
+                access_token,
+                expires_at,
+            })
+        }
+    }
+
+    impl std::fmt::Debug for OAuth2 {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("OAuth2")
+                .field("token_url", &self.token_url)
+                .field("client_id", &self.client_id)
+                .field("scopes", &self.scopes)
+                .field("origins", &self.origins)
+                .finish_non_exhaustive()
+        }
+    }
+
+    impl Authenticator for OAuth2 {
+        type Err = Error;
+
+        async fn authorize(
+            &self,
+            client: &Client,
+            request: &Request,
+        ) -> Result<Option<HeaderValue>> {
+            if !self.in_scope(request.url()) {
+                return Ok(None);
+            }
+            // Only one token request is sent at a time.
+            let mut state = self.state.lock().await;
+            let valid = state
+                .token
+                .as_ref()
+                .is_some_and(|token| token.expires_at.is_none_or(|at| at > Instant::now()));
+            if !valid {
+                state.token = None;
+                let token = match self.request_token(client, &mut state).await {
+                    Ok(token) => token,
+                    // The refresh token may be expired or revoked.
+                    Err(_) if self.client_credentials && state.refresh_token.is_some() => {
+                        state.refresh_token = None;
+                        self.request_token(client, &mut state).await?
+                    }
+                    Err(e) => return Err(e),
+                };
+                state.token = Some(token);
+            }
+            let token = state.token.as_ref().expect("the token is requested");
+            let mut value = HeaderValue::try_from(format!("Bearer {}", token.access_token))
+                .map_err(|e| Error::Http(e.into()))?;
+            value.set_sensitive(true);
+            Ok(Some(value))
+        }
+
+        async fn challenge(
+            &self,
+            _client: &Client,
+            request: &Request,
+            response: &Response,
+        ) -> Result<bool> {
+            // The rejection may come from another origin after redirects.
+            if !self.in_scope(request.url()) || !self.in_scope(response.url()) {
+                return Ok(false);
+            }
+            let rejected = request
+                .headers()
+                .get(http::header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "));
+            let mut state = self.state.lock().await;
+            // Keep the token if it has been refreshed by another request.
+            if state
+                .token
+                .as_ref()
+                .is_some_and(|token| rejected.is_none_or(|rejected| rejected == token.access_token))
+            {
+                state.token = None;
+            }
+            Ok(true)
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+pub use oauth2::OAuth2;
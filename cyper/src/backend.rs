@@ -1,4 +1,12 @@
-use compio::tls::TlsConnector;
+#[cfg(tls)]
+use {
+    crate::TlsInfo,
+    compio::{
+        io::{AsyncRead, AsyncWrite, util::Splittable},
+        tls::TlsStream,
+    },
+    std::io,
+};
 #[cfg(feature = "rustls")]
 use {compio::rustls, std::sync::Arc};
 
@@ -76,23 +84,34 @@ impl TlsBackend {
         self.accept_invalid_certs
     }
 
+    /// Returns the custom rustls configuration, if any.
+    #[cfg(all(feature = "rustls", feature = "http3"))]
+    pub(crate) fn rustls_config(&self) -> Option<Arc<rustls::ClientConfig>> {
+        match &self.ty {
+            TlsBackendInner::Rustls(config) => config.clone(),
+            _ => None,
+        }
+    }
+
     pub(crate) fn create_connector(&self) -> Result<TlsConnector> {
         match &self.ty {
             TlsBackendInner::None => Err(Error::NoTlsBackend),
             #[cfg(feature = "native-tls")]
-            TlsBackendInner::NativeTls => Ok(TlsConnector::from(
-                compio::tls::native_tls::TlsConnector::builder()
-                    .request_alpns(if cfg!(feature = "http2") {
-                        &["h2", "http/1.1"]
-                    } else {
-                        &["http/1.1"]
-                    })
-                    .danger_accept_invalid_certs(self.accept_invalid_certs)
-                    .build()?,
-            )),
+            TlsBackendInner::NativeTls => {
+                Ok(TlsConnector::Compio(compio::tls::TlsConnector::from(
+                    compio::tls::native_tls::TlsConnector::builder()
+                        .request_alpns(if cfg!(feature = "http2") {
+                            &["h2", "http/1.1"]
+                        } else {
+                            &["http/1.1"]
+                        })
+                        .danger_accept_invalid_certs(self.accept_invalid_certs)
+                        .build()?,
+                )))
+            }
             #[cfg(feature = "rustls")]
             TlsBackendInner::Rustls(config) => {
-                Ok(TlsConnector::from(if let Some(config) = config.clone() {
+                Ok(TlsConnector::Rustls(if let Some(config) = config.clone() {
                     config
                 } else {
                     use compio::rustls::{
@@ -176,3 +195,43 @@ impl TlsBackend {
         }
     }
 }
+
+/// The TLS connector of a client.
+#[derive(Debug, Clone)]
+pub(crate) enum TlsConnector {
+    #[cfg(feature = "native-tls")]
+    Compio(compio::tls::TlsConnector),
+    /// The handshake is performed directly, to record the session details.
+    #[cfg(feature = "rustls")]
+    Rustls(Arc<rustls::ClientConfig>),
+}
+
+#[cfg(tls)]
+impl TlsConnector {
+    /// Connects the stream, assuming the provided domain. The session details
+    /// are returned if the backend provides them.
+    pub(crate) async fn connect<S: Splittable + 'static>(
+        &self,
+        domain: &str,
+        stream: S,
+    ) -> io::Result<(TlsStream<S>, Option<TlsInfo>)>
+    where
+        S::ReadHalf: AsyncRead + Unpin,
+        S::WriteHalf: AsyncWrite + Unpin,
+    {
+        match self {
+            #[cfg(feature = "native-tls")]
+            Self::Compio(connector) => Ok((connector.connect(domain, stream).await?, None)),
+            #[cfg(feature = "rustls")]
+            Self::Rustls(config) => {
+                let name = rustls::pki_types::ServerName::try_from(domain.to_string())
+                    .map_err(io::Error::other)?;
+                let stream = futures_rustls::TlsConnector::from(config.clone())
+                    .connect(name, Box::pin(compio::io::compat::AsyncStream::new(stream)))
+                    .await?;
+                let info = TlsInfo::from_rustls(stream.get_ref().1);
+                Ok((TlsStream::from(stream), Some(info)))
+            }
+        }
+    }
+}
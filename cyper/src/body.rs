@@ -1,7 +1,8 @@
 use std::{
     fmt::Debug,
+    future::Future,
     pin::Pin,
-    task::{Context, Poll},
+    task::{Context, Poll, ready},
 };
 
 use async_stream::try_stream;
@@ -10,12 +11,19 @@ use futures_util::{Stream, StreamExt};
 use hyper::{
     HeaderMap,
     body::{Frame, Incoming, SizeHint},
+    header::{HeaderName, HeaderValue},
 };
 use send_wrapper::SendWrapper;
 
 enum BodyInner {
     Bytes(Bytes),
     Stream(Pin<Box<dyn Stream<Item = crate::Result<Bytes>> + Send>>),
+    WithTrailers(Box<BodyInner>, Option<Trailers>),
+}
+
+enum Trailers {
+    Map(HeaderMap),
+    Future(Pin<Box<dyn Future<Output = crate::Result<HeaderMap>> + Send>>),
 }
 
 impl hyper::body::Body for BodyInner {
@@ -35,13 +43,32 @@ impl hyper::body::Body for BodyInner {
                 }
             }
             Self::Stream(s) => s.poll_next_unpin(cx).map(|b| b.map(|b| b.map(Frame::data))),
+            Self::WithTrailers(body, trailers) => {
+                if let Some(frame) = ready!(Pin::new(body.as_mut()).poll_frame(cx)) {
+                    return Poll::Ready(Some(frame));
+                }
+                let map = match trailers {
+                    Some(Trailers::Map(map)) => std::mem::take(map),
+                    Some(Trailers::Future(fut)) => match ready!(fut.as_mut().poll(cx)) {
+                        Ok(map) => map,
+                        Err(e) => {
+                            *trailers = None;
+                            return Poll::Ready(Some(Err(e)));
+                        }
+                    },
+                    None => return Poll::Ready(None),
+                };
+                *trailers = None;
+                Poll::Ready(Some(Ok(Frame::trailers(map))))
+            }
         }
     }
 
     fn size_hint(&self) -> SizeHint {
         match self {
             Self::Bytes(b) => SizeHint::with_exact(b.len() as _),
-            Self::Stream(_) => SizeHint::default(),
+            // The trailers can only be sent with a chunked body in HTTP/1.1.
+            Self::Stream(_) | Self::WithTrailers(..) => SizeHint::default(),
         }
     }
 }
@@ -51,6 +78,17 @@ impl Debug for BodyInner {
         match self {
             Self::Bytes(b) => f.debug_tuple("Bytes").field(b).finish(),
             Self::Stream(_) => f.debug_struct("Stream").finish_non_exhaustive(),
+            Self::WithTrailers(body, trailers) => f
+                .debug_struct("WithTrailers")
+                .field("body", body)
+                .field(
+                    "trailers",
+                    &match trailers {
+                        Some(Trailers::Map(map)) => Some(map),
+                        _ => None,
+                    },
+                )
+                .finish(),
         }
     }
 }
@@ -92,13 +130,41 @@ impl Body {
         Self(BodyInner::Stream(Box::pin(s)))
     }
 
+    /// Send the trailers after the body.
+    ///
+    /// The names of the trailers are declared in the `Trailer` header of the
+    /// request, unless it is already set. Over HTTP/1.1 the body is sent with
+    /// chunked encoding.
+    pub fn with_trailers(self, trailers: HeaderMap) -> Self {
+        Self(BodyInner::WithTrailers(
+            Box::new(self.0),
+            Some(Trailers::Map(trailers)),
+        ))
+    }
+
+    /// Send the trailers resolved after the body is sent, e.g. a digest of a
+    /// streaming body.
+    ///
+    /// Over HTTP/1.1, the names of the trailers need to be declared in the
+    /// `Trailer` header of the request, or they are not sent.
+    pub fn with_trailers_future(
+        self,
+        trailers: impl Future<Output = crate::Result<HeaderMap>> + Send + 'static,
+    ) -> Self {
+        Self(BodyInner::WithTrailers(
+            Box::new(self.0),
+            Some(Trailers::Future(Box::pin(trailers))),
+        ))
+    }
+
     /// Returns a reference to the internal data of the `Body`.
     ///
-    /// [`None`] is returned, if the underlying data is a stream.
+    /// [`None`] is returned, if the underlying data is a stream, or if there
+    /// are trailers.
     pub fn as_bytes(&self) -> Option<&Bytes> {
         match &self.0 {
             BodyInner::Bytes(b) => Some(b),
-            BodyInner::Stream(_) => None,
+            BodyInner::Stream(_) | BodyInner::WithTrailers(..) => None,
         }
     }
 
@@ -106,18 +172,69 @@ impl Body {
     pub fn content_length(&self) -> Option<u64> {
         match &self.0 {
             BodyInner::Bytes(b) => Some(b.len() as u64),
-            BodyInner::Stream(_) => None,
+            BodyInner::Stream(_) | BodyInner::WithTrailers(..) => None,
         }
     }
 
     /// Try to clone this body.
     ///
-    /// Bytes bodies can be cloned, but stream bodies cannot.
+    /// Bytes bodies can be cloned, but stream bodies and trailers resolved by
+    /// a future cannot.
     pub fn try_clone(&self) -> Option<Self> {
-        match &self.0 {
-            BodyInner::Bytes(b) => Some(Self(BodyInner::Bytes(b.clone()))),
-            BodyInner::Stream(_) => None,
+        fn clone_inner(inner: &BodyInner) -> Option<BodyInner> {
+            match inner {
+                BodyInner::Bytes(b) => Some(BodyInner::Bytes(b.clone())),
+                BodyInner::Stream(_) => None,
+                BodyInner::WithTrailers(body, Some(Trailers::Map(map))) => {
+                    Some(BodyInner::WithTrailers(
+                        Box::new(clone_inner(body)?),
+                        Some(Trailers::Map(map.clone())),
+                    ))
+                }
+                BodyInner::WithTrailers(..) => None,
+            }
+        }
+
+        clone_inner(&self.0).map(Self)
+    }
+
+    /// Copy the data to the capture while it is sent.
+    #[cfg(feature = "har")]
+    pub(crate) fn tee(self, capture: &crate::har::Capture) -> Self {
+        fn tee_inner(inner: BodyInner, capture: &crate::har::Capture) -> BodyInner {
+            match inner {
+                BodyInner::Bytes(b) => {
+                    capture.push(&b);
+                    BodyInner::Bytes(b)
+                }
+                BodyInner::Stream(s) => {
+                    let capture = capture.clone();
+                    BodyInner::Stream(Box::pin(s.inspect(move |chunk| {
+                        if let Ok(chunk) = chunk {
+                            capture.push(chunk);
+                        }
+                    })))
+                }
+                BodyInner::WithTrailers(body, trailers) => {
+                    BodyInner::WithTrailers(Box::new(tee_inner(*body, capture)), trailers)
+                }
+            }
         }
+
+        Self(tee_inner(self.0, capture))
+    }
+
+    /// The value of the `Trailer` header declaring the names of the trailers.
+    pub(crate) fn trailer_header(&self) -> Option<HeaderValue> {
+        let BodyInner::WithTrailers(_, Some(Trailers::Map(map))) = &self.0 else {
+            return None;
+        };
+        let names = map
+            .keys()
+            .map(HeaderName::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+        HeaderValue::from_str(&names).ok()
     }
 }
 
@@ -198,10 +315,20 @@ impl Stream for Body {
 
 pub(crate) enum ResponseBody {
     Incoming(Incoming),
-    #[cfg(feature = "http3")]
-    Blob(Option<crate::Result<Bytes>>),
+    #[cfg(any(feature = "http3", feature = "vcr"))]
+    Blob(Option<crate::Result<Bytes>>, Option<HeaderMap>),
     #[cfg(feature = "__decompression")]
-    Decompressed(Pin<Box<dyn Stream<Item = crate::Result<Bytes>> + Send + Sync>>),
+    Decompressed(Pin<Box<dyn Stream<Item = crate::Result<Frame<Bytes>>> + Send + Sync>>),
+    #[cfg(feature = "har")]
+    Recorded(Box<ResponseBody>, crate::har::Capture),
+}
+
+#[cfg(feature = "har")]
+impl ResponseBody {
+    /// Copy the data to the capture while it is read.
+    pub(crate) fn tee(self, capture: crate::har::Capture) -> Self {
+        Self::Recorded(Box::new(self), capture)
+    }
 }
 
 #[cfg(feature = "__decompression")]
@@ -217,11 +344,11 @@ impl ResponseBody {
                 let new_body = Self::Decompressed(Box::pin(decoder.decode_incoming(incoming)));
                 (true, None, new_body)
             }
-            #[cfg(feature = "http3")]
-            Self::Blob(Some(Ok(bytes))) => {
+            #[cfg(any(feature = "http3", feature = "vcr"))]
+            Self::Blob(Some(Ok(bytes)), trailers) => {
                 let decoded = decoder.decode_all(&bytes);
                 let len = decoded.as_ref().ok().map(|b| b.len());
-                let new_body = Self::Blob(Some(decoded.map_err(|e| e.into())));
+                let new_body = Self::Blob(Some(decoded.map_err(|e| e.into())), trailers);
                 (true, len, new_body)
             }
             _ => (false, None, self),
@@ -279,35 +406,39 @@ impl hyper::body::Body for ResponseBody {
             Self::Incoming(b) => unsafe { Pin::new_unchecked(b) }
                 .poll_frame(cx)
                 .map_err(|e| e.into()),
-            #[cfg(feature = "http3")]
-            Self::Blob(res) => {
-                let res = res.take();
-                match res {
-                    Some(Ok(b)) => {
-                        if b.is_empty() {
-                            Poll::Ready(None)
-                        } else {
-                            Poll::Ready(Some(Ok(Frame::data(b))))
+            #[cfg(any(feature = "http3", feature = "vcr"))]
+            Self::Blob(res, trailers) => match res.take() {
+                Some(Ok(b)) if !b.is_empty() => Poll::Ready(Some(Ok(Frame::data(b)))),
+                Some(Err(e)) => Poll::Ready(Some(Err(e))),
+                _ => Poll::Ready(trailers.take().map(|t| Ok(Frame::trailers(t)))),
+            },
+            #[cfg(feature = "__decompression")]
+            Self::Decompressed(b) => b.as_mut().poll_next(cx),
+            #[cfg(feature = "har")]
+            Self::Recorded(b, capture) => {
+                let frame = ready!(unsafe { Pin::new_unchecked(b.as_mut()) }.poll_frame(cx));
+                match &frame {
+                    Some(Ok(frame)) => {
+                        if let Some(data) = frame.data_ref() {
+                            capture.push(data);
                         }
                     }
-                    Some(Err(e)) => Poll::Ready(Some(Err(e))),
-                    None => Poll::Ready(None),
+                    Some(Err(_)) => {}
+                    None => capture.finish(),
                 }
+                Poll::Ready(frame)
             }
-            #[cfg(feature = "__decompression")]
-            Self::Decompressed(b) => b
-                .as_mut()
-                .poll_next(cx)
-                .map(|b| b.map(|b| b.map(Frame::data))),
         }
     }
 
     fn size_hint(&self) -> SizeHint {
         match self {
             Self::Incoming(b) => b.size_hint(),
-            #[cfg(feature = "http3")]
-            Self::Blob(Some(Ok(b))) => SizeHint::with_exact(b.len() as _),
-            #[cfg(any(feature = "http3", feature = "__decompression"))]
+            #[cfg(any(feature = "http3", feature = "vcr"))]
+            Self::Blob(Some(Ok(b)), _) => SizeHint::with_exact(b.len() as _),
+            #[cfg(feature = "har")]
+            Self::Recorded(b, _) => hyper::body::Body::size_hint(b.as_ref()),
+            #[cfg(any(feature = "http3", feature = "vcr", feature = "__decompression"))]
             _ => SizeHint::default(),
         }
     }
@@ -339,18 +470,24 @@ impl From<ResponseBody> for Body {
     fn from(value: ResponseBody) -> Self {
         match value {
             ResponseBody::Incoming(_) => Self(BodyInner::Stream(Box::pin(value))),
-            #[cfg(feature = "http3")]
-            ResponseBody::Blob(Some(Ok(b))) => Self(BodyInner::Bytes(b)),
-            #[cfg(feature = "http3")]
-            ResponseBody::Blob(Some(res)) => Self(BodyInner::Stream(Box::pin(
+            #[cfg(any(feature = "http3", feature = "vcr"))]
+            ResponseBody::Blob(Some(Ok(b)), None) => Self(BodyInner::Bytes(b)),
+            #[cfg(any(feature = "http3", feature = "vcr"))]
+            ResponseBody::Blob(Some(Ok(b)), Some(trailers)) => {
+                Self(BodyInner::Bytes(b)).with_trailers(trailers)
+            }
+            #[cfg(any(feature = "http3", feature = "vcr"))]
+            ResponseBody::Blob(Some(res), _) => Self(BodyInner::Stream(Box::pin(
                 futures_util::stream::once(std::future::ready(res)),
             ))),
-            #[cfg(feature = "http3")]
-            ResponseBody::Blob(None) => {
+            #[cfg(any(feature = "http3", feature = "vcr"))]
+            ResponseBody::Blob(None, _) => {
                 Self(BodyInner::Stream(Box::pin(futures_util::stream::empty())))
             }
             #[cfg(feature = "__decompression")]
-            ResponseBody::Decompressed(b) => Self(BodyInner::Stream(b)),
+            ResponseBody::Decompressed(_) => Self(BodyInner::Stream(Box::pin(value))),
+            #[cfg(feature = "har")]
+            ResponseBody::Recorded(..) => Self(BodyInner::Stream(Box::pin(value))),
         }
     }
 }
@@ -1,16 +1,21 @@
 #[cfg(feature = "stream")]
 use std::task::{Context, Poll};
+use std::time::Instant;
 
+use compio::io::{AsyncRead, AsyncWrite, util::Splittable};
 use cyper_core::{CompioExecutor, CompioTimer};
 use http::{HeaderValue, header::Entry};
 use hyper::{HeaderMap, Method, StatusCode, Uri};
 use url::Url;
 #[cfg(feature = "cookies")]
-use {compio::bytes::Bytes, cookie_store::CookieStore, std::sync::RwLock};
+use {crate::cookie::CookieStore, std::sync::Arc};
 
 use crate::{
-    Body, Connector, IntoUrl, Request, RequestBuilder, Response, Result, TlsBackend, proxy,
-    redirect,
+    Body, Connector, CustomConnector, IntoUrl, Request, RequestBuilder, Response, Result, Timings,
+    TlsBackend,
+    auth::{Authenticator, SharedAuthenticator},
+    metrics::{Metrics, SharedMetrics},
+    proxy, redirect,
     resolve::{Resolve, SharedResolver},
     sync::shared::Shared,
 };
@@ -22,7 +27,9 @@ pub struct Client {
     #[cfg(feature = "http3")]
     h3_client: crate::http3::Client,
     #[cfg(feature = "http3-altsvc")]
-    h3_hosts: crate::altsvc::KnownHosts,
+    h3_hosts: crate::AltSvcCache,
+    authenticator: Option<SharedAuthenticator>,
+    netrc: Option<Shared<crate::netrc::Netrc>>,
 }
 
 impl Client {
@@ -40,24 +47,91 @@ impl Client {
 
     /// Send a request and wait for a response.
     pub async fn execute(&self, request: Request) -> Result<Response> {
+        self.execute_with(request, async |request| self.execute_auth(request).await)
+            .await
+    }
+
+    /// Send the request with `send`, reporting it to the metrics and the
+    /// tracing span as one request.
+    pub(crate) async fn execute_with(
+        &self,
+        request: Request,
+        send: impl AsyncFnOnce(Request) -> Result<Response>,
+    ) -> Result<Response> {
+        let guard = self
+            .client
+            .metrics
+            .as_ref()
+            .map(|metrics| metrics.start(request.method(), request.url()));
+        #[cfg(feature = "tracing")]
+        let res = {
+            use tracing::Instrument;
+
+            let span = crate::trace::span(&request);
+            let res = send(request).instrument(span.clone()).await;
+            crate::trace::record(&span, &res);
+            res
+        };
+        #[cfg(not(feature = "tracing"))]
+        let res = send(request).await;
+        if let Some(guard) = guard {
+            guard.end(res.as_ref().map(|res| res.status()));
+        }
+        res
+    }
+
+    pub(crate) async fn execute_auth(&self, request: Request) -> Result<Response> {
+        #[cfg(feature = "digest-auth")]
+        let mut request = request;
+        #[cfg(feature = "digest-auth")]
+        if let Some(credentials) = request.take_digest_auth() {
+            // The explicit credentials take precedence over `.netrc`.
+            let client = Client {
+                netrc: None,
+                ..self.clone()
+            };
+            return crate::digest::execute(&client, request, credentials).await;
+        }
+        if let Some(authenticator) = &self.authenticator {
+            let client = Client {
+                authenticator: None,
+                ..self.clone()
+            };
+            return crate::auth::execute(authenticator, &client, request).await;
+        }
+        self.execute_request(request).await
+    }
+
+    pub(crate) async fn execute_request(&self, mut request: Request) -> Result<Response> {
+        let policy = request.take_redirect_policy();
+        #[cfg(all(feature = "ws", feature = "http2"))]
+        let protocol = request.protocol_mut().take();
         let (method, url, headers, body, version) = request.pieces();
 
-        let request = hyper::Request::builder()
+        let builder = hyper::Request::builder()
             .method(method)
             .uri(
                 url.as_str()
                     .parse::<Uri>()
                     .expect("a parsed Url should always be a valid Uri"),
             )
-            .version(version)
-            .body(body)?;
-        self.execute_impl(request, headers, url).await
+            .version(version);
+        #[cfg(all(feature = "ws", feature = "http2"))]
+        let builder = match protocol {
+            Some(protocol) => builder.extension(protocol),
+            None => builder,
+        };
+        let request = builder.body(body)?;
+        self.execute_impl(request, headers, url, policy.as_ref())
+            .await
     }
 
     #[cfg(feature = "stream")]
     async fn execute_tower(&self, request: http::Request<Body>) -> Result<http::Response<Body>> {
         let url = request.uri().to_string().parse::<Url>()?;
-        let resp = self.execute_impl(request, HeaderMap::new(), url).await?;
+        let resp = self
+            .execute_impl(request, HeaderMap::new(), url, None)
+            .await?;
         let http_resp = resp.res;
         let body = resp.body;
         Ok(http::Response::from_parts(
@@ -66,6 +140,11 @@ impl Client {
         ))
     }
 
+    #[cfg(feature = "digest-auth")]
+    pub(crate) fn digest_cache(&self) -> &crate::digest::Cache {
+        &self.client.digest
+    }
+
     #[cfg(feature = "cookies")]
     fn store_response_cookies(&self, url: &Url, res: &Response) {
         if let Some(cookie_store) = &self.client.cookies {
@@ -75,21 +154,29 @@ impl Client {
                 .into_iter()
                 .peekable();
             if values.peek().is_some() {
-                let mut cookie_store = cookie_store.write().unwrap();
-                cookie_store.store_response_cookies(
-                    values.filter_map(|val| std::str::from_utf8(val.as_bytes()).ok()?.parse().ok()),
-                    url,
-                );
+                cookie_store.set_cookies(&mut values, url);
             }
         }
     }
 
-    async fn execute_impl(
+    pub(crate) async fn execute_impl(
         &self,
         mut request: http::Request<Body>,
         mut headers: HeaderMap<HeaderValue>,
-        url: Url,
+        mut url: Url,
+        redirect_policy: Option<&redirect::Policy>,
     ) -> Result<Response> {
+        let redirect_policy = redirect_policy.unwrap_or(&self.client.redirect_policy);
+
+        if let Some(hsts) = &self.client.hsts
+            && hsts.upgrade(&mut url)
+        {
+            *request.uri_mut() = url
+                .as_str()
+                .parse::<Uri>()
+                .expect("a parsed Url should always be a valid Uri");
+        }
+
         for (key, value) in &self.client.headers {
             if let Entry::Vacant(entry) = headers.entry(key) {
                 entry.insert(value.clone());
@@ -105,6 +192,15 @@ impl Client {
             }
         }
 
+        self.netrc_auth(&url, &mut headers);
+
+        // HTTP/1.1 only sends the trailers declared in the `Trailer` header.
+        if !headers.contains_key(http::header::TRAILER)
+            && let Some(trailer) = request.body().trailer_header()
+        {
+            headers.insert(http::header::TRAILER, trailer);
+        }
+
         *request.headers_mut() = headers;
 
         // Save state for potential redirects before request is consumed
@@ -113,19 +209,30 @@ impl Client {
         let mut body_backup = request.body().try_clone();
         let mut redirect_headers = request.headers().clone();
 
+        let start = Instant::now();
+        let mut sent = start;
         let mut res = self.send_request(request, &url).await?;
+        self.record_pool(&url, sent, &res);
 
         // Redirect loop
         let mut current_url = url;
         let mut prev_urls: Vec<Url> = Vec::new();
+        let mut history = Vec::new();
 
-        loop {
+        let mut res = loop {
             #[cfg(feature = "cookies")]
             self.store_response_cookies(&current_url, &res);
 
+            if self.client.hsts_record
+                && let Some(hsts) = &self.client.hsts
+                && let Some(value) = res.headers().get(http::header::STRICT_TRANSPORT_SECURITY)
+            {
+                hsts.update(&current_url, value);
+            }
+
             let status = res.status();
             if !status.is_redirection() {
-                return Ok(res);
+                break res;
             }
 
             let Some(location) = res
@@ -133,29 +240,44 @@ impl Client {
                 .get(http::header::LOCATION)
                 .and_then(|v| v.to_str().ok())
             else {
-                return Ok(res);
+                break res;
             };
 
-            let Ok(next_url) = current_url.join(location) else {
-                return Ok(res);
+            let Ok(mut next_url) = current_url.join(location) else {
+                break res;
             };
+            if let Some(hsts) = &self.client.hsts {
+                hsts.upgrade(&mut next_url);
+            }
 
             prev_urls.push(current_url);
 
-            let action = self
-                .client
-                .redirect_policy
-                .check(status, &next_url, &prev_urls);
+            let action = redirect_policy.check(redirect::Attempt::new(
+                status,
+                &next_url,
+                &prev_urls,
+                &method,
+                &redirect_headers,
+                res.headers(),
+            ));
 
             current_url = next_url;
 
             match action {
-                redirect::ActionKind::Follow => {
+                redirect::ActionKind::Follow(rewrite) => {
+                    let hop = redirect::Hop::new(
+                        method.clone(),
+                        prev_urls.last().expect("the URL is pushed").clone(),
+                        status,
+                        res.headers(),
+                        sent.elapsed(),
+                    );
                     redirect::remove_sensitive_headers(
                         &mut redirect_headers,
                         &current_url,
                         &prev_urls,
                     );
+                    self.netrc_auth(&current_url, &mut redirect_headers);
 
                     if self.client.referer
                         && let Some(previous_url) = prev_urls.last()
@@ -168,6 +290,11 @@ impl Client {
                     }
 
                     match status {
+                        StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND if rewrite.keep_body => {
+                            if body_backup.is_none() {
+                                break res;
+                            }
+                        }
                         StatusCode::MOVED_PERMANENTLY
                         | StatusCode::FOUND
                         | StatusCode::SEE_OTHER => {
@@ -178,14 +305,19 @@ impl Client {
                             redirect_headers.remove(http::header::CONTENT_LENGTH);
                             redirect_headers.remove(http::header::CONTENT_TYPE);
                             redirect_headers.remove(http::header::TRANSFER_ENCODING);
+                            redirect_headers.remove("content-digest");
                         }
                         StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => {
                             if body_backup.is_none() {
-                                return Ok(res);
+                                break res;
                             }
                         }
-                        _ => return Ok(res),
+                        _ => break res,
                     }
+                    if let Some(m) = &rewrite.method {
+                        method = m.clone();
+                    }
+                    rewrite.apply_headers(&mut redirect_headers);
 
                     let send_body = body_backup
                         .as_ref()
@@ -205,11 +337,27 @@ impl Client {
                     let mut req = req;
                     *req.headers_mut() = redirect_headers.clone();
 
+                    history.push(hop);
+                    sent = Instant::now();
                     res = self.send_request(req, &current_url).await?;
+                    self.record_pool(&current_url, sent, &res);
                 }
-                redirect::ActionKind::Stop => return Ok(res),
+                redirect::ActionKind::Stop => break res,
                 redirect::ActionKind::Error(e) => return Err(crate::Error::Redirect(e)),
             }
+        };
+        res.redirect_history = history;
+        let timings = Timings::new(start, sent, &res);
+        res.extensions_mut().insert(timings);
+        Ok(res)
+    }
+
+    /// Notify the metrics whether the response of the request sent at `start`
+    /// is received on a pooled connection.
+    fn record_pool(&self, url: &Url, start: Instant, res: &Response) {
+        if let Some(metrics) = &self.client.metrics {
+            let info = res.extensions().get::<crate::ConnectionInfo>();
+            metrics.pool(url.host_str().unwrap_or_default(), start, info);
         }
     }
 
@@ -220,42 +368,74 @@ impl Client {
         self.proxy_custom_headers(&uri, request.headers_mut());
         self.accept_header(request.headers_mut());
 
-        #[cfg(feature = "http3")]
+        #[cfg(feature = "tracing")]
+        if let Some(propagator) = &self.client.propagator {
+            propagator.inject(request.headers_mut());
+        }
+
+        #[cfg(feature = "signatures")]
+        if let Some(signer) = url
+            .host_str()
+            .and_then(|host| self.client.signers.get(host))
         {
-            #[cfg(feature = "http3-altsvc")]
-            let host = url.host_str().expect("a parsed Url should have host");
+            signer.sign(&mut request, url)?;
+        }
 
-            #[allow(unused_mut)]
-            let mut should_http3 = request.version() == http::Version::HTTP_3;
+        #[cfg(feature = "har")]
+        let recording = self
+            .client
+            .recorder
+            .as_ref()
+            .map(|recorder| recorder.record(&mut request, url));
 
-            #[cfg(feature = "http3-altsvc")]
-            if url.port().is_none() && self.h3_hosts.find(host) {
-                if let Ok(value) = http::HeaderValue::from_bytes(host.as_bytes()) {
-                    request.headers_mut().insert("Alt-Used", value);
-                }
-                should_http3 = true;
-            }
+        let res = self.dispatch(request, url).await;
+
+        #[cfg(feature = "har")]
+        let res = match recording {
+            Some(recording) => recording.finish(res),
+            None => res,
+        };
+        res
+    }
 
-            let res = if should_http3 {
-                self.h3_client.request(request, url.clone()).await?
+    /// Send the request with the cassette, or to the network.
+    async fn dispatch(&self, request: http::Request<Body>, url: &Url) -> Result<Response> {
+        #[cfg(feature = "vcr")]
+        if let Some(cassette) = &self.client.cassette
+            && !crate::util::is_upgrade(&request)
+        {
+            return cassette
+                .play(request, url, |request| self.send_network(request, url))
+                .await;
+        }
+        self.send_network(request, url).await
+    }
+
+    /// Send the request over HTTP/3 or TCP.
+    async fn send_network(&self, request: http::Request<Body>, url: &Url) -> Result<Response> {
+        #[cfg(feature = "http3")]
+        {
+            let uri = request.uri().clone();
+            let res = if request.version() == http::Version::HTTP_3 {
+                let route = self.h3_client.route(&uri, None, Vec::new())?;
+                self.h3_client.request(route, request, url.clone()).await?
+            } else if !crate::util::is_upgrade(&request)
+                && let Some(route) = self.discover_http3(&uri, url).await
+            {
+                self.send_h3_with_fallback(route, request, url).await?
             } else {
                 self.send_h1h2_request(request, url).await?
             };
+            // Alternative services are only used for secure origins.
             #[cfg(feature = "http3-altsvc")]
-            if let Some(alt_svc) = res.headers().get(http::header::ALT_SVC)
+            if url.scheme() == "https"
+                && let Some(host) = url.host_str()
+                && let Some(port) = url.port_or_known_default()
+                && let Some(alt_svc) = res.headers().get(http::header::ALT_SVC)
                 && let Ok(alt_svc) = std::str::from_utf8(alt_svc.as_bytes())
                 && let Ok(services) = crate::altsvc::parse(alt_svc)
             {
-                match services {
-                    crate::altsvc::AltService::Clear => self.h3_hosts.clear(host),
-                    crate::altsvc::AltService::Services(services) => {
-                        for srv in services {
-                            if self.h3_hosts.try_insert(host, &srv) {
-                                break;
-                            }
-                        }
-                    }
-                }
+                self.h3_hosts.update(host, port, services);
             }
             Ok(res)
         }
@@ -265,6 +445,123 @@ impl Client {
         }
     }
 
+    /// Find the HTTP/3 route of a secure origin from the Alt-Svc cache or the
+    /// HTTPS DNS record, skipping the endpoints where QUIC is broken.
+    #[cfg(feature = "http3")]
+    async fn discover_http3(&self, uri: &Uri, url: &Url) -> Option<crate::http3::Route> {
+        if url.scheme() != "https" {
+            return None;
+        }
+        let host = url.host_str()?;
+        let port = url.port_or_known_default()?;
+
+        #[cfg(feature = "http3-altsvc")]
+        {
+            let route = |alt: &crate::altsvc::Alternative| {
+                self.h3_client
+                    .route(uri, alt.endpoint(host, port), Vec::new())
+                    .ok()
+            };
+            if let Some(alt) = self.h3_hosts.find(host, port, |alt| {
+                route(alt).is_some_and(|route| !self.h3_client.is_broken(&route))
+            }) && let Some(mut route) = route(&alt)
+            {
+                route.alt_used = HeaderValue::from_str(&alt.alt_used()).ok();
+                return Some(route);
+            }
+        }
+
+        // The HTTPS DNS record tells if the origin supports HTTP/3 before
+        // the first request.
+        let record = self.h3_client.discover(uri).await?;
+        let target = record.target.as_deref().unwrap_or(host);
+        let target_port = record.port.unwrap_or(port);
+        let alt = if target != host || target_port != port {
+            let authority =
+                http::uri::Authority::try_from(format!("{target}:{target_port}")).ok()?;
+            Some(crate::http3::AltEndpoint {
+                authority,
+                draft29: false,
+            })
+        } else {
+            None
+        };
+        let route = self.h3_client.route(uri, alt, record.addrs).ok()?;
+        (!self.h3_client.is_broken(&route)).then_some(route)
+    }
+
+    /// Send the request over HTTP/3, falling back to TCP if QUIC fails or
+    /// isn't connected in time.
+    #[cfg(feature = "http3")]
+    async fn send_h3_with_fallback(
+        &self,
+        route: crate::http3::Route,
+        request: http::Request<Body>,
+        url: &Url,
+    ) -> Result<Response> {
+        use std::pin::pin;
+
+        use compio::runtime::ResumeUnwind;
+        use futures_util::future::{Either, select};
+
+        // The handshake runs in a separate task, so that the connection could
+        // still be pooled if TCP wins.
+        let mut quic = compio::runtime::spawn({
+            let h3_client = self.h3_client.clone();
+            let route = route.clone();
+            let method = request.method().clone();
+            async move { h3_client.connect(&route, &method).await }
+        });
+        let delay = pin!(compio::time::sleep(self.h3_client.fallback_delay()));
+        let quic_res = match select(&mut quic, delay).await {
+            Either::Left((res, _)) => res,
+            Either::Right(_) => {
+                // The pool may send the request on an open connection right
+                // away.
+                let uri = request.uri().clone();
+                if self.client.connector.is_connected(&uri) {
+                    quic.detach();
+                    self.record_fallback(url);
+                    return self.send_h1h2_request(request, url).await;
+                }
+                // Only the connections race, and the request is sent once on
+                // the winner.
+                let tcp = pin!(self.client.connector.preconnect(uri.clone()));
+                match select(&mut quic, tcp).await {
+                    Either::Left((res, _)) => res,
+                    Either::Right((Ok(()), _)) => {
+                        quic.detach();
+                        self.record_fallback(url);
+                        let res = self.send_h1h2_request(request, url).await;
+                        // A pooled connection may have been used instead.
+                        self.client.connector.discard(&uri);
+                        return res;
+                    }
+                    // Wait for QUIC if TCP fails first.
+                    Either::Right((Err(_), _)) => quic.await,
+                }
+            }
+        };
+        match quic_res.resume_unwind() {
+            Some(Ok(pooled)) => {
+                self.h3_client
+                    .send(&route, pooled, request, url.clone())
+                    .await
+            }
+            _ => {
+                self.record_fallback(url);
+                self.send_h1h2_request(request, url).await
+            }
+        }
+    }
+
+    #[cfg(feature = "http3")]
+    fn record_fallback(&self, url: &Url) {
+        if let Some(metrics) = &self.client.metrics {
+            metrics.http3_fallback(url.host_str().unwrap_or_default());
+        }
+    }
+
     async fn send_h1h2_request(&self, request: http::Request<Body>, url: &Url) -> Result<Response> {
         let res = self.client.client.request(request).await?;
         Ok(Response::new(res, url.clone()))
@@ -279,17 +576,7 @@ impl Client {
 
     #[cfg(feature = "cookies")]
     fn cookie_value_impl(&self, url: &Url) -> Option<HeaderValue> {
-        let cookie_store = self.client.cookies.as_ref()?.read().unwrap();
-        let value = cookie_store
-            .get_request_values(url)
-            .map(|(name, value)| format!("{name}={value}"))
-            .collect::<Vec<_>>()
-            .join("; ");
-        if !value.is_empty() {
-            Some(HeaderValue::from_maybe_shared(Bytes::from(value)).ok()?)
-        } else {
-            None
-        }
+        self.client.cookies.as_ref()?.cookies(url)
     }
 
     fn proxy_auth(&self, dst: &Uri, headers: &mut HeaderMap) {
@@ -335,6 +622,15 @@ impl Client {
         }
     }
 
+    fn netrc_auth(&self, url: &Url, headers: &mut HeaderMap) {
+        if let Some(netrc) = &self.netrc
+            && !headers.contains_key(http::header::AUTHORIZATION)
+            && let Some(value) = netrc.authorization(url)
+        {
+            headers.insert(http::header::AUTHORIZATION, value);
+        }
+    }
+
     fn accept_header(&self, headers: &mut HeaderMap) {
         if !headers.contains_key(http::header::ACCEPT) {
             headers.insert(http::header::ACCEPT, http::HeaderValue::from_static("*/*"));
@@ -349,6 +645,17 @@ impl Client {
         }
     }
 
+    /// Get the cache of alternative services advertised by `Alt-Svc`.
+    #[cfg(feature = "http3-altsvc")]
+    pub fn alt_svc_cache(&self) -> &crate::AltSvcCache {
+        &self.h3_hosts
+    }
+
+    /// Get the store of the known HSTS hosts, if HSTS is enabled.
+    pub fn hsts_store(&self) -> Option<&crate::HstsStore> {
+        self.client.hsts.as_ref()
+    }
+
     /// Send a request with method and url.
     pub fn request<U: IntoUrl>(&self, method: Method, url: U) -> Result<RequestBuilder> {
         Ok(RequestBuilder::new(
@@ -391,6 +698,8 @@ impl Client {
 #[derive(Debug)]
 struct ClientInner {
     client: hyper_util::client::legacy::Client<Connector, Body>,
+    #[cfg(feature = "http3")]
+    connector: Connector,
     headers: HeaderMap,
     redirect_policy: redirect::Policy,
     referer: bool,
@@ -398,8 +707,21 @@ struct ClientInner {
     proxies_maybe_http_auth: bool,
     proxies_maybe_http_custom_headers: bool,
     #[cfg(feature = "cookies")]
-    cookies: Option<RwLock<CookieStore>>,
+    cookies: Option<Arc<dyn CookieStore>>,
+    #[cfg(feature = "digest-auth")]
+    digest: crate::digest::Cache,
+    #[cfg(feature = "signatures")]
+    signers: std::collections::HashMap<String, crate::signatures::Signer>,
+    hsts: Option<crate::HstsStore>,
+    hsts_record: bool,
     accepts: Option<HeaderValue>,
+    #[cfg(feature = "tracing")]
+    propagator: Option<crate::trace::Propagator>,
+    metrics: Option<SharedMetrics>,
+    #[cfg(feature = "har")]
+    recorder: Option<crate::har::HarRecorder>,
+    #[cfg(feature = "vcr")]
+    cassette: Option<crate::vcr::Cassette>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -414,6 +736,9 @@ struct Accepts {
     deflate: bool,
 }
 
+// Without decompression the struct is empty, and the impl is trivially
+// derivable.
+#[cfg_attr(not(feature = "__decompression"), allow(clippy::derivable_impls))]
 impl Default for Accepts {
     fn default() -> Accepts {
         Accepts {
@@ -463,14 +788,31 @@ pub struct ClientBuilder {
     tls: TlsBackend,
     headers: HeaderMap,
     resolver: Option<SharedResolver>,
+    authenticator: Option<SharedAuthenticator>,
+    #[cfg(feature = "signatures")]
+    signers: std::collections::HashMap<String, crate::signatures::Signer>,
     redirect_policy: redirect::Policy,
     referer: bool,
+    netrc: bool,
+    hsts: Option<crate::HstsStore>,
     proxies: Vec<proxy::Proxy>,
     no_proxy: bool,
     #[cfg(feature = "cookies")]
-    cookies: Option<RwLock<CookieStore>>,
+    cookies: Option<Arc<dyn CookieStore>>,
     hickory_dns: bool,
     http2_only: bool,
+    #[cfg(feature = "http3")]
+    http3: crate::Http3Options,
+    #[cfg(feature = "http3-altsvc")]
+    alt_svc_cache: crate::AltSvcCache,
+    #[cfg(feature = "tracing")]
+    propagator: Option<crate::trace::Propagator>,
+    metrics: Option<SharedMetrics>,
+    connector: Option<CustomConnector>,
+    #[cfg(feature = "har")]
+    recorder: Option<crate::har::HarRecorder>,
+    #[cfg(feature = "vcr")]
+    cassette: Option<crate::vcr::Cassette>,
 }
 
 impl Default for ClientBuilder {
@@ -487,14 +829,31 @@ impl ClientBuilder {
             headers: HeaderMap::new(),
             tls: TlsBackend::default(),
             resolver: None,
+            authenticator: None,
+            #[cfg(feature = "signatures")]
+            signers: std::collections::HashMap::new(),
             redirect_policy: redirect::Policy::default(),
             referer: true,
+            netrc: false,
+            hsts: None,
             proxies: Vec::new(),
             no_proxy: false,
             #[cfg(feature = "cookies")]
             cookies: None,
             hickory_dns: cfg!(feature = "hickory-dns"),
             http2_only: false,
+            #[cfg(feature = "http3")]
+            http3: crate::Http3Options::default(),
+            #[cfg(feature = "http3-altsvc")]
+            alt_svc_cache: crate::AltSvcCache::new(),
+            #[cfg(feature = "tracing")]
+            propagator: None,
+            metrics: None,
+            connector: None,
+            #[cfg(feature = "har")]
+            recorder: None,
+            #[cfg(feature = "vcr")]
+            cassette: None,
         }
     }
 
@@ -524,19 +883,29 @@ impl ClientBuilder {
                 true => unreachable!("hickory-dns shouldn't be enabled unless the feature is"),
             },
         };
+        let netrc = if self.netrc {
+            Some(Shared::new(crate::netrc::Netrc::load()?))
+        } else {
+            None
+        };
         let mut builder = hyper_util::client::legacy::Client::builder(CompioExecutor);
         builder.set_host(true).timer(CompioTimer);
         #[cfg(feature = "http2")]
         {
             builder.http2_only(self.http2_only);
         }
-        let client = builder.build(Connector::new(tls, resolver.clone(), proxies.clone()));
+        let connector = Connector::new(tls, resolver.clone(), proxies.clone())
+            .with_metrics(self.metrics.clone())
+            .with_custom(self.connector);
+        let client = builder.build(connector.clone());
 
         let proxies_maybe_http_auth = proxies.iter().any(|p| p.maybe_has_http_auth());
         let proxies_maybe_http_custom_headers =
             proxies.iter().any(|p| p.maybe_has_http_custom_headers());
         let client_ref = ClientInner {
             client,
+            #[cfg(feature = "http3")]
+            connector,
             headers: self.headers,
             redirect_policy: self.redirect_policy,
             referer: self.referer,
@@ -545,14 +914,36 @@ impl ClientBuilder {
             proxies_maybe_http_custom_headers,
             #[cfg(feature = "cookies")]
             cookies: self.cookies,
+            #[cfg(feature = "digest-auth")]
+            digest: crate::digest::Cache::default(),
+            #[cfg(feature = "signatures")]
+            signers: self.signers,
+            hsts: self.hsts,
+            // The header is only trusted over verified connections.
+            hsts_record: !accept_invalid_certs,
             accepts: self.accepts.header_value(),
+            #[cfg(feature = "tracing")]
+            propagator: self.propagator,
+            metrics: self.metrics.clone(),
+            #[cfg(feature = "har")]
+            recorder: self.recorder,
+            #[cfg(feature = "vcr")]
+            cassette: self.cassette,
         };
         Ok(Client {
             client: Shared::new(client_ref),
             #[cfg(feature = "http3")]
-            h3_client: crate::http3::Client::new(accept_invalid_certs, resolver),
+            h3_client: crate::http3::Client::new(
+                self.http3,
+                self.tls.rustls_config(),
+                accept_invalid_certs,
+                resolver,
+                self.metrics,
+            ),
             #[cfg(feature = "http3-altsvc")]
-            h3_hosts: crate::altsvc::KnownHosts::default(),
+            h3_hosts: self.alt_svc_cache,
+            authenticator: self.authenticator,
+            netrc,
         })
     }
 
@@ -570,6 +961,24 @@ impl ClientBuilder {
         self
     }
 
+    /// Set the options of HTTP/3 connections.
+    #[cfg(feature = "http3")]
+    pub fn http3_options(mut self, options: crate::Http3Options) -> Self {
+        self.http3 = options;
+        self
+    }
+
+    /// Set the cache of alternative services advertised by `Alt-Svc`.
+    ///
+    /// The cache could be shared between clients, and loaded from a file with
+    /// [`AltSvcCache::load`](crate::AltSvcCache::load) to start on HTTP/3
+    /// directly.
+    #[cfg(feature = "http3-altsvc")]
+    pub fn alt_svc_cache(mut self, cache: crate::AltSvcCache) -> Self {
+        self.alt_svc_cache = cache;
+        self
+    }
+
     /// Force using the native TLS backend.
     #[cfg(feature = "native-tls")]
     pub fn use_native_tls(mut self) -> Self {
@@ -613,22 +1022,92 @@ impl ClientBuilder {
         self
     }
 
+    /// Enable or disable the credentials from the `.netrc` file.
+    ///
+    /// The file is read from `$NETRC`, or `~/.netrc`, when the client is
+    /// built. Requests without an `Authorization` header are sent with the
+    /// basic credentials of the matching `machine`, or the `default` entry.
+    /// The credentials are looked up again for each redirect hop, and
+    /// removed on cross-host redirects like any `Authorization` header.
+    ///
+    /// Default is `false`.
+    pub fn netrc(mut self, enable: bool) -> Self {
+        self.netrc = enable;
+        self
+    }
+
+    /// Enable or disable HTTP Strict Transport Security (RFC 6797).
+    ///
+    /// When enabled, the hosts are recorded from the
+    /// `Strict-Transport-Security` header of HTTPS responses, and the later
+    /// `http` requests and redirects to them are upgraded to `https` before
+    /// connecting. The header is ignored if invalid certificates are
+    /// accepted.
+    ///
+    /// Default is `false`.
+    pub fn hsts(mut self, enable: bool) -> Self {
+        if !enable {
+            self.hsts = None;
+        } else if self.hsts.is_none() {
+            self.hsts = Some(crate::HstsStore::new());
+        }
+        self
+    }
+
+    /// Enable HSTS with the store, which could be preloaded, shared between
+    /// clients, or loaded from a file with
+    /// [`HstsStore::load`](crate::HstsStore::load).
+    pub fn hsts_store(mut self, store: crate::HstsStore) -> Self {
+        self.hsts = Some(store);
+        self
+    }
+
+    /// Set the function providing the trace context of the current span,
+    /// which is injected into the `traceparent` and `tracestate` headers of
+    /// each request, unless they are already set.
+    ///
+    /// It is called in the span of [`Client::execute`], so with
+    /// `tracing-opentelemetry`, the context could be taken from
+    /// `Span::current().context()`.
+    #[cfg(feature = "tracing")]
+    pub fn trace_context(
+        mut self,
+        f: impl Fn() -> Option<crate::trace::TraceContext> + Send + Sync + 'static,
+    ) -> Self {
+        self.propagator = Some(crate::trace::Propagator::new(f));
+        self
+    }
+
     /// Enable a persistent cookie store for the client.
     ///
     /// Cookies received in responses will be preserved and included in
     /// additional requests.
     ///
-    /// By default, no cookie store is used.
+    /// By default, no cookie store is used. Enabling it sets a new
+    /// [`Jar`](crate::cookie::Jar), while disabling it removes any store set
+    /// with [`cookie_provider`](Self::cookie_provider).
     #[cfg(feature = "cookies")]
     pub fn cookie_store(mut self, enable: bool) -> Self {
         if enable {
-            self.cookies = Some(RwLock::new(CookieStore::default()))
+            self.cookies = Some(Arc::new(crate::cookie::Jar::default()))
         } else {
             self.cookies = None;
         }
         self
     }
 
+    /// Set the persistent cookie store for the client.
+    ///
+    /// The store could be kept to inspect the cookies, to seed them, or to
+    /// save them when the client is done.
+    ///
+    /// By default, no cookie store is used.
+    #[cfg(feature = "cookies")]
+    pub fn cookie_provider<C: CookieStore + 'static>(mut self, cookie_store: Arc<C>) -> Self {
+        self.cookies = Some(cookie_store as _);
+        self
+    }
+
     /// Add a `Proxy` to the list of proxies the `Client` will use.
     ///
     /// # Note
@@ -651,6 +1130,99 @@ impl ClientBuilder {
         self
     }
 
+    /// Set the [`Authenticator`] providing the credentials of the requests.
+    ///
+    /// Requests with an `Authorization` header, or with
+    /// [`RequestBuilder::digest_auth`], are sent without the authenticator.
+    pub fn authenticator<A: Authenticator + 'static>(mut self, authenticator: A) -> Self {
+        self.authenticator = Some(SharedAuthenticator::new(authenticator));
+        self
+    }
+
+    /// Set the [`Metrics`] notified of the requests and connections, e.g.
+    /// [`PrometheusMetrics`](crate::metrics::PrometheusMetrics).
+    pub fn metrics<M: Metrics + 'static>(mut self, metrics: M) -> Self {
+        self.metrics = Some(SharedMetrics::new(metrics));
+        self
+    }
+
+    /// Connect with a custom connector instead of TCP, e.g. over a tunnel, or
+    /// to an in-memory server with the streams of [`duplex`].
+    ///
+    /// The connector is called with the URI of the destination, and TLS is
+    /// negotiated over the returned stream for `https`. The proxies are not
+    /// used, and the HTTP/3 requests are still sent over QUIC.
+    ///
+    /// ```no_run
+    /// use cyper::{Client, duplex};
+    /// use http::Uri;
+    ///
+    /// # fn run() -> cyper::Result<()> {
+    /// let client = Client::builder()
+    ///     .connector(tower::service_fn(|_uri: Uri| async move {
+    ///         let (client, server) = duplex(64 * 1024);
+    ///         // Serve the server stream, e.g. with `cyper_axum::serve`.
+    ///         # drop(server);
+    ///         Ok::<_, std::io::Error>(client)
+    ///     }))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`duplex`]: crate::duplex
+    pub fn connector<C>(mut self, connector: C) -> Self
+    where
+        C: tower_service::Service<Uri> + Clone + 'static,
+        C::Response: Splittable + Unpin + 'static,
+        <C::Response as Splittable>::ReadHalf: AsyncRead + Unpin,
+        <C::Response as Splittable>::WriteHalf: AsyncWrite + Unpin,
+        C::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Future: 'static,
+    {
+        self.connector = Some(CustomConnector::new(connector));
+        self
+    }
+
+    /// Record the requests and responses to the [`HarRecorder`], e.g. to
+    /// export a HAR file for troubleshooting.
+    ///
+    /// [`HarRecorder`]: crate::har::HarRecorder
+    #[cfg(feature = "har")]
+    pub fn recorder(mut self, recorder: crate::har::HarRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Record the exchanges to the [`Cassette`], or replay them from it
+    /// without touching the network, depending on its [`Mode`].
+    ///
+    /// The cassette is used for each hop, so the redirects, cookies and
+    /// authentication behave as if the responses were from the network.
+    ///
+    /// [`Cassette`]: crate::vcr::Cassette
+    /// [`Mode`]: crate::vcr::Mode
+    #[cfg(feature = "vcr")]
+    pub fn cassette(mut self, cassette: crate::vcr::Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Sign the requests to the host with HTTP Message Signatures (RFC 9421).
+    ///
+    /// The requests are signed right before being sent, after all headers
+    /// are set, and signed again for each redirect hop to the host.
+    #[cfg(feature = "signatures")]
+    pub fn sign_requests(
+        mut self,
+        host: impl Into<String>,
+        signer: crate::signatures::Signer,
+    ) -> Self {
+        self.signers
+            .insert(host.into().to_ascii_lowercase(), signer);
+        self
+    }
+
     /// Set the custom resolver for DNS resolution.
     pub fn custom_resolver<R: Resolve + 'static>(mut self, resolver: R) -> Self {
         self.resolver = Some(SharedResolver::new(resolver));
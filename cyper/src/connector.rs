@@ -1,17 +1,26 @@
 use std::{
-    future::Future,
+    fmt::Debug,
+    future::{Future, poll_fn},
+    io,
     pin::Pin,
     task::{Context, Poll},
 };
 
-use compio::tls::TlsConnector;
+use compio::io::{AsyncRead, AsyncWrite, util::Splittable};
 use futures_util::TryFutureExt;
 use hyper::Uri;
 use send_wrapper::SendWrapper;
 use tower_service::Service;
+#[cfg(feature = "http3")]
+use {
+    crate::sync::mutex_blocking::{Mutex, MutexGuard},
+    http::uri::{Authority, Scheme},
+    std::collections::HashMap,
+};
 
 use crate::{
-    HttpStream, WrappedHttpStream,
+    HttpStream, TlsConnector, WrappedHttpStream,
+    metrics::SharedMetrics,
     proxy::{self, Intercepted},
     resolve::SharedResolver,
     sync::shared::Shared,
@@ -25,6 +34,10 @@ use crate::{
 pub struct Connector {
     inner: HttpsConnector,
     proxies: SendWrapper<Shared<Vec<proxy::Matcher>>>,
+    metrics: Option<SharedMetrics>,
+    custom: Option<CustomConnector>,
+    #[cfg(feature = "http3")]
+    destinations: Destinations,
 }
 
 impl Connector {
@@ -37,20 +50,65 @@ impl Connector {
         Self {
             inner: HttpsConnector::new(tls, resolver),
             proxies: SendWrapper::new(proxies),
+            metrics: None,
+            custom: None,
+            #[cfg(feature = "http3")]
+            destinations: Destinations::new(),
         }
     }
-}
 
-impl Service<Uri> for Connector {
-    type Error = crate::Error;
-    type Future = Pin<Box<dyn Future<Output = crate::Result<Self::Response>> + Send>>;
-    type Response = WrappedHttpStream;
+    /// Connect with the custom connector instead.
+    pub(crate) fn with_custom(mut self, custom: Option<CustomConnector>) -> Self {
+        self.custom = custom;
+        self
+    }
 
-    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
+    /// Notify the metrics of the connections.
+    pub(crate) fn with_metrics(mut self, metrics: Option<SharedMetrics>) -> Self {
+        self.metrics = metrics;
+        self
     }
 
-    fn call(&mut self, dst: Uri) -> Self::Future {
+    /// Connect to the destination ahead of the request, keeping the stream
+    /// for the next connection to it.
+    #[cfg(feature = "http3")]
+    pub(crate) async fn preconnect(&self, dst: Uri) -> crate::Result<()> {
+        let stream = self.clone().call(dst.clone()).await?;
+        let replaced = self
+            .destinations
+            .lock()
+            .preconnected
+            .insert(Destinations::key(&dst), stream);
+        // The stream is dropped without the lock, which its guard takes.
+        drop(replaced);
+        Ok(())
+    }
+
+    /// Drop the stream connected ahead to the destination if it isn't used.
+    #[cfg(feature = "http3")]
+    pub(crate) fn discard(&self, dst: &Uri) {
+        let stream = self
+            .destinations
+            .lock()
+            .preconnected
+            .remove(&Destinations::key(dst));
+        drop(stream);
+    }
+
+    /// Whether a connection to the destination is open, which the pool of
+    /// the client may reuse.
+    #[cfg(feature = "http3")]
+    pub(crate) fn is_connected(&self, dst: &Uri) -> bool {
+        self.destinations
+            .lock()
+            .open
+            .contains_key(&Destinations::key(dst))
+    }
+
+    fn connect(&mut self, dst: Uri) -> <Self as Service<Uri>>::Future {
+        if let Some(custom) = &self.custom {
+            return Box::pin(SendWrapper::new((custom.0)(dst, self.inner.tls.clone())));
+        }
         for matcher in self.proxies.iter() {
             if let Some(intercepted) = matcher.intercept(&dst) {
                 return Box::pin(SendWrapper::new(connect_via_proxy(
@@ -65,6 +123,151 @@ impl Service<Uri> for Connector {
     }
 }
 
+impl Service<Uri> for Connector {
+    type Error = crate::Error;
+    type Future = Pin<Box<dyn Future<Output = crate::Result<Self::Response>> + Send>>;
+    type Response = WrappedHttpStream;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        #[cfg(feature = "http3")]
+        let destinations = self.destinations.clone();
+        #[cfg(feature = "http3")]
+        let key = Destinations::key(&dst);
+        #[cfg(feature = "http3")]
+        if let Some(stream) = destinations.lock().preconnected.remove(&key) {
+            return Box::pin(std::future::ready(Ok(stream)));
+        }
+        let metrics = self.metrics.clone();
+        let host = dst.host().unwrap_or_default().to_string();
+        Box::pin(self.connect(dst).map_ok(move |mut stream| {
+            if let Some(metrics) = &metrics {
+                stream.track(&host, metrics);
+            }
+            #[cfg(feature = "http3")]
+            stream.hold(destinations.open(key));
+            stream
+        }))
+    }
+}
+
+/// The connections of the connector, by destination.
+#[cfg(feature = "http3")]
+#[derive(Default)]
+struct DestinationsInner {
+    /// The streams connected ahead of the requests.
+    preconnected: HashMap<DestinationKey, WrappedHttpStream>,
+    /// The numbers of the open connections.
+    open: HashMap<DestinationKey, usize>,
+}
+
+#[cfg(feature = "http3")]
+type DestinationKey = (Option<Scheme>, Option<Authority>);
+
+#[cfg(feature = "http3")]
+#[derive(Clone)]
+struct Destinations(SendWrapper<Shared<Mutex<DestinationsInner>>>);
+
+#[cfg(feature = "http3")]
+impl Destinations {
+    fn new() -> Self {
+        Self(SendWrapper::new(Shared::new(Mutex::new(
+            DestinationsInner::default(),
+        ))))
+    }
+
+    fn key(dst: &Uri) -> DestinationKey {
+        (dst.scheme().cloned(), dst.authority().cloned())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, DestinationsInner> {
+        self.0.lock()
+    }
+
+    /// Count the connection to the destination as open until the guard is
+    /// dropped.
+    fn open(&self, key: DestinationKey) -> OpenGuard {
+        *self.lock().open.entry(key.clone()).or_default() += 1;
+        OpenGuard {
+            destinations: self.clone(),
+            key,
+        }
+    }
+}
+
+#[cfg(feature = "http3")]
+impl Debug for Destinations {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Destinations").finish_non_exhaustive()
+    }
+}
+
+/// Counts the connection holding it as open.
+#[cfg(feature = "http3")]
+pub(crate) struct OpenGuard {
+    destinations: Destinations,
+    key: DestinationKey,
+}
+
+#[cfg(feature = "http3")]
+impl Drop for OpenGuard {
+    fn drop(&mut self) {
+        let mut inner = self.destinations.lock();
+        if let Some(count) = inner.open.get_mut(&self.key) {
+            *count -= 1;
+            if *count == 0 {
+                inner.open.remove(&self.key);
+            }
+        }
+    }
+}
+
+type CustomConnect = dyn Fn(
+    Uri,
+    Option<TlsConnector>,
+) -> Pin<Box<dyn Future<Output = crate::Result<WrappedHttpStream>>>>;
+
+/// A connector service provided by the user, e.g. of in-memory streams.
+#[derive(Clone)]
+pub(crate) struct CustomConnector(SendWrapper<Shared<CustomConnect>>);
+
+impl CustomConnector {
+    pub(crate) fn new<C>(connector: C) -> Self
+    where
+        C: Service<Uri> + Clone + 'static,
+        C::Response: Splittable + Unpin + 'static,
+        <C::Response as Splittable>::ReadHalf: AsyncRead + Unpin,
+        <C::Response as Splittable>::WriteHalf: AsyncWrite + Unpin,
+        C::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        C::Future: 'static,
+    {
+        let connect: Shared<CustomConnect> = Shared::new(move |dst: Uri, tls| {
+            let mut connector = connector.clone();
+            Box::pin(async move {
+                poll_fn(|cx| connector.poll_ready(cx))
+                    .await
+                    .map_err(io::Error::other)?;
+                let stream = connector
+                    .call(dst.clone())
+                    .await
+                    .map_err(io::Error::other)?;
+                let stream = HttpStream::connect_custom(stream, dst, tls).await?;
+                Ok(WrappedHttpStream::Custom(Box::new(stream)))
+            })
+        });
+        Self(SendWrapper::new(connect))
+    }
+}
+
+impl Debug for CustomConnector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CustomConnector").finish_non_exhaustive()
+    }
+}
+
 async fn connect_via_proxy(
     connector: HttpsConnector,
     dst: Uri,
@@ -97,7 +300,8 @@ async fn connect_via_proxy(
                 .call(dst.clone())
                 .await
                 .map_err(|e| crate::Error::Proxy(e.into()))?;
-            Ok(HttpStream::connect_with_https(tunneled, dst, tls)
+            let info = tunneled.info().clone();
+            Ok(HttpStream::connect_with_https(tunneled, dst, tls, info)
                 .await?
                 .into_wrapped())
         }
@@ -193,9 +397,12 @@ mod socks {
         // Wrap with TLS if targeting HTTPS.
         match dst.scheme_str() {
             #[cfg(tls)]
-            Some("https") => Ok(HttpStream::connect_with_https(stream, dst, tls)
-                .await?
-                .into_wrapped()),
+            Some("https") => {
+                let info = stream.info().clone();
+                Ok(HttpStream::connect_with_https(stream, dst, tls, info)
+                    .await?
+                    .into_wrapped())
+            }
             _ => Ok(stream.into_wrapped()),
         }
     }
@@ -0,0 +1,273 @@
+//! HTTP cookies.
+//!
+//! A [`CookieStore`] set with
+//! [`ClientBuilder::cookie_provider`](crate::ClientBuilder::cookie_provider)
+//! receives the `Set-Cookie` headers of the responses, and provides the
+//! `Cookie` header of the requests. The default store is a [`Jar`], which
+//! could be saved to and loaded from a file to resume the sessions.
+
+use std::{convert::Infallible, fmt::Write as _, io::BufReader, path::Path, sync::RwLock};
+
+use http::HeaderValue;
+use url::Url;
+
+use crate::{Error, Result};
+
+/// Trait for storing the cookies of a client.
+///
+/// The store is shared between the clones of the client, and may be shared
+/// between clients.
+pub trait CookieStore: Send + Sync {
+    /// Store the `Set-Cookie` headers of a response from the URL.
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url);
+
+    /// Get the `Cookie` header value of a request to the URL.
+    fn cookies(&self, url: &Url) -> Option<HeaderValue>;
+}
+
+impl std::fmt::Debug for dyn CookieStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CookieStore").finish_non_exhaustive()
+    }
+}
+
+/// A simple cookie store following RFC 6265.
+///
+/// The cookies can be inspected and seeded with [`add_cookie_str`], and
+/// saved to or loaded from a JSON file, or a Netscape `cookies.txt` file as
+/// used by curl and browser extensions.
+///
+/// [`add_cookie_str`]: Jar::add_cookie_str
+#[derive(Debug, Default)]
+pub struct Jar(RwLock<cookie_store::CookieStore>);
+
+impl Jar {
+    /// Creates an empty jar.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a cookie to the jar, as if it were received in a `Set-Cookie`
+    /// header of a response from the URL.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cyper::cookie::{CookieStore, Jar};
+    ///
+    /// let url = "https://example.com".parse().unwrap();
+    /// let jar = Jar::new();
+    /// jar.add_cookie_str("foo=bar; Domain=example.com", &url);
+    /// assert_eq!(jar.cookies(&url).unwrap(), "foo=bar");
+    /// ```
+    pub fn add_cookie_str(&self, cookie: &str, url: &Url) {
+        let _ = self.0.write().unwrap().parse(cookie, url);
+    }
+
+    /// Remove all cookies.
+    pub fn clear(&self) {
+        self.0.write().unwrap().clear();
+    }
+
+    /// Loads the cookies from a JSON file saved with
+    /// [`save_json`](Self::save_json). A missing file results in an empty
+    /// jar, and expired cookies are ignored.
+    pub async fn load_json(path: impl AsRef<Path>) -> Result<Self> {
+        let Some(content) = read(path).await? else {
+            return Ok(Self::new());
+        };
+        let store = cookie_store::serde::json::load(BufReader::new(content.as_slice()))
+            .map_err(Error::Cookie)?;
+        Ok(Self(RwLock::new(store)))
+    }
+
+    /// Saves the unexpired cookies to a JSON file, including the session
+    /// cookies.
+    pub async fn save_json(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut content = vec![];
+        {
+            let store = self.0.read().unwrap();
+            let unexpired = cookie_store::CookieStore::from_cookies(
+                store
+                    .iter_unexpired()
+                    .map(|c| Ok::<_, Infallible>(c.clone())),
+                false,
+            )
+            .unwrap_or_else(|e| match e {});
+            cookie_store::serde::json::save_incl_expired_and_nonpersistent(
+                &unexpired,
+                &mut content,
+            )
+            .map_err(Error::Cookie)?;
+        }
+        compio::fs::write(path, content).await.0?;
+        Ok(())
+    }
+
+    /// Loads the cookies from a Netscape `cookies.txt` file. A missing file
+    /// results in an empty jar, and malformed or expired cookies are ignored.
+    pub async fn load_netscape(path: impl AsRef<Path>) -> Result<Self> {
+        let jar = Self::new();
+        if let Some(content) = read(path).await? {
+            jar.read_netscape(&String::from_utf8_lossy(&content));
+        }
+        Ok(jar)
+    }
+
+    /// Saves the unexpired cookies to a Netscape `cookies.txt` file,
+    /// including the session cookies with a zero expiry.
+    pub async fn save_netscape(&self, path: impl AsRef<Path>) -> Result<()> {
+        let content = self.write_netscape();
+        compio::fs::write(path, content).await.0?;
+        Ok(())
+    }
+
+    fn read_netscape(&self, content: &str) {
+        let mut store = self.0.write().unwrap();
+        for line in content.lines() {
+            let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+                Some(line) => (line, true),
+                None => (line, false),
+            };
+            if line.starts_with('#') {
+                continue;
+            }
+            let [domain, subdomains, path, secure, expires, name, value] =
+                line.trim_end().splitn(7, '\t').collect::<Vec<_>>()[..]
+            else {
+                continue;
+            };
+            let Ok(expires) = expires.parse::<i64>() else {
+                continue;
+            };
+            let host = domain.trim_start_matches('.');
+            let Ok(url) = Url::parse(&format!("https://{host}{path}")) else {
+                continue;
+            };
+            let mut cookie = format!("{name}={value}; Path={path}");
+            if subdomains.eq_ignore_ascii_case("TRUE") {
+                let _ = write!(cookie, "; Domain={host}");
+            }
+            if secure.eq_ignore_ascii_case("TRUE") {
+                cookie.push_str("; Secure");
+            }
+            if http_only {
+                cookie.push_str("; HttpOnly");
+            }
+            let Ok(mut cookie) = cookie_store::Cookie::parse(cookie, &url) else {
+                continue;
+            };
+            // Zero is for the session cookies.
+            if expires != 0 {
+                let Ok(at) = time::OffsetDateTime::from_unix_timestamp(expires) else {
+                    continue;
+                };
+                cookie.expires = cookie_store::CookieExpiration::AtUtc(at);
+            }
+            let _ = store.insert(cookie.into_owned(), &url);
+        }
+    }
+
+    fn write_netscape(&self) -> String {
+        let mut content = String::from("# Netscape HTTP Cookie File\n");
+        let store = self.0.read().unwrap();
+        for cookie in store.iter_unexpired() {
+            let (domain, subdomains) = match &cookie.domain {
+                cookie_store::CookieDomain::HostOnly(host) => (host.clone(), "FALSE"),
+                cookie_store::CookieDomain::Suffix(suffix) => (format!(".{suffix}"), "TRUE"),
+                _ => continue,
+            };
+            let expires = match cookie.expires {
+                cookie_store::CookieExpiration::AtUtc(at) => at.unix_timestamp().max(1),
+                cookie_store::CookieExpiration::SessionEnd => 0,
+            };
+            let bool = |b: Option<bool>| if b == Some(true) { "TRUE" } else { "FALSE" };
+            let _ = writeln!(
+                content,
+                "{}{domain}\t{subdomains}\t{}\t{}\t{expires}\t{}\t{}",
+                if cookie.http_only() == Some(true) {
+                    "#HttpOnly_"
+                } else {
+                    ""
+                },
+                cookie.path.as_ref(),
+                bool(cookie.secure()),
+                cookie.name(),
+                cookie.value(),
+            );
+        }
+        content
+    }
+}
+
+impl CookieStore for Jar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let mut cookies = cookie_headers
+            .filter_map(|val| std::str::from_utf8(val.as_bytes()).ok()?.parse().ok())
+            .peekable();
+        if cookies.peek().is_some() {
+            self.0.write().unwrap().store_response_cookies(cookies, url);
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let value = self
+            .0
+            .read()
+            .unwrap()
+            .get_request_values(url)
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if value.is_empty() {
+            None
+        } else {
+            HeaderValue::from_str(&value).ok()
+        }
+    }
+}
+
+async fn read(path: impl AsRef<Path>) -> Result<Option<Vec<u8>>> {
+    match compio::fs::read(path).await {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_netscape() {
+        let jar = Jar::new();
+        jar.read_netscape(concat!(
+            "# Netscape HTTP Cookie File\n",
+            ".example.com\tTRUE\t/\tFALSE\t0\tsession\t1\n",
+            "#HttpOnly_example.com\tFALSE\t/api\tTRUE\t4102444800\tsecure\ta=b\n",
+            "example.com\tFALSE\t/\tFALSE\t1\texpired\t1\n",
+            "malformed\tline\n",
+        ));
+        let url = |url: &str| url.parse::<Url>().unwrap();
+        assert_eq!(
+            jar.cookies(&url("http://sub.example.com/api")).unwrap(),
+            "session=1"
+        );
+        let cookies = jar.cookies(&url("https://example.com/api/users")).unwrap();
+        let mut cookies = cookies.to_str().unwrap().split("; ").collect::<Vec<_>>();
+        cookies.sort();
+        assert_eq!(cookies, ["secure=a=b", "session=1"]);
+
+        let content = jar.write_netscape();
+        let mut lines = content.lines().skip(1).collect::<Vec<_>>();
+        lines.sort();
+        assert_eq!(
+            lines,
+            [
+                "#HttpOnly_example.com\tFALSE\t/api\tTRUE\t4102444800\tsecure\ta=b",
+                ".example.com\tTRUE\t/\tFALSE\t0\tsession\t1",
+            ]
+        );
+    }
+}
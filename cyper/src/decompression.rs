@@ -8,7 +8,7 @@ use compression_codecs::{
 };
 use futures_util::Stream;
 use http_body_util::BodyExt;
-use hyper::body::Incoming;
+use hyper::body::{Frame, Incoming};
 
 // flate2 requires a window-sized output buffer (~32KB).
 const MIN_SPARE: usize = 32768;
@@ -92,15 +92,17 @@ impl<D: DecodeV2> Decoder<D> {
     pub fn decode_incoming(
         mut self,
         mut incoming: Incoming,
-    ) -> impl Stream<Item = crate::Result<Bytes>> {
+    ) -> impl Stream<Item = crate::Result<Frame<Bytes>>> {
         try_stream! {
             while let Some(frame) = incoming.frame().await {
-                let frame = frame?;
-                if let Some(data) = frame.data_ref() {
-                    let bytes = self.decode_all(data)?;
-                    if !bytes.is_empty() {
-                        yield bytes;
+                match frame?.into_data() {
+                    Ok(data) => {
+                        let bytes = self.decode_all(&data)?;
+                        if !bytes.is_empty() {
+                            yield Frame::data(bytes);
+                        }
                     }
+                    Err(frame) => yield frame,
                 }
             }
         }
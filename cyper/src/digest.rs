@@ -0,0 +1,564 @@
+//! HTTP Digest access authentication (RFC 7616).
+
+use std::fmt::Write;
+
+use compio::bytes::Bytes;
+use http::{HeaderMap, HeaderValue, Method, StatusCode, header};
+use md5::Md5;
+use sha2::{Digest, Sha256, Sha512_256};
+use url::{Origin, Url};
+
+use crate::{Client, Request, Response, Result, sync::mutex_blocking::Mutex};
+
+/// The username and password of Digest authentication.
+#[derive(Clone)]
+pub(crate) struct Credentials {
+    username: String,
+    password: String,
+}
+
+impl Credentials {
+    pub(crate) fn new(username: String, password: String) -> Self {
+        Self { username, password }
+    }
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Algorithm {
+    Md5,
+    Sha256,
+    Sha512_256,
+}
+
+impl Algorithm {
+    fn parse(name: &str) -> Option<(Self, bool)> {
+        let (name, sess) = match name.len().checked_sub(5) {
+            Some(i) if name.is_char_boundary(i) && name[i..].eq_ignore_ascii_case("-sess") => {
+                (&name[..i], true)
+            }
+            _ => (name, false),
+        };
+        let alg = if name.eq_ignore_ascii_case("MD5") {
+            Self::Md5
+        } else if name.eq_ignore_ascii_case("SHA-256") {
+            Self::Sha256
+        } else if name.eq_ignore_ascii_case("SHA-512-256") {
+            Self::Sha512_256
+        } else {
+            return None;
+        };
+        Some((alg, sess))
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Md5 => "MD5",
+            Self::Sha256 => "SHA-256",
+            Self::Sha512_256 => "SHA-512-256",
+        }
+    }
+
+    fn hash(self, data: &[u8]) -> String {
+        match self {
+            Self::Md5 => hex(&Md5::digest(data)),
+            Self::Sha256 => hex(&Sha256::digest(data)),
+            Self::Sha512_256 => hex(&Sha512_256::digest(data)),
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Qop {
+    Auth,
+    AuthInt,
+}
+
+/// A `Digest` challenge of `WWW-Authenticate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Challenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: Algorithm,
+    sess: bool,
+    qop: Vec<Qop>,
+    domain: Vec<String>,
+    stale: bool,
+    userhash: bool,
+}
+
+impl Challenge {
+    fn parse(params: &[(String, String)]) -> Option<Self> {
+        let get = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let (algorithm, sess) = Algorithm::parse(get("algorithm").unwrap_or("MD5"))?;
+        let qop = match get("qop") {
+            Some(qop) => {
+                let qop = qop
+                    .split(',')
+                    .filter_map(|qop| match qop.trim() {
+                        "auth" => Some(Qop::Auth),
+                        "auth-int" => Some(Qop::AuthInt),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                // None of the offered qop is supported.
+                if qop.is_empty() {
+                    return None;
+                }
+                qop
+            }
+            None => vec![],
+        };
+        Some(Self {
+            realm: get("realm")?.to_string(),
+            nonce: get("nonce")?.to_string(),
+            opaque: get("opaque").map(ToString::to_string),
+            algorithm,
+            sess,
+            qop,
+            domain: get("domain")
+                .map(|domain| domain.split_whitespace().map(ToString::to_string).collect())
+                .unwrap_or_default(),
+            stale: get("stale").is_some_and(|stale| stale.eq_ignore_ascii_case("true")),
+            userhash: get("userhash").is_some_and(|userhash| userhash.eq_ignore_ascii_case("true")),
+        })
+    }
+
+    /// Whether the URL is in the protection space of this challenge.
+    fn covers(&self, origin: &Origin, url: &Url) -> bool {
+        self.domain.is_empty()
+            || self.domain.iter().any(|domain| match Url::parse(domain) {
+                Ok(domain) => domain.origin() == *origin && url.path().starts_with(domain.path()),
+                Err(_) => url.path().starts_with(domain.as_str()),
+            })
+    }
+
+    /// Compute the `Authorization` header value.
+    fn authorize(
+        &self,
+        credentials: &Credentials,
+        method: &Method,
+        url: &Url,
+        body: Option<&Bytes>,
+        nc: u32,
+        cnonce: &str,
+    ) -> Option<HeaderValue> {
+        let alg = self.algorithm;
+        let uri = match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
+        };
+        let qop = if self.qop.is_empty() {
+            None
+        } else if self.qop.contains(&Qop::Auth) {
+            Some(Qop::Auth)
+        } else {
+            // The body is required to protect its integrity.
+            body.map(|_| Qop::AuthInt)
+        };
+        if !self.qop.is_empty() && qop.is_none() {
+            return None;
+        }
+
+        let mut ha1 = alg.hash(
+            format!(
+                "{}:{}:{}",
+                credentials.username, self.realm, credentials.password
+            )
+            .as_bytes(),
+        );
+        if self.sess {
+            ha1 = alg.hash(format!("{ha1}:{}:{cnonce}", self.nonce).as_bytes());
+        }
+        let ha2 = match qop {
+            Some(Qop::AuthInt) => alg.hash(
+                format!(
+                    "{method}:{uri}:{}",
+                    alg.hash(body.map(|b| &b[..]).unwrap_or_default())
+                )
+                .as_bytes(),
+            ),
+            _ => alg.hash(format!("{method}:{uri}").as_bytes()),
+        };
+        let nc = format!("{nc:08x}");
+        let response = match qop {
+            Some(qop) => alg.hash(
+                format!("{ha1}:{}:{nc}:{cnonce}:{}:{ha2}", self.nonce, qop_name(qop)).as_bytes(),
+            ),
+            None => alg.hash(format!("{ha1}:{}:{ha2}", self.nonce).as_bytes()),
+        };
+
+        let username = if self.userhash {
+            alg.hash(format!("{}:{}", credentials.username, self.realm).as_bytes())
+        } else {
+            credentials.username.clone()
+        };
+        let mut value = format!(
+            "Digest username={}, realm={}, uri={}, algorithm={}{}, nonce={}",
+            quote(&username),
+            quote(&self.realm),
+            quote(&uri),
+            alg.name(),
+            if self.sess { "-sess" } else { "" },
+            quote(&self.nonce),
+        );
+        if let Some(qop) = qop {
+            let _ = write!(
+                value,
+                ", nc={nc}, cnonce={}, qop={}",
+                quote(cnonce),
+                qop_name(qop)
+            );
+        }
+        let _ = write!(value, ", response={}", quote(&response));
+        if let Some(opaque) = &self.opaque {
+            let _ = write!(value, ", opaque={}", quote(opaque));
+        }
+        if self.userhash {
+            value.push_str(", userhash=true");
+        }
+        let mut value = HeaderValue::from_str(&value).ok()?;
+        value.set_sensitive(true);
+        Some(value)
+    }
+}
+
+fn qop_name(qop: Qop) -> &'static str {
+    match qop {
+        Qop::Auth => "auth",
+        Qop::AuthInt => "auth-int",
+    }
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn cnonce() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).expect("failed to generate a cnonce");
+    hex(&bytes)
+}
+
+/// Parse the challenges of `WWW-Authenticate` into the schemes and their
+/// parameters.
+fn parse_challenges(value: &str) -> Vec<(&str, Vec<(String, String)>)> {
+    let mut cursor = Cursor { s: value, pos: 0 };
+    let mut challenges = vec![];
+    loop {
+        cursor.skip(|c| c == b',' || c == b' ' || c == b'\t');
+        let Some(scheme) = cursor.token() else {
+            break;
+        };
+        let mut params = vec![];
+        loop {
+            let start = cursor.pos;
+            cursor.skip(|c| c == b',' || c == b' ' || c == b'\t');
+            let Some(name) = cursor.token() else {
+                break;
+            };
+            cursor.skip(|c| c == b' ' || c == b'\t');
+            if cursor.peek() != Some(b'=') {
+                // The start of the next challenge.
+                cursor.pos = start;
+                break;
+            }
+            cursor.pos += 1;
+            cursor.skip(|c| c == b' ' || c == b'\t');
+            let value = cursor.value();
+            params.push((name.to_ascii_lowercase(), value));
+        }
+        challenges.push((scheme, params));
+    }
+    challenges
+}
+
+struct Cursor<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.s.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip(&mut self, f: impl Fn(u8) -> bool) {
+        while self.peek().is_some_and(&f) {
+            self.pos += 1;
+        }
+    }
+
+    fn token(&mut self) -> Option<&'a str> {
+        let start = self.pos;
+        self.skip(|c| c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c));
+        (self.pos > start).then(|| &self.s[start..self.pos])
+    }
+
+    fn value(&mut self) -> String {
+        if self.peek() != Some(b'"') {
+            // A token, or a token68 with the trailing `=`.
+            let start = self.pos;
+            self.skip(|c| c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~/=".contains(&c));
+            return self.s[start..self.pos].to_string();
+        }
+        self.pos += 1;
+        let mut value = String::new();
+        let mut chars = self.s[self.pos..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return value;
+                }
+                '\\' => {
+                    if let Some((_, c)) = chars.next() {
+                        value.push(c);
+                    }
+                }
+                c => value.push(c),
+            }
+        }
+        // Unterminated quoted string.
+        self.pos = self.s.len();
+        value
+    }
+}
+
+#[derive(Debug)]
+struct Session {
+    origin: Origin,
+    challenge: Challenge,
+    nc: u32,
+}
+
+/// The challenges received by a client, reused for later requests.
+#[derive(Debug)]
+pub(crate) struct Cache {
+    sessions: Mutex<Vec<Session>>,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self {
+            sessions: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl Cache {
+    /// Authorize the request with a cached challenge, before being challenged.
+    fn authorize(
+        &self,
+        credentials: &Credentials,
+        method: &Method,
+        url: &Url,
+        body: Option<&Bytes>,
+    ) -> Option<HeaderValue> {
+        let origin = url.origin();
+        let mut sessions = self.sessions.lock();
+        let session = sessions
+            .iter_mut()
+            .find(|s| s.origin == origin && s.challenge.covers(&origin, url))?;
+        session.nc += 1;
+        session
+            .challenge
+            .authorize(credentials, method, url, body, session.nc, &cnonce())
+    }
+
+    /// Store the strongest supported challenge of the response, and authorize
+    /// the request with it.
+    fn challenge(
+        &self,
+        credentials: &Credentials,
+        method: &Method,
+        url: &Url,
+        body: Option<&Bytes>,
+        headers: &HeaderMap,
+    ) -> Option<HeaderValue> {
+        let challenge = headers
+            .get_all(header::WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(parse_challenges)
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Digest"))
+            .filter_map(|(_, params)| Challenge::parse(&params))
+            .max_by_key(|challenge| challenge.algorithm)?;
+
+        let origin = url.origin();
+        let mut sessions = self.sessions.lock();
+        let index = sessions
+            .iter()
+            .position(|s| s.origin == origin && s.challenge.realm == challenge.realm);
+        let session = match index {
+            Some(index) => {
+                let session = &mut sessions[index];
+                if session.challenge.nonce != challenge.nonce {
+                    session.nc = 0;
+                }
+                session.challenge = challenge;
+                session
+            }
+            None => {
+                sessions.push(Session {
+                    origin,
+                    challenge,
+                    nc: 0,
+                });
+                sessions.last_mut().unwrap()
+            }
+        };
+        session.nc += 1;
+        session
+            .challenge
+            .authorize(credentials, method, url, body, session.nc, &cnonce())
+    }
+}
+
+/// Send the request, and replay it once with Digest authentication if the
+/// server responds with a `401` challenge.
+pub(crate) async fn execute(
+    client: &Client,
+    mut request: Request,
+    credentials: Credentials,
+) -> Result<Response> {
+    let cache = client.digest_cache();
+    if !request.headers().contains_key(header::AUTHORIZATION)
+        && let Some(value) = cache.authorize(
+            &credentials,
+            request.method(),
+            request.url(),
+            request.body().as_bytes(),
+        )
+    {
+        request.headers_mut().insert(header::AUTHORIZATION, value);
+    }
+    let retry = request.try_clone();
+    let res = client.execute_request(request).await?;
+    if res.status() != StatusCode::UNAUTHORIZED {
+        return Ok(res);
+    }
+    let Some(mut retry) = retry else {
+        return Ok(res);
+    };
+    // The challenge of a redirect target on another origin is not answered,
+    // since the retry is sent to the original origin.
+    if res.url().origin() != retry.url().origin() {
+        return Ok(res);
+    }
+    let Some(value) = cache.challenge(
+        &credentials,
+        retry.method(),
+        retry.url(),
+        retry.body().as_bytes(),
+        res.headers(),
+    ) else {
+        return Ok(res);
+    };
+    retry.headers_mut().insert(header::AUTHORIZATION, value);
+    let mut res = client.execute_request(retry).await?;
+    res.retries += 1;
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn challenge(value: &str) -> Challenge {
+        let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+        let challenges = parse_challenges(&value);
+        Challenge::parse(&challenges[0].1).unwrap()
+    }
+
+    // The example of RFC 7616 section 3.9.1.
+    const CHALLENGE: &str = r#"Digest
+        realm="http-auth@example.org",
+        qop="auth, auth-int",
+        algorithm=ALGORITHM,
+        nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v",
+        opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#;
+
+    fn rfc_response(algorithm: &str) -> String {
+        let challenge = challenge(&CHALLENGE.replace("ALGORITHM", algorithm));
+        let credentials = Credentials::new("Mufasa".into(), "Circle of Life".into());
+        let value = challenge
+            .authorize(
+                &credentials,
+                &Method::GET,
+                &Url::parse("http://www.example.org/dir/index.html").unwrap(),
+                None,
+                1,
+                "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ",
+            )
+            .unwrap();
+        value.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_rfc7616_example() {
+        assert_eq!(
+            rfc_response("MD5"),
+            r#"Digest username="Mufasa", realm="http-auth@example.org", uri="/dir/index.html", algorithm=MD5, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", nc=00000001, cnonce="f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ", qop=auth, response="8ca523f5e9506fed4657c9700eebdbec", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#
+        );
+        assert!(rfc_response("SHA-256").contains(
+            r#"response="753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1""#
+        ));
+    }
+
+    #[test]
+    fn test_parse_challenges() {
+        let challenges = parse_challenges(
+            r#"Newauth realm="apps", type=1, title="Login to \"apps\"", Basic realm="simple", Negotiate abc==, Digest realm="a", nonce="n", algorithm=SHA-256-sess, stale=TRUE"#,
+        );
+        let schemes = challenges.iter().map(|(s, _)| *s).collect::<Vec<_>>();
+        assert_eq!(schemes, ["Newauth", "Basic", "Negotiate", "Digest"]);
+        assert_eq!(challenges[0].1[2].1, "Login to \"apps\"");
+
+        let challenge = Challenge::parse(&challenges[3].1).unwrap();
+        assert_eq!(challenge.algorithm, Algorithm::Sha256);
+        assert!(challenge.sess && challenge.stale);
+        assert!(challenge.qop.is_empty());
+
+        // Unsupported algorithms and qop are ignored.
+        assert!(
+            Challenge::parse(
+                &parse_challenges(r#"Digest realm="a", nonce="n", algorithm=SHA-1"#)[0].1
+            )
+            .is_none()
+        );
+        assert!(
+            Challenge::parse(&parse_challenges(r#"Digest realm="a", nonce="n", qop="other""#)[0].1)
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,448 @@
+//! HTTP Archive (HAR) recording.
+//!
+//! A [`HarRecorder`] set with
+//! [`ClientBuilder::recorder`](crate::ClientBuilder::recorder) records each
+//! request sent by the client, including the redirects, and exports them as
+//! a [HAR 1.2] archive, like the one exported by the browsers.
+//!
+//! The bodies are copied while they are sent and read, so streaming is not
+//! affected. A body is recorded up to the end of it read by the user.
+//!
+//! [HAR 1.2]: http://www.softwareishard.com/blog/har-12-spec/
+
+use std::{
+    net::SocketAddr,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use http::{HeaderMap, HeaderName, Method, StatusCode, Version, header};
+use serde_json::{Value, json};
+use url::Url;
+
+use crate::{
+    Body, Response, Result, Timings,
+    sync::{mutex_blocking::Mutex, shared::Shared},
+    util::format_rfc3339,
+};
+
+/// The default limit of the recorded body size.
+const DEFAULT_BODY_LIMIT: usize = 64 * 1024;
+
+/// The value of the sensitive headers, e.g. the credentials.
+const REDACTED: &str = "REDACTED";
+
+/// The headers always redacted, even if not marked as sensitive.
+const REDACTED_HEADERS: [HeaderName; 4] = [
+    header::AUTHORIZATION,
+    header::PROXY_AUTHORIZATION,
+    header::COOKIE,
+    header::SET_COOKIE,
+];
+
+/// The data of a body, copied while it is transferred.
+///
+/// It is shared with the body, which could be sent to other threads.
+#[derive(Debug, Clone)]
+pub(crate) struct Capture(Arc<std::sync::Mutex<CaptureInner>>);
+
+#[derive(Debug)]
+struct CaptureInner {
+    data: Vec<u8>,
+    size: u64,
+    limit: usize,
+    end: Option<Instant>,
+}
+
+impl Capture {
+    fn new(limit: usize) -> Self {
+        Self(Arc::new(std::sync::Mutex::new(CaptureInner {
+            data: Vec::new(),
+            size: 0,
+            limit,
+            end: None,
+        })))
+    }
+
+    pub(crate) fn push(&self, data: &[u8]) {
+        let mut inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let len = data.len().min(inner.limit.saturating_sub(inner.data.len()));
+        inner.data.extend_from_slice(&data[..len]);
+        inner.size += data.len() as u64;
+    }
+
+    /// The body is read to the end.
+    pub(crate) fn finish(&self) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).end = Some(Instant::now());
+    }
+
+    /// The size, end time and HAR fields of the content.
+    fn export(&self, mime_type: &str) -> (u64, Option<Instant>, Value) {
+        let inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let mut content = json!({ "size": inner.size, "mimeType": mime_type });
+        let truncated = inner.size > inner.data.len() as u64;
+        let text = match std::str::from_utf8(&inner.data) {
+            Ok(text) => Some(text),
+            // The truncation may split a character.
+            Err(e) if truncated && e.error_len().is_none() => {
+                Some(std::str::from_utf8(&inner.data[..e.valid_up_to()]).expect("valid UTF-8"))
+            }
+            Err(_) => None,
+        };
+        match text {
+            Some(text) => content["text"] = text.into(),
+            None => {
+                use base64::Engine;
+
+                content["text"] = base64::prelude::BASE64_STANDARD.encode(&inner.data).into();
+                content["encoding"] = "base64".into();
+            }
+        }
+        if truncated {
+            content["comment"] = format!("truncated to {} bytes", inner.data.len()).into();
+        }
+        (inner.size, inner.end, content)
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    started: SystemTime,
+    method: Method,
+    url: Url,
+    version: Version,
+    headers: HeaderMap,
+    body: Option<Capture>,
+    response: std::result::Result<ResponseEntry, String>,
+}
+
+#[derive(Debug)]
+struct ResponseEntry {
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+    body: Capture,
+    remote_addr: Option<SocketAddr>,
+    local_addr: Option<SocketAddr>,
+    timings: Timings,
+    /// When the response headers are received.
+    received: Instant,
+}
+
+/// A recorder of the requests and responses of clients, exported as a HAR
+/// 1.2 archive.
+///
+/// The entries are shared between the clones. The values of the sensitive
+/// headers, the credentials and the cookies are redacted.
+///
+/// ```no_run
+/// use cyper::{Client, har::HarRecorder};
+///
+/// # async fn run() -> cyper::Result<()> {
+/// let recorder = HarRecorder::new();
+/// let client = Client::builder().recorder(recorder.clone()).build()?;
+/// client
+///     .get("https://example.com")?
+///     .send()
+///     .await?
+///     .text()
+///     .await?;
+/// recorder.save("cyper.har").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct HarRecorder {
+    entries: Shared<Mutex<Vec<Entry>>>,
+    body_limit: usize,
+}
+
+impl Default for HarRecorder {
+    fn default() -> Self {
+        Self {
+            entries: Shared::new(Mutex::new(Vec::new())),
+            body_limit: DEFAULT_BODY_LIMIT,
+        }
+    }
+}
+
+impl HarRecorder {
+    /// Creates an empty recorder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum size of each recorded body. The rest is not recorded,
+    /// but counted in the size.
+    ///
+    /// Default is 64 KiB.
+    pub fn body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = limit;
+        self
+    }
+
+    /// The count of the recorded requests.
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    /// Whether no request is recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all entries.
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+
+    /// Exports the entries as the HAR JSON.
+    pub fn to_json(&self) -> String {
+        let entries = self
+            .entries
+            .lock()
+            .iter()
+            .map(Entry::export)
+            .collect::<Vec<_>>();
+        let har = json!({
+            "log": {
+                "version": "1.2",
+                "creator": {
+                    "name": "cyper",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+                "entries": entries,
+            }
+        });
+        serde_json::to_string_pretty(&har).expect("the HAR should be serializable")
+    }
+
+    /// Saves the HAR JSON to a file.
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        compio::fs::write(path, self.to_json()).await.0?;
+        Ok(())
+    }
+
+    /// Start recording the request, copying its body.
+    pub(crate) fn record(&self, request: &mut http::Request<Body>, url: &Url) -> Recording {
+        let body = (request.body().content_length() != Some(0)).then(|| {
+            let capture = Capture::new(self.body_limit);
+            let body = std::mem::take(request.body_mut()).tee(&capture);
+            *request.body_mut() = body;
+            capture
+        });
+        Recording {
+            recorder: self.clone(),
+            started: SystemTime::now(),
+            start: Instant::now(),
+            method: request.method().clone(),
+            url: url.clone(),
+            version: request.version(),
+            headers: request.headers().clone(),
+            body,
+        }
+    }
+}
+
+/// A request being recorded.
+pub(crate) struct Recording {
+    recorder: HarRecorder,
+    started: SystemTime,
+    start: Instant,
+    method: Method,
+    url: Url,
+    version: Version,
+    headers: HeaderMap,
+    body: Option<Capture>,
+}
+
+impl Recording {
+    /// Record the response, copying its body, or the error.
+    pub(crate) fn finish(self, res: Result<Response>) -> Result<Response> {
+        let (response, res) = match res {
+            Ok(mut res) => {
+                let capture = Capture::new(self.recorder.body_limit);
+                let response = ResponseEntry {
+                    status: res.status(),
+                    version: res.version(),
+                    headers: res.headers().clone(),
+                    body: capture.clone(),
+                    remote_addr: res.remote_addr(),
+                    local_addr: res.local_addr(),
+                    timings: Timings::new(self.start, self.start, &res),
+                    received: res.received,
+                };
+                res.body = res.body.tee(capture);
+                (Ok(response), Ok(res))
+            }
+            Err(e) => (Err(e.to_string()), Err(e)),
+        };
+        self.recorder.entries.lock().push(Entry {
+            started: self.started,
+            method: self.method,
+            url: self.url,
+            version: self.version,
+            headers: self.headers,
+            body: self.body,
+            response,
+        });
+        res
+    }
+}
+
+impl Entry {
+    fn export(&self) -> Value {
+        let mut request = json!({
+            "method": self.method.as_str(),
+            "url": self.url.as_str(),
+            "httpVersion": format!("{:?}", self.version),
+            "cookies": [],
+            "headers": headers(&self.headers),
+            "queryString": self
+                .url
+                .query_pairs()
+                .map(|(name, value)| json!({ "name": name, "value": value }))
+                .collect::<Vec<_>>(),
+            "headersSize": -1,
+            "bodySize": 0,
+        });
+        if let Some(body) = &self.body {
+            let (size, _, content) = body.export(&mime_type(&self.headers));
+            request["bodySize"] = size.into();
+            let mut post_data = json!({ "mimeType": content["mimeType"], "text": content["text"] });
+            if let Some(encoding) = content.get("encoding") {
+                post_data["encoding"] = encoding.clone();
+            }
+            request["postData"] = post_data;
+        }
+
+        let mut entry = match &self.response {
+            Ok(response) => response.export(),
+            Err(e) => json!({
+                "time": 0,
+                "response": {
+                    "status": 0,
+                    "statusText": "",
+                    "httpVersion": "",
+                    "cookies": [],
+                    "headers": [],
+                    "content": { "size": 0, "mimeType": "x-unknown" },
+                    "redirectURL": "",
+                    "headersSize": -1,
+                    "bodySize": -1,
+                },
+                "timings": { "send": 0, "wait": 0, "receive": 0 },
+                "_error": e,
+            }),
+        };
+        entry["startedDateTime"] = format_rfc3339(self.started).into();
+        entry["request"] = request;
+        entry["cache"] = json!({});
+        entry
+    }
+}
+
+impl ResponseEntry {
+    fn export(&self) -> Value {
+        let (size, end, content) = self.body.export(&mime_type(&self.headers));
+
+        let t = &self.timings;
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let phase = |start: Duration, end: Option<Duration>| match end {
+            Some(end) => ms(end.saturating_sub(start)),
+            None => -1.0,
+        };
+        let dns = t.name_lookup();
+        let connected = t.connect();
+        let handshaken = t.tls_handshake();
+        // The connect time includes the TLS handshake.
+        let ready = handshaken.or(connected).or(dns).unwrap_or_default();
+        let sent = t.request_sent().unwrap_or(ready).max(ready);
+        let receive = end.map_or(0.0, |end| ms(end.saturating_duration_since(self.received)));
+
+        let mut entry = json!({
+            "time": ms(t.first_byte()) + receive,
+            "response": {
+                "status": self.status.as_u16(),
+                "statusText": self.status.canonical_reason().unwrap_or_default(),
+                "httpVersion": format!("{:?}", self.version),
+                "cookies": [],
+                "headers": headers(&self.headers),
+                "content": content,
+                "redirectURL": self
+                    .headers
+                    .get(header::LOCATION)
+                    .map(|location| String::from_utf8_lossy(location.as_bytes()))
+                    .unwrap_or_default(),
+                "headersSize": -1,
+                "bodySize": if end.is_some() { size as i64 } else { -1 },
+            },
+            "timings": {
+                "blocked": -1,
+                "dns": phase(Duration::ZERO, dns),
+                "connect": phase(dns.unwrap_or_default(), handshaken.or(connected)),
+                "ssl": phase(connected.unwrap_or_default(), handshaken),
+                "send": ms(sent - ready),
+                "wait": ms(t.first_byte().saturating_sub(sent)),
+                "receive": receive,
+            },
+        });
+        if let Some(addr) = self.remote_addr {
+            entry["serverIPAddress"] = addr.ip().to_string().into();
+        }
+        if let Some(addr) = self.local_addr {
+            entry["connection"] = addr.port().to_string().into();
+        }
+        entry
+    }
+}
+
+fn headers(headers: &HeaderMap) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if value.is_sensitive() || REDACTED_HEADERS.contains(name) {
+                REDACTED.into()
+            } else {
+                String::from_utf8_lossy(value.as_bytes())
+            };
+            json!({ "name": name.as_str(), "value": value })
+        })
+        .collect()
+}
+
+fn mime_type(headers: &HeaderMap) -> String {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("x-unknown")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture() {
+        let capture = Capture::new(4);
+        capture.push("ab".as_bytes());
+        capture.push("cé".as_bytes());
+        let (size, end, content) = capture.export("text/plain");
+        assert_eq!(size, 5);
+        assert!(end.is_none());
+        assert_eq!(content["text"], "abc");
+        assert_eq!(content["comment"], "truncated to 4 bytes");
+
+        let capture = Capture::new(4);
+        capture.push(&[0xff, 0]);
+        capture.finish();
+        let (size, end, content) = capture.export("application/octet-stream");
+        assert_eq!(size, 2);
+        assert!(end.is_some());
+        assert_eq!(content["text"], "/wA=");
+        assert_eq!(content["encoding"], "base64");
+        assert!(content.get("comment").is_none());
+    }
+}
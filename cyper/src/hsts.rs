@@ -0,0 +1,360 @@
+//! HTTP Strict Transport Security ([RFC 6797]).
+//!
+//! [RFC 6797]: https://www.rfc-editor.org/rfc/rfc6797
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use http::HeaderValue;
+use url::{Host, Url};
+
+use crate::{
+    Result,
+    sync::{mutex_blocking::Mutex, shared::Shared},
+    util::{format_expiry, parse_expiry},
+};
+
+/// The upper bound of `max-age`, about 100 years.
+const MAX_AGE_LIMIT: u64 = 100 * 365 * 86400;
+
+/// The expiry of the preloaded hosts in the file.
+const UNLIMITED: &str = "unlimited";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    include_subdomains: bool,
+    /// `None` for the preloaded hosts, which never expire.
+    expires: Option<SystemTime>,
+}
+
+impl Entry {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+}
+
+/// A store of the known HSTS hosts, which are only requested over HTTPS.
+///
+/// The hosts are recorded from the `Strict-Transport-Security` header of
+/// HTTPS responses, or preloaded with [`preload`](Self::preload). The store
+/// is shared between the clones. It could be saved to and loaded from a file,
+/// and the file format is the same as the one used by curl.
+#[derive(Debug, Clone)]
+pub struct HstsStore {
+    map: Shared<Mutex<HashMap<String, Entry>>>,
+}
+
+impl Default for HstsStore {
+    fn default() -> Self {
+        Self {
+            map: Shared::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl HstsStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a host to the preload list. The preloaded hosts never expire, and
+    /// are not changed by the received headers.
+    pub fn preload(&self, host: &str, include_subdomains: bool) {
+        if let Some(host) = canonicalize(host) {
+            self.map.lock().insert(
+                host,
+                Entry {
+                    include_subdomains,
+                    expires: None,
+                },
+            );
+        }
+    }
+
+    /// Loads the store from a file. A missing file results in an empty store,
+    /// and malformed or expired entries are ignored.
+    ///
+    /// Entries expiring `"unlimited"` are preloaded.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let store = Self::new();
+        match compio::fs::read(path).await {
+            Ok(content) => store.read_from(&String::from_utf8_lossy(&content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(store)
+    }
+
+    /// Saves the unexpired entries to a file.
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let content = self.write_to();
+        compio::fs::write(path, content).await.0?;
+        Ok(())
+    }
+
+    /// Removes all entries, including the preloaded ones.
+    pub fn clear(&self) {
+        self.map.lock().clear();
+    }
+
+    /// Whether the host is a known HSTS host, directly or as a subdomain.
+    pub fn is_secure(&self, host: &str) -> bool {
+        let Some(host) = canonicalize(host) else {
+            return false;
+        };
+        let now = SystemTime::now();
+        let map = self.map.lock();
+        if map.get(&host).is_some_and(|entry| !entry.is_expired(now)) {
+            return true;
+        }
+        host.match_indices('.').any(|(i, _)| {
+            map.get(&host[i + 1..])
+                .is_some_and(|entry| entry.include_subdomains && !entry.is_expired(now))
+        })
+    }
+
+    /// Upgrades the `http` URL of a known HSTS host to `https`. Returns
+    /// whether it is upgraded.
+    pub(crate) fn upgrade(&self, url: &mut Url) -> bool {
+        if url.scheme() != "http" || !url.host_str().is_some_and(|host| self.is_secure(host)) {
+            return false;
+        }
+        // The default port 80 is not kept in the URL, and becomes 443.
+        url.set_scheme("https").is_ok()
+    }
+
+    /// Records the `Strict-Transport-Security` header of a response.
+    pub(crate) fn update(&self, url: &Url, value: &HeaderValue) {
+        // The header is ignored over plain HTTP, and for IP addresses.
+        if url.scheme() != "https" {
+            return;
+        }
+        let Some(Host::Domain(host)) = url.host() else {
+            return;
+        };
+        let Some(host) = canonicalize(host) else {
+            return;
+        };
+        let Some((max_age, include_subdomains)) = value.to_str().ok().and_then(parse) else {
+            return;
+        };
+        let mut map = self.map.lock();
+        if map.get(&host).is_some_and(|entry| entry.expires.is_none()) {
+            return;
+        }
+        if max_age == 0 {
+            map.remove(&host);
+        } else {
+            map.insert(
+                host,
+                Entry {
+                    include_subdomains,
+                    expires: Some(SystemTime::now() + Duration::from_secs(max_age)),
+                },
+            );
+        }
+    }
+
+    fn read_from(&self, content: &str) {
+        let now = SystemTime::now();
+        let mut map = self.map.lock();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((host, expires)) = line.split_once(char::is_whitespace) else {
+                continue;
+            };
+            let Some(expires) = expires
+                .trim()
+                .strip_prefix('"')
+                .and_then(|s| s.strip_suffix('"'))
+            else {
+                continue;
+            };
+            let expires = if expires == UNLIMITED {
+                None
+            } else {
+                match parse_expiry(expires) {
+                    Some(expires) => Some(expires),
+                    None => continue,
+                }
+            };
+            let (host, include_subdomains) = match host.strip_prefix('.') {
+                Some(host) => (host, true),
+                None => (host, false),
+            };
+            let Some(host) = canonicalize(host) else {
+                continue;
+            };
+            let entry = Entry {
+                include_subdomains,
+                expires,
+            };
+            if !entry.is_expired(now) {
+                map.insert(host, entry);
+            }
+        }
+    }
+
+    fn write_to(&self) -> String {
+        let now = SystemTime::now();
+        let map = self.map.lock();
+        let mut content = String::from("# HSTS cache file\n");
+        for (host, entry) in map.iter().filter(|(_, entry)| !entry.is_expired(now)) {
+            content.push_str(&format!(
+                "{}{host} \"{}\"\n",
+                if entry.include_subdomains { "." } else { "" },
+                entry
+                    .expires
+                    .map(format_expiry)
+                    .unwrap_or_else(|| UNLIMITED.to_string()),
+            ));
+        }
+        content
+    }
+}
+
+/// Lowercase the domain name without the trailing dot. IP addresses are
+/// rejected.
+fn canonicalize(host: &str) -> Option<String> {
+    let host = host.strip_suffix('.').unwrap_or(host);
+    if host.is_empty() || host.starts_with('[') || host.parse::<std::net::IpAddr>().is_ok() {
+        return None;
+    }
+    Some(host.to_ascii_lowercase())
+}
+
+/// Parses the `Strict-Transport-Security` header into `max-age` and
+/// `includeSubDomains`. The header is invalid without `max-age`, or with
+/// duplicate directives.
+fn parse(value: &str) -> Option<(u64, bool)> {
+    let mut max_age = None;
+    let mut include_subdomains = false;
+    let mut names = Vec::new();
+    for directive in value.split(';') {
+        let directive = directive.trim();
+        if directive.is_empty() {
+            continue;
+        }
+        let (name, value) = match directive.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (directive, None),
+        };
+        let name = name.to_ascii_lowercase();
+        if names.contains(&name) {
+            return None;
+        }
+        match name.as_str() {
+            "max-age" => {
+                let value = value?;
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(value);
+                if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                // Long enough for any practical purpose, without overflowing
+                // the expiry time.
+                max_age = Some(value.parse().unwrap_or(u64::MAX).min(MAX_AGE_LIMIT));
+            }
+            "includesubdomains" => {
+                if value.is_some() {
+                    return None;
+                }
+                include_subdomains = true;
+            }
+            _ => {}
+        }
+        names.push(name);
+    }
+    Some((max_age?, include_subdomains))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("max-age=31536000"), Some((31536000, false)));
+        assert_eq!(
+            parse(r#"Max-Age="3600" ; includeSubDomains; preload"#),
+            Some((3600, true))
+        );
+        assert_eq!(parse("max-age=0; includeSubDomains"), Some((0, true)));
+        assert_eq!(parse("includeSubDomains"), None);
+        assert_eq!(parse("max-age=-1"), None);
+        assert_eq!(parse("max-age=1; max-age=2"), None);
+        assert_eq!(parse("max-age=1; includeSubDomains=1"), None);
+    }
+
+    #[test]
+    fn test_update() {
+        let store = HstsStore::new();
+        let update = |url: &str, value: &'static str| {
+            store.update(&url.parse().unwrap(), &HeaderValue::from_static(value))
+        };
+        update("https://example.com/", "max-age=3600; includeSubDomains");
+        update("http://plain.com/", "max-age=3600");
+        update("https://127.0.0.1/", "max-age=3600");
+        update("https://exact.com./", "max-age=3600");
+        assert!(store.is_secure("example.com"));
+        assert!(store.is_secure("a.b.EXAMPLE.com"));
+        assert!(!store.is_secure("notexample.com"));
+        assert!(!store.is_secure("plain.com"));
+        assert!(!store.is_secure("127.0.0.1"));
+        assert!(store.is_secure("exact.com"));
+        assert!(!store.is_secure("sub.exact.com"));
+
+        update("https://example.com/", "max-age=0");
+        assert!(!store.is_secure("example.com"));
+
+        // The preloaded hosts are not changed by the headers.
+        store.preload("preload.com", true);
+        update("https://preload.com/", "max-age=0");
+        assert!(store.is_secure("sub.preload.com"));
+
+        let mut url = "http://sub.preload.com:80/path?q".parse().unwrap();
+        assert!(store.upgrade(&mut url));
+        assert_eq!(url.as_str(), "https://sub.preload.com/path?q");
+        let mut url = "http://preload.com:8080/".parse().unwrap();
+        assert!(store.upgrade(&mut url));
+        assert_eq!(url.as_str(), "https://preload.com:8080/");
+        let mut url = "http://other.com/".parse().unwrap();
+        assert!(!store.upgrade(&mut url));
+    }
+
+    #[test]
+    fn test_file() {
+        let store = HstsStore::new();
+        store.update(
+            &"https://example.com/".parse().unwrap(),
+            &HeaderValue::from_static("max-age=3600; includeSubDomains"),
+        );
+        store.preload("preload.com", false);
+        let content = store.write_to();
+
+        let loaded = HstsStore::new();
+        loaded.read_from(&format!(
+            "{content}expired.com \"20000101 00:00:00\"\ngarbage\n"
+        ));
+        loaded.read_from("overflow.com \"20000101 99999999999999999:0:0\"\n");
+        let map = loaded.map.lock().clone();
+        assert_eq!(map.len(), 2);
+        assert!(map["example.com"].include_subdomains);
+        assert_eq!(
+            map["preload.com"],
+            Entry {
+                include_subdomains: false,
+                expires: None,
+            }
+        );
+    }
+}
@@ -1,34 +1,43 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashMap,
     fmt::Debug,
-    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
-    sync::mpsc::{Receiver, TryRecvError},
-    time::Instant,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{Receiver, TryRecvError},
+    },
+    time::{Duration, Instant},
 };
 
 use compio::{
     buf::bytes::Bytes,
     net::{ToSocketAddrsAsync, UdpSocket},
     quic::{
-        ClientBuilder, ConnectError, Connecting, Connection, Endpoint, EndpointConfig,
+        ClientBuilder, ClientConfig, ConnectError, Connecting, Connection, Endpoint,
+        EndpointConfig, IdleTimeout, TransportConfig, VarInt,
+        congestion::ControllerFactory,
+        crypto::rustls::QuicClientConfig,
         h3::{OpenStreams, client::SendRequest},
     },
     runtime::Runtime,
 };
-use futures_util::{Stream, StreamExt, TryStreamExt, future::Either, stream};
+use futures_channel::oneshot;
+use futures_util::{Stream, StreamExt, future::Either, stream};
 use h3::error::ConnectionError;
 use http::{
-    Request, Uri,
+    HeaderValue, Method, Request, Uri,
     uri::{Authority, Scheme},
 };
-use http_body_util::BodyDataStream;
+use http_body_util::BodyExt;
 use hyper::body::Buf;
 use socket2::{Domain, Protocol, SockAddr, Socket, Type};
 use url::Url;
 
 use crate::{
-    Body, Error, Response, Result,
-    resolve::SharedResolver,
+    Body, ConnectionInfo, Error, Response, Result, TlsInfo,
+    metrics::{ConnectionGuard, SharedMetrics},
+    resolve::{HttpsRecord, SharedResolver},
     sync::{mutex_blocking::Mutex, shared::Shared},
 };
 
@@ -56,27 +65,257 @@ mod sync {
 
 use sync::OnceLock;
 
-#[derive(Debug)]
-struct DualEndpoint {
-    v4end: Option<Endpoint>,
-    v6end: Endpoint,
+/// Options for HTTP/3 connections.
+///
+/// By default, the QUIC transport uses the defaults of [`TransportConfig`], and
+/// the TLS configuration is derived from the TLS backend of the
+/// [`ClientBuilder`](crate::ClientBuilder): a custom rustls config passed to
+/// [`use_rustls`](crate::ClientBuilder::use_rustls) is reused, otherwise the
+/// platform verifier is used.
+#[derive(Clone)]
+pub struct Http3Options {
+    tls: Option<Arc<compio::rustls::ClientConfig>>,
+    idle_timeout: Option<Duration>,
+    keep_alive_interval: Option<Duration>,
+    max_concurrent_bidi_streams: Option<u32>,
+    max_concurrent_uni_streams: Option<u32>,
+    congestion_controller: Option<Arc<dyn ControllerFactory + Send + Sync>>,
+    zero_rtt: bool,
+    pool_idle_timeout: Option<Duration>,
+    max_streams_per_connection: usize,
+    fallback_delay: Duration,
+    broken_timeout: Duration,
 }
 
-impl DualEndpoint {
-    fn client_builder(
+impl Default for Http3Options {
+    fn default() -> Self {
+        Self {
+            tls: None,
+            idle_timeout: None,
+            keep_alive_interval: None,
+            max_concurrent_bidi_streams: None,
+            max_concurrent_uni_streams: None,
+            congestion_controller: None,
+            zero_rtt: false,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            max_streams_per_connection: 100,
+            fallback_delay: Duration::from_millis(300),
+            broken_timeout: Duration::from_secs(300),
+        }
+    }
+}
+
+impl Http3Options {
+    /// Create the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a custom rustls config for QUIC connections.
+    ///
+    /// The config must support TLS 1.3. The ALPN protocols are always
+    /// overridden with `h3` and `h3-29`.
+    pub fn tls_config(mut self, config: Arc<compio::rustls::ClientConfig>) -> Self {
+        self.tls = Some(config);
+        self
+    }
+
+    /// Set the maximum duration of inactivity before the connection is closed.
+    ///
+    /// The actual timeout is the minimum of this and the peer's own idle
+    /// timeout.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the period of inactivity before sending a keep-alive packet.
+    ///
+    /// Keep-alive is disabled by default.
+    pub fn keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.keep_alive_interval = Some(interval);
+        self
+    }
+
+    /// Set the maximum number of bidirectional streams the peer may open
+    /// concurrently.
+    pub fn max_concurrent_bidi_streams(mut self, count: u32) -> Self {
+        self.max_concurrent_bidi_streams = Some(count);
+        self
+    }
+
+    /// Set the maximum number of unidirectional streams the peer may open
+    /// concurrently.
+    pub fn max_concurrent_uni_streams(mut self, count: u32) -> Self {
+        self.max_concurrent_uni_streams = Some(count);
+        self
+    }
+
+    /// Set the congestion controller of QUIC connections, e.g.
+    /// [`BbrConfig`](compio::quic::congestion::BbrConfig).
+    pub fn congestion_controller(
+        mut self,
+        factory: Arc<dyn ControllerFactory + Send + Sync>,
+    ) -> Self {
+        self.congestion_controller = Some(factory);
+        self
+    }
+
+    /// Enable 0-RTT for idempotent requests.
+    ///
+    /// When a TLS session to the server has been resumed, idempotent requests
+    /// on a new connection are sent before the handshake completes. These
+    /// requests may be replayed by an attacker, so only requests with
+    /// idempotent methods are sent this way. Other requests wait for the
+    /// handshake to complete.
+    ///
+    /// Default is `false`.
+    pub fn zero_rtt(mut self, enable: bool) -> Self {
+        self.zero_rtt = enable;
+        self
+    }
+
+    /// Set an optional timeout for idle pooled connections.
+    ///
+    /// A connection without in-flight requests is closed after it has not
+    /// been used for this duration, checked at most once per duration. Pass
+    /// `None` to keep idle connections until the server closes them.
+    ///
+    /// Default is 90 seconds.
+    pub fn pool_idle_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.pool_idle_timeout = timeout.into();
+        self
+    }
+
+    /// Set the maximum number of concurrent requests sent on one connection.
+    ///
+    /// Requests are sent on the pooled connection with the fewest in-flight
+    /// requests. When all connections to a host are saturated, a new
+    /// connection is opened.
+    ///
+    /// Default is 100, the minimum stream limit recommended for servers.
+    pub fn max_streams_per_connection(mut self, max: usize) -> Self {
+        self.max_streams_per_connection = max.max(1);
+        self
+    }
+
+    /// Set the delay before falling back to TCP when HTTP/3 is discovered via
+    /// `Alt-Svc` or DNS.
+    ///
+    /// The QUIC handshake starts first. If it hasn't completed after the
+    /// delay, a TCP connection is started as well, and the request is sent
+    /// on the one established first. Only idempotent requests with a
+    /// clonable body are raced, other requests are sent over TCP after the
+    /// delay. Requests forced to [`Version::HTTP_3`](http::Version::HTTP_3)
+    /// never fall back.
+    ///
+    /// Default is 300 milliseconds.
+    pub fn fallback_delay(mut self, delay: Duration) -> Self {
+        self.fallback_delay = delay;
+        self
+    }
+
+    /// Set how long to avoid an endpoint after QUIC to it failed. The
+    /// period doubles on each consecutive failure.
+    ///
+    /// Default is 5 minutes.
+    pub fn broken_timeout(mut self, timeout: Duration) -> Self {
+        self.broken_timeout = timeout;
+        self
+    }
+
+    fn transport_config(&self) -> Result<TransportConfig> {
+        let mut transport = TransportConfig::default();
+        if let Some(timeout) = self.idle_timeout {
+            let timeout = IdleTimeout::try_from(timeout)
+                .map_err(|e| Error::H3Client(format!("invalid idle timeout: {e}")))?;
+            transport.max_idle_timeout(Some(timeout));
+        }
+        transport.keep_alive_interval(self.keep_alive_interval);
+        if let Some(count) = self.max_concurrent_bidi_streams {
+            transport.max_concurrent_bidi_streams(VarInt::from_u32(count));
+        }
+        if let Some(count) = self.max_concurrent_uni_streams {
+            transport.max_concurrent_uni_streams(VarInt::from_u32(count));
+        }
+        if let Some(factory) = &self.congestion_controller {
+            transport.congestion_controller_factory(factory.clone());
+        }
+        Ok(transport)
+    }
+
+    fn client_config(
+        &self,
+        fallback_tls: Option<&Arc<compio::rustls::ClientConfig>>,
         accept_invalid_certs: bool,
-    ) -> Result<ClientBuilder<compio::rustls::ClientConfig>> {
-        let builder = if accept_invalid_certs {
-            ClientBuilder::new_with_no_server_verification()
-        } else {
-            ClientBuilder::new_with_platform_verifier()?.with_key_log()
+    ) -> Result<ClientConfig> {
+        let mut config = match self.tls.as_ref().or(fallback_tls) {
+            Some(tls) => {
+                let mut tls = compio::rustls::ClientConfig::clone(tls);
+                tls.alpn_protocols = vec![b"h3".into(), b"h3-29".into()];
+                tls.enable_early_data = self.zero_rtt;
+                let crypto = QuicClientConfig::try_from(tls)
+                    .map_err(|e| Error::H3Client(format!("invalid TLS config: {e}")))?;
+                ClientConfig::new(Arc::new(crypto))
+            }
+            None => {
+                let builder = if accept_invalid_certs {
+                    ClientBuilder::new_with_no_server_verification()
+                } else {
+                    ClientBuilder::new_with_platform_verifier()?.with_key_log()
+                };
+                builder.with_alpn_protocols(&["h3", "h3-29"]).build()
+            }
         };
-        Ok(builder.with_alpn_protocols(&["h3"]))
+        config.transport_config(Arc::new(self.transport_config()?));
+        Ok(config)
+    }
+}
+
+impl Debug for Http3Options {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Http3Options")
+            .field("tls", &self.tls.is_some())
+            .field("idle_timeout", &self.idle_timeout)
+            .field("keep_alive_interval", &self.keep_alive_interval)
+            .field(
+                "max_concurrent_bidi_streams",
+                &self.max_concurrent_bidi_streams,
+            )
+            .field(
+                "max_concurrent_uni_streams",
+                &self.max_concurrent_uni_streams,
+            )
+            .field(
+                "congestion_controller",
+                &self.congestion_controller.is_some(),
+            )
+            .field("zero_rtt", &self.zero_rtt)
+            .field("pool_idle_timeout", &self.pool_idle_timeout)
+            .field(
+                "max_streams_per_connection",
+                &self.max_streams_per_connection,
+            )
+            .field("fallback_delay", &self.fallback_delay)
+            .field("broken_timeout", &self.broken_timeout)
+            .finish()
     }
+}
 
-    fn new(accept_invalid_certs: bool) -> Result<Self> {
-        let client_config = Self::client_builder(accept_invalid_certs)?.build();
+/// The QUIC version of draft 29, used by `h3-29` alternative services.
+const QUIC_DRAFT_29: u32 = 0xff00_001d;
 
+#[derive(Debug)]
+struct DualEndpoint {
+    v4end: Option<Endpoint>,
+    v6end: Endpoint,
+    draft29: ClientConfig,
+}
+
+impl DualEndpoint {
+    fn new(client_config: ClientConfig) -> Result<Self> {
+        let mut draft29 = client_config.clone();
+        draft29.version(QUIC_DRAFT_29);
         let v6sock = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
         let dual_stack = v6sock.set_only_v6(false).is_ok();
         v6sock.bind(&SockAddr::from(SocketAddrV6::new(
@@ -113,7 +352,11 @@ impl DualEndpoint {
             )?)
         };
 
-        Ok(Self { v4end, v6end })
+        Ok(Self {
+            v4end,
+            v6end,
+            draft29,
+        })
     }
 
     fn end(&self, is_v4: bool) -> &Endpoint {
@@ -129,55 +372,87 @@ impl DualEndpoint {
         &self,
         remote: SocketAddr,
         server_name: &str,
+        draft29: bool,
     ) -> std::result::Result<Connecting, ConnectError> {
+        let config = draft29.then(|| self.draft29.clone());
         self.end(remote.is_ipv4())
-            .connect(remote, server_name, None)
+            .connect(remote, server_name, config)
     }
 }
 
+type H3Connection = (
+    h3::client::Connection<Connection, Bytes>,
+    SendRequest<OpenStreams, Bytes>,
+);
+
 #[derive(Debug, Clone)]
 struct Connector {
     endpoint: Shared<OnceLock<DualEndpoint>>,
+    options: Http3Options,
+    tls: Option<Arc<compio::rustls::ClientConfig>>,
     accept_invalid_certs: bool,
     resolver: Option<SharedResolver>,
 }
 
 impl Connector {
-    pub fn new(accept_invalid_certs: bool, resolver: Option<SharedResolver>) -> Self {
+    pub fn new(
+        options: Http3Options,
+        tls: Option<Arc<compio::rustls::ClientConfig>>,
+        accept_invalid_certs: bool,
+        resolver: Option<SharedResolver>,
+    ) -> Self {
         Self {
             endpoint: Shared::new(OnceLock::new()),
+            options,
+            tls,
             accept_invalid_certs,
             resolver,
         }
     }
 
     fn endpoint(&self) -> Result<&DualEndpoint> {
-        self.endpoint
-            .get_or_try_init(|| DualEndpoint::new(self.accept_invalid_certs))
+        self.endpoint.get_or_try_init(|| {
+            DualEndpoint::new(
+                self.options
+                    .client_config(self.tls.as_ref(), self.accept_invalid_certs)?,
+            )
+        })
     }
 
+    /// Connect to `key`, or its alternative endpoint if any. The address
+    /// `hints` are tried before the resolved addresses. If `early` is true
+    /// and 0-RTT is enabled, the connection is returned before the handshake
+    /// completes, together with the QUIC connection to wait on.
     pub async fn connect(
         &self,
-        dest: Uri,
-    ) -> Result<(
-        h3::client::Connection<Connection, Bytes>,
-        SendRequest<OpenStreams, Bytes>,
-    )> {
-        let host = dest.host().expect("there should be host");
-        // `Uri::host()` includes brackets for IPv6, we must strip them.
-        let host = host
-            .strip_prefix('[')
-            .and_then(|h| h.strip_suffix(']'))
-            .unwrap_or(host);
+        (scheme, origin, alt): Key,
+        hints: &[IpAddr],
+        early: bool,
+    ) -> Result<(H3Connection, Option<Connection>, ConnectionInfo)> {
+        // The certificate is always verified against the origin.
+        let server_name = strip_brackets(origin.host()).to_string();
+        let draft29 = alt.as_ref().is_some_and(|alt| alt.draft29);
+        let dest = domain_as_uri(scheme, alt.map(|alt| alt.authority).unwrap_or(origin));
+        let host = strip_brackets(dest.host().expect("there should be host"));
         let port = dest.port_u16().unwrap_or(443);
 
         let endpoint = self.endpoint()?;
+        let early = early && self.options.zero_rtt;
 
         let mut err = None;
+        for remote in hints.iter().map(|ip| SocketAddr::new(*ip, port)) {
+            match Self::connect_impl(endpoint, remote, &server_name, draft29, early).await {
+                Ok(conn) => return Ok(conn),
+                Err(e) => err = Some(e),
+            }
+        }
         let mut addr_stream = self.get_addr_stream(&dest, host, port).await?;
         while let Some(remote) = addr_stream.next().await {
-            match Self::connect_impl(endpoint, remote, host).await {
-                Ok(conn) => return Ok(compio::quic::h3::client::new(conn).await?),
+            if hints.contains(&remote.ip()) {
+                continue;
+            }
+            match Self::connect_impl(endpoint, remote, &server_name, draft29, early).await {
+                Ok(conn) => return Ok(conn),
                 Err(e) => err = Some(e),
             }
         }
@@ -211,22 +486,126 @@ impl Connector {
         endpoint: &DualEndpoint,
         remote: SocketAddr,
         server_name: &str,
-    ) -> Result<Connection> {
-        Ok(endpoint.connect(remote, server_name)?.await?)
+        draft29: bool,
+        early: bool,
+    ) -> Result<(H3Connection, Option<Connection>, ConnectionInfo)> {
+        let start = Instant::now();
+        let connecting = endpoint.connect(remote, server_name, draft29)?;
+        let (conn, is_early) = if early {
+            match connecting.into_0rtt() {
+                Ok(conn) => (conn, true),
+                Err(connecting) => (connecting.await?, false),
+            }
+        } else {
+            (connecting.await?, false)
+        };
+        let mut info = Self::connection_info(endpoint, &conn, is_early);
+        info.tls_handshake = info.handshaken.map(|handshaken| handshaken - start);
+        info.ready = Some(Instant::now());
+        let early_conn = is_early.then(|| conn.clone());
+        Ok((compio::quic::h3::client::new(conn).await?, early_conn, info))
+    }
+
+    /// The details of the connection. The TLS session is unknown before the
+    /// handshake completes.
+    fn connection_info(endpoint: &DualEndpoint, conn: &Connection, early: bool) -> ConnectionInfo {
+        let remote = conn.remote_address();
+        // The dual-stack socket reports IPv4 peers as mapped addresses.
+        let remote_addr = match remote {
+            SocketAddr::V6(addr) => match addr.ip().to_ipv4_mapped() {
+                Some(ip) => SocketAddr::new(ip.into(), addr.port()),
+                None => remote,
+            },
+            SocketAddr::V4(_) => remote,
+        };
+        let local_addr =
+            endpoint
+                .end(remote_addr.is_ipv4())
+                .local_addr()
+                .ok()
+                .map(|addr| match conn.local_ip() {
+                    Some(ip) => SocketAddr::new(ip, addr.port()),
+                    None => addr,
+                });
+        let now = Instant::now();
+        let (tls, alpn, handshaken) = if early {
+            (None, None, None)
+        } else {
+            (
+                conn.peer_identity().map(|certs| TlsInfo::from_quic(&certs)),
+                conn.clone()
+                    .handshake_data()
+                    .ok()
+                    .and_then(|data| data.protocol),
+                Some(now),
+            )
+        };
+        ConnectionInfo {
+            remote_addr: Some(remote_addr),
+            local_addr,
+            tls,
+            alpn,
+            resolved: None,
+            // The transport and TLS handshakes are combined in QUIC.
+            connected: handshaken,
+            handshaken,
+            tls_handshake: None,
+            ready: None,
+        }
     }
 }
 
 #[derive(Clone)]
 pub struct PoolClient {
     inner: SendRequest<OpenStreams, Bytes>,
+    // The QUIC connection, if it was established with 0-RTT.
+    early: Option<Connection>,
+    // The count of in-flight requests on this connection.
+    active: Shared<AtomicUsize>,
+    // The stream reserved for the request, if checked out.
+    _slot: Option<Shared<StreamSlot>>,
+    info: Shared<ConnectionInfo>,
 }
 
 impl PoolClient {
-    pub fn new(tx: SendRequest<OpenStreams, Bytes>) -> Self {
-        Self { inner: tx }
+    pub fn new(
+        tx: SendRequest<OpenStreams, Bytes>,
+        early: Option<Connection>,
+        info: ConnectionInfo,
+    ) -> Self {
+        Self {
+            inner: tx,
+            early,
+            active: Shared::new(AtomicUsize::new(0)),
+            _slot: None,
+            info: Shared::new(info),
+        }
+    }
+
+    /// Check out the connection, reserving a stream for a request.
+    fn checkout(&self) -> Self {
+        Self {
+            _slot: Some(Shared::new(StreamSlot::new(&self.active))),
+            ..self.clone()
+        }
+    }
+
+    /// Whether the 0-RTT data of this connection was rejected by the server.
+    async fn early_rejected(&self) -> bool {
+        match &self.early {
+            Some(conn) => !conn.accepted_0rtt().await.unwrap_or(false),
+            None => false,
+        }
     }
 
     pub async fn send_request(&mut self, req: Request<Body>, url: Url) -> Result<Response> {
+        if let Some(conn) = &self.early
+            && !is_idempotent(req.method())
+            && !conn.accepted_0rtt().await?
+        {
+            return Err(Error::H3Client("0-RTT data rejected by the server".into()));
+        }
+
         let (head, req_body) = req.into_parts();
         let mut req = Request::from_parts(head, ());
 
@@ -238,22 +617,54 @@ impl PoolClient {
         }
 
         let mut stream = self.inner.send_request(req).await?;
+        let sent = Instant::now();
 
-        let mut req_body = BodyDataStream::new(req_body);
-        while let Some(b) = req_body.try_next().await? {
-            stream.send_data(b).await?;
+        let mut req_body = std::pin::pin!(req_body);
+        while let Some(frame) = req_body.frame().await {
+            match frame?.into_data() {
+                Ok(b) => stream.send_data(b).await?,
+                Err(frame) => {
+                    if let Ok(trailers) = frame.into_trailers() {
+                        stream.send_trailers(trailers).await?;
+                    }
+                }
+            }
         }
 
         stream.finish().await?;
 
-        let resp = stream.recv_response().await?;
+        let mut resp = stream.recv_response().await?;
+        let received = Instant::now();
+        resp.extensions_mut()
+            .insert(ConnectionInfo::clone(&self.info));
 
         let mut resp_body = Vec::<u8>::new();
         while let Some(chunk) = stream.recv_data().await? {
             resp_body.extend(chunk.chunk())
         }
+        let trailers = stream.recv_trailers().await?;
+
+        let mut res = Response::with_body(resp, Bytes::from(resp_body), trailers, url);
+        res.sent = Some(sent);
+        res.received = received;
+        Ok(res)
+    }
+}
+
+/// A stream reserved on a connection, released when dropped.
+#[derive(Debug)]
+struct StreamSlot(Shared<AtomicUsize>);
+
+impl StreamSlot {
+    fn new(active: &Shared<AtomicUsize>) -> Self {
+        active.fetch_add(1, Ordering::AcqRel);
+        Self(active.clone())
+    }
+}
 
-        Ok(Response::with_body(resp, Bytes::from(resp_body), url))
+impl Drop for StreamSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
     }
 }
 
@@ -268,6 +679,7 @@ struct PoolConnection {
     // This receives errors from polling h3 driver.
     close_rx: Receiver<ConnectionError>,
     client: PoolClient,
+    // The last time this connection was checked out.
     idle_timeout: Instant,
 }
 
@@ -282,7 +694,11 @@ impl PoolConnection {
 
     pub fn pool(&mut self) -> PoolClient {
         self.idle_timeout = Instant::now();
-        self.client.clone()
+        self.client.checkout()
+    }
+
+    pub fn active_streams(&self) -> usize {
+        self.client.active.load(Ordering::Acquire)
     }
 
     pub fn is_invalid(&self) -> bool {
@@ -292,19 +708,103 @@ impl PoolConnection {
             Ok(_) => true,
         }
     }
+
+    pub fn is_expired(&self, now: Instant, idle_timeout: Option<Duration>) -> bool {
+        match idle_timeout {
+            Some(timeout) => {
+                self.active_streams() == 0 && now.duration_since(self.idle_timeout) >= timeout
+            }
+            None => false,
+        }
+    }
+}
+
+/// An alternative endpoint of an origin, advertised by `Alt-Svc`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct AltEndpoint {
+    pub authority: Authority,
+    /// Whether to use QUIC draft 29 (`h3-29`).
+    pub draft29: bool,
 }
 
-type Key = (Scheme, Authority);
+/// The origin, and the alternative endpoint to connect to if any.
+type Key = (Scheme, Authority, Option<AltEndpoint>);
+
+type Waiter = oneshot::Sender<std::result::Result<PoolClient, Arc<Error>>>;
 
 #[derive(Debug)]
 struct PoolInner {
-    connecting: HashSet<Key>,
-    idle_conns: HashMap<Key, PoolConnection>,
+    // Requesters waiting for the in-flight handshake of a key.
+    connecting: HashMap<Key, Vec<Waiter>>,
+    idle_conns: HashMap<Key, Vec<PoolConnection>>,
+    idle_timeout: Option<Duration>,
+    max_streams: usize,
+    // Whether the task evicting the idle connections is running.
+    sweeping: bool,
 }
 
 impl PoolInner {
     fn insert(&mut self, key: Key, conn: PoolConnection) {
-        self.idle_conns.insert(key, conn);
+        self.idle_conns.entry(key).or_default().push(conn);
+    }
+
+    fn evict(&mut self) {
+        let now = Instant::now();
+        let idle_timeout = self.idle_timeout;
+        self.idle_conns.retain(|_, conns| {
+            conns.retain(|conn| !conn.is_invalid() && !conn.is_expired(now, idle_timeout));
+            !conns.is_empty()
+        });
+    }
+
+    fn try_pool(&mut self, key: &Key) -> Option<PoolClient> {
+        let max_streams = self.max_streams;
+        self.idle_conns
+            .get_mut(key)?
+            .iter_mut()
+            .filter(|conn| conn.active_streams() < max_streams)
+            .min_by_key(|conn| conn.active_streams())
+            .map(|conn| conn.pool())
+    }
+}
+
+enum Checkout {
+    Pooled(PoolClient),
+    Wait(oneshot::Receiver<std::result::Result<PoolClient, Arc<Error>>>),
+    Connect(ConnectingGuard),
+}
+
+/// Marks a key as connecting. If it is dropped before the connection is
+/// established, the waiters are woken up and try again.
+struct ConnectingGuard {
+    pool: Pool,
+    key: Option<Key>,
+}
+
+impl ConnectingGuard {
+    /// Notify the waiters of the error, which is shared with them if any.
+    fn fail(mut self, e: Error) -> Error {
+        let waiters = self
+            .key
+            .take()
+            .and_then(|key| self.pool.inner.lock().connecting.remove(&key))
+            .unwrap_or_default();
+        if waiters.is_empty() {
+            return e;
+        }
+        let e = Arc::new(e);
+        for waiter in waiters {
+            waiter.send(Err(e.clone())).ok();
+        }
+        Error::H3Shared(e)
+    }
+}
+
+impl Drop for ConnectingGuard {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.pool.inner.lock().connecting.remove(&key);
+        }
     }
 }
 
@@ -314,111 +814,306 @@ struct Pool {
 }
 
 impl Pool {
-    pub fn new() -> Self {
+    pub fn new(idle_timeout: Option<Duration>, max_streams: usize) -> Self {
         Self {
             inner: Shared::new(Mutex::new(PoolInner {
-                connecting: HashSet::new(),
+                connecting: HashMap::new(),
                 idle_conns: HashMap::new(),
+                idle_timeout,
+                max_streams,
+                sweeping: false,
             })),
         }
     }
 
-    pub fn connecting(&self, key: Key) -> Result<()> {
-        let mut inner = self.inner.lock();
-        if !inner.connecting.insert(key.clone()) {
-            return Err(Error::H3Client(format!(
-                "HTTP/3 connecting already in progress for {key:?}"
-            )));
+    /// Evict the expired connections periodically while the pool isn't
+    /// empty, so that they are closed even if no more requests are sent.
+    fn sweep(&self, inner: &mut PoolInner) {
+        let Some(timeout) = inner.idle_timeout else {
+            return;
+        };
+        if inner.sweeping {
+            return;
         }
-        Ok(())
+        inner.sweeping = true;
+        let pool = Shared::downgrade(&self.inner);
+        compio::runtime::spawn(async move {
+            loop {
+                compio::time::sleep(timeout).await;
+                let Some(pool) = pool.upgrade() else {
+                    break;
+                };
+                let mut inner = pool.lock();
+                inner.evict();
+                if inner.idle_conns.is_empty() {
+                    inner.sweeping = false;
+                    break;
+                }
+            }
+        })
+        .detach();
     }
 
-    pub fn try_pool(&self, key: &Key) -> Option<PoolClient> {
+    fn checkout(&self, key: &Key) -> Checkout {
         let mut inner = self.inner.lock();
-        if let Some(conn) = inner.idle_conns.get(key) {
-            // We check first if the connection still valid
-            // and if not, we remove it from the pool.
-            if conn.is_invalid() {
-                inner.idle_conns.remove(key);
-                return None;
-            }
+        // We check first if the connections are still valid
+        // and if not, we remove them from the pool.
+        inner.evict();
+
+        if let Some(client) = inner.try_pool(key) {
+            return Checkout::Pooled(client);
+        }
+
+        if let Some(waiters) = inner.connecting.get_mut(key) {
+            let (tx, rx) = oneshot::channel();
+            waiters.push(tx);
+            return Checkout::Wait(rx);
         }
 
-        inner.idle_conns.get_mut(key).map(|conn| conn.pool())
+        inner.connecting.insert(key.clone(), Vec::new());
+        Checkout::Connect(ConnectingGuard {
+            pool: self.clone(),
+            key: Some(key.clone()),
+        })
+    }
+
+    pub fn remove(&self, key: &Key, client: &PoolClient) {
+        let mut inner = self.inner.lock();
+        if let Some(conns) = inner.idle_conns.get_mut(key) {
+            conns.retain(|conn| !Shared::ptr_eq(&conn.client.active, &client.active));
+        }
     }
 
     pub fn new_connection(
-        &mut self,
-        key: Key,
-        mut driver: h3::client::Connection<Connection, Bytes>,
-        tx: SendRequest<OpenStreams, Bytes>,
+        &self,
+        mut connecting: ConnectingGuard,
+        (mut driver, tx): H3Connection,
+        early: Option<Connection>,
+        info: ConnectionInfo,
+        guard: Option<ConnectionGuard>,
     ) -> PoolClient {
         let (close_tx, close_rx) = std::sync::mpsc::channel();
         compio::runtime::spawn(async move {
             let e = driver.wait_idle().await;
+            drop(guard);
             close_tx.send(e).ok();
         })
         .detach();
 
+        let key = connecting
+            .key
+            .take()
+            .expect("connecting should hold the key");
+
         let mut inner = self.inner.lock();
 
-        let client = PoolClient::new(tx);
-        let conn = PoolConnection::new(client.clone(), close_rx);
-        inner.insert(key.clone(), conn);
+        let client = PoolClient::new(tx, early, info);
+        let checked_out = client.checkout();
 
         // We clean up "connecting" here so we don't have to acquire the lock again.
-        let existed = inner.connecting.remove(&key);
-        debug_assert!(existed, "key not in connecting set");
+        let waiters = inner.connecting.remove(&key);
+        debug_assert!(waiters.is_some(), "key not in connecting set");
+        // The waiters beyond the stream limit are dropped, and check out again.
+        let max_streams = inner.max_streams;
+        for waiter in waiters.into_iter().flatten() {
+            if client.active.load(Ordering::Acquire) >= max_streams {
+                break;
+            }
+            waiter.send(Ok(client.checkout())).ok();
+        }
 
-        client
+        inner.insert(key, PoolConnection::new(client, close_rx));
+        self.sweep(&mut inner);
+        checked_out
     }
 }
 
+/// The route of an HTTP/3 request.
+#[derive(Debug, Clone)]
+pub(crate) struct Route {
+    key: Key,
+    /// The address hints tried before the resolved addresses.
+    hints: Vec<IpAddr>,
+    /// The value of the `Alt-Used` header sent with the request.
+    pub alt_used: Option<HeaderValue>,
+}
+
+/// An endpoint where QUIC failed recently.
+#[derive(Debug)]
+struct Broken {
+    until: Instant,
+    failures: u32,
+}
+
 #[derive(Debug, Clone)]
 pub struct Client {
     pool: Pool,
     connector: Connector,
+    broken: Shared<Mutex<HashMap<Key, Broken>>>,
+    metrics: Option<SharedMetrics>,
 }
 
 impl Client {
-    pub fn new(accept_invalid_certs: bool, resolver: Option<SharedResolver>) -> Self {
+    pub fn new(
+        options: Http3Options,
+        tls: Option<Arc<compio::rustls::ClientConfig>>,
+        accept_invalid_certs: bool,
+        resolver: Option<SharedResolver>,
+        metrics: Option<SharedMetrics>,
+    ) -> Self {
         Self {
-            pool: Pool::new(),
-            connector: Connector::new(accept_invalid_certs, resolver),
+            pool: Pool::new(
+                options.pool_idle_timeout,
+                options.max_streams_per_connection,
+            ),
+            connector: Connector::new(options, tls, accept_invalid_certs, resolver),
+            broken: Shared::new(Mutex::new(HashMap::new())),
+            metrics,
         }
     }
 
-    async fn get_pooled_client(&mut self, key: Key) -> Result<PoolClient> {
-        if let Some(client) = self.pool.try_pool(&key) {
-            return Ok(client);
+    /// The route to the origin of `uri`, or to `alt` if specified.
+    pub fn route(&self, uri: &Uri, alt: Option<AltEndpoint>, hints: Vec<IpAddr>) -> Result<Route> {
+        let (scheme, origin) = extract_domain(uri)?;
+        Ok(Route {
+            key: (scheme, origin, alt),
+            hints,
+            alt_used: None,
+        })
+    }
+
+    /// The delay before falling back to TCP if QUIC is not connected.
+    pub fn fallback_delay(&self) -> Duration {
+        self.connector.options.fallback_delay
+    }
+
+    /// Whether QUIC to the endpoint of the route failed recently.
+    pub fn is_broken(&self, route: &Route) -> bool {
+        self.broken
+            .lock()
+            .get(&route.key)
+            .is_some_and(|broken| broken.until > Instant::now())
+    }
+
+    fn mark_broken(&self, key: &Key) {
+        let period = self.connector.options.broken_timeout;
+        let mut broken = self.broken.lock();
+        let entry = broken.entry(key.clone()).or_insert(Broken {
+            until: Instant::now(),
+            failures: 0,
+        });
+        // The period doubles on each consecutive failure.
+        entry.until = Instant::now() + period * (1 << entry.failures.min(6));
+        entry.failures += 1;
+    }
+
+    async fn get_pooled_client(&self, route: &Route, early: bool) -> Result<PoolClient> {
+        let key = &route.key;
+        loop {
+            match self.pool.checkout(key) {
+                Checkout::Pooled(client) => return Ok(client),
+                Checkout::Wait(rx) => match rx.await {
+                    Ok(Ok(client)) => return Ok(client),
+                    Ok(Err(e)) => return Err(Error::H3Shared(e)),
+                    // The connecting task was cancelled, or the connection is
+                    // full, try again.
+                    Err(_) => continue,
+                },
+                Checkout::Connect(connecting) => {
+                    return match self
+                        .connector
+                        .connect(key.clone(), &route.hints, early)
+                        .await
+                    {
+                        Ok((conn, early, info)) => {
+                            self.broken.lock().remove(key);
+                            let guard = self
+                                .metrics
+                                .as_ref()
+                                .map(|metrics| metrics.open(key.1.host(), &info));
+                            Ok(self
+                                .pool
+                                .new_connection(connecting, conn, early, info, guard))
+                        }
+                        Err(e) => {
+                            self.mark_broken(key);
+                            Err(connecting.fail(e))
+                        }
+                    };
+                }
+            }
+        }
+    }
+
+    /// Get a pooled connection for the request, or establish a new one.
+    pub async fn connect(&self, route: &Route, method: &Method) -> Result<PoolClient> {
+        self.get_pooled_client(route, is_idempotent(method)).await
+    }
+
+    /// Send the request on a connection from [`Client::connect`].
+    pub async fn send(
+        &self,
+        route: &Route,
+        mut pooled: PoolClient,
+        mut req: Request<Body>,
+        url: Url,
+    ) -> Result<Response> {
+        if let Some(alt_used) = &route.alt_used {
+            req.headers_mut().insert("alt-used", alt_used.clone());
+        }
+        if pooled.early.is_none() {
+            return pooled.send_request(req, url).await;
         }
 
-        let dest = domain_as_uri(key.clone());
-        self.pool.connecting(key.clone())?;
-        let (driver, tx) = self.connector.connect(dest).await?;
-        Ok(self.pool.new_connection(key, driver, tx))
+        // Keep a copy of the request to retry it if 0-RTT data is rejected.
+        let retry = crate::util::try_clone_request(&req);
+        match pooled.send_request(req, url.clone()).await {
+            Err(e) if pooled.early_rejected().await => {
+                self.pool.remove(&route.key, &pooled);
+                let Some(retry) = retry else {
+                    return Err(e);
+                };
+                let mut pooled = self.get_pooled_client(route, false).await?;
+                pooled.send_request(retry, url).await
+            }
+            res => res,
+        }
     }
 
-    async fn send_request(mut self, key: Key, req: Request<Body>, url: Url) -> Result<Response> {
-        let mut pooled = self.get_pooled_client(key).await?;
-        pooled.send_request(req, url).await
+    /// Send the request over HTTP/3 with the route.
+    pub async fn request(&self, route: Route, req: Request<Body>, url: Url) -> Result<Response> {
+        let pooled = self.connect(&route, req.method()).await?;
+        self.send(&route, pooled, req, url).await
     }
 
-    pub async fn request(&self, mut req: Request<Body>, url: Url) -> Result<Response> {
-        let pool_key = extract_domain(req.uri_mut())?;
-        self.clone().send_request(pool_key, req, url).await
+    /// Looks up the HTTPS DNS record of the origin, if it advertises HTTP/3.
+    /// Lookup failures are ignored.
+    pub async fn discover(&self, uri: &Uri) -> Option<HttpsRecord> {
+        let resolver = self.connector.resolver.as_ref()?;
+        let record = resolver.resolve_https(uri).await.ok()??;
+        record
+            .alpn
+            .iter()
+            .any(|alpn| alpn == "h3")
+            .then_some(record)
     }
 }
 
-fn extract_domain(uri: &mut Uri) -> Result<Key> {
-    let uri_clone = uri.clone();
-    match (uri_clone.scheme(), uri_clone.authority()) {
+pub(crate) fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE | Method::PUT | Method::DELETE
+    )
+}
+
+fn extract_domain(uri: &Uri) -> Result<(Scheme, Authority)> {
+    match (uri.scheme(), uri.authority()) {
         (Some(scheme), Some(auth)) => Ok((scheme.clone(), auth.clone())),
         _ => Err(Error::H3Client("failed to extract domain".into())),
     }
 }
 
-fn domain_as_uri((scheme, auth): Key) -> Uri {
+fn domain_as_uri(scheme: Scheme, auth: Authority) -> Uri {
     http::uri::Builder::new()
         .scheme(scheme)
         .authority(auth)
@@ -426,3 +1121,10 @@ fn domain_as_uri((scheme, auth): Key) -> Uri {
         .build()
         .expect("domain is valid Uri")
 }
+
+/// `Uri::host()` includes brackets for IPv6, we must strip them.
+fn strip_brackets(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host)
+}
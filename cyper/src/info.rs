@@ -0,0 +1,193 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use crate::Response;
+
+/// The details of the connection a response is received from, recorded when
+/// the connection is established.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ConnectionInfo {
+    pub(crate) remote_addr: Option<SocketAddr>,
+    pub(crate) local_addr: Option<SocketAddr>,
+    pub(crate) tls: Option<TlsInfo>,
+    pub(crate) alpn: Option<Vec<u8>>,
+    /// When the host name is resolved.
+    pub(crate) resolved: Option<Instant>,
+    /// When the transport is connected.
+    pub(crate) connected: Option<Instant>,
+    /// When the TLS handshake completes.
+    pub(crate) handshaken: Option<Instant>,
+    /// How long the TLS handshake takes.
+    pub(crate) tls_handshake: Option<Duration>,
+    /// When the connection is ready for requests.
+    pub(crate) ready: Option<Instant>,
+}
+
+impl ConnectionInfo {
+    /// Whether the connection was ready before `start`, i.e. it is reused
+    /// from the pool by a request started at `start`.
+    pub(crate) fn is_reused(&self, start: Instant) -> bool {
+        self.ready.is_some_and(|ready| ready < start)
+    }
+}
+
+/// The timings of a request, like the `-w` timings of curl.
+///
+/// Each duration is measured from the start of the request to the end of the
+/// phase. The phases of establishing the connection are `None` when a pooled
+/// connection is reused. After redirects, they are the timings of the last
+/// request, except [`total`](Self::total).
+///
+/// It could be found in [`Response::extensions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timings {
+    reused: bool,
+    name_lookup: Option<Duration>,
+    connect: Option<Duration>,
+    tls_handshake: Option<Duration>,
+    request_sent: Option<Duration>,
+    first_byte: Duration,
+    total: Duration,
+}
+
+impl Timings {
+    /// The timings of `res`, to the last request started at `start`, and the
+    /// first one at `first_start`.
+    pub(crate) fn new(first_start: Instant, start: Instant, res: &Response) -> Self {
+        let info = res.extensions().get::<ConnectionInfo>();
+        let reused = info.is_some_and(|info| info.is_reused(start));
+        let phase = |at: Option<Instant>| {
+            at.filter(|_| !reused)
+                .map(|at| at.saturating_duration_since(start))
+        };
+        Self {
+            reused,
+            name_lookup: phase(info.and_then(|info| info.resolved)),
+            connect: phase(info.and_then(|info| info.connected)),
+            tls_handshake: phase(info.and_then(|info| info.handshaken)),
+            // The headers are written in the background over HTTP/1 and
+            // HTTP/2, right after the new connection is ready.
+            request_sent: res
+                .sent
+                .map(|sent| sent.saturating_duration_since(start))
+                .or_else(|| phase(info.and_then(|info| info.ready))),
+            first_byte: res.received.saturating_duration_since(start),
+            total: res.received.saturating_duration_since(first_start),
+        }
+    }
+
+    /// Whether the request is sent on a reused pooled connection.
+    pub fn reused(&self) -> bool {
+        self.reused
+    }
+
+    /// The time until the host name is resolved.
+    pub fn name_lookup(&self) -> Option<Duration> {
+        self.name_lookup
+    }
+
+    /// The time until the transport is connected. Over HTTP/3, it is when the
+    /// QUIC handshake completes.
+    pub fn connect(&self) -> Option<Duration> {
+        self.connect
+    }
+
+    /// The time until the TLS handshake completes.
+    pub fn tls_handshake(&self) -> Option<Duration> {
+        self.tls_handshake
+    }
+
+    /// The time until the request headers are sent.
+    ///
+    /// Over HTTP/1 and HTTP/2, it is only known on a new connection, when the
+    /// connection is ready.
+    pub fn request_sent(&self) -> Option<Duration> {
+        self.request_sent
+    }
+
+    /// The time until the response headers are received.
+    pub fn first_byte(&self) -> Duration {
+        self.first_byte
+    }
+
+    /// The time from the start of the first request, before redirects, until
+    /// the response headers are received. Reading the body is not included.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+}
+
+/// The details of a TLS session.
+///
+/// It is only recorded with the rustls backend, including HTTP/3.
+#[derive(Debug, Clone, Default)]
+pub struct TlsInfo {
+    peer_certificates: Vec<Vec<u8>>,
+    protocol_version: Option<String>,
+    cipher_suite: Option<String>,
+}
+
+impl TlsInfo {
+    #[cfg(feature = "rustls")]
+    pub(crate) fn from_rustls(conn: &compio::rustls::CommonState) -> Self {
+        Self {
+            peer_certificates: conn
+                .peer_certificates()
+                .unwrap_or_default()
+                .iter()
+                .map(|cert| cert.to_vec())
+                .collect(),
+            protocol_version: conn.protocol_version().map(protocol_version),
+            cipher_suite: conn.negotiated_cipher_suite().map(|suite| {
+                let suite = suite.suite();
+                suite
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("{:#06x}", u16::from(suite)))
+            }),
+        }
+    }
+
+    /// The TLS session of a QUIC connection. The cipher suite is unknown.
+    #[cfg(feature = "http3")]
+    pub(crate) fn from_quic(certs: &[compio::rustls::pki_types::CertificateDer]) -> Self {
+        Self {
+            peer_certificates: certs.iter().map(|cert| cert.to_vec()).collect(),
+            protocol_version: Some(protocol_version(compio::rustls::ProtocolVersion::TLSv1_3)),
+            cipher_suite: None,
+        }
+    }
+
+    /// The DER-encoded certificate chain of the peer, starting with the
+    /// end-entity certificate.
+    pub fn peer_certificates(&self) -> &[Vec<u8>] {
+        &self.peer_certificates
+    }
+
+    /// The DER-encoded end-entity certificate of the peer.
+    pub fn peer_certificate(&self) -> Option<&[u8]> {
+        self.peer_certificates.first().map(Vec::as_slice)
+    }
+
+    /// The negotiated protocol version, e.g. `"TLSv1.3"`.
+    pub fn protocol_version(&self) -> Option<&str> {
+        self.protocol_version.as_deref()
+    }
+
+    /// The name of the negotiated cipher suite, e.g.
+    /// `"TLS13_AES_128_GCM_SHA256"`.
+    pub fn cipher_suite(&self) -> Option<&str> {
+        self.cipher_suite.as_deref()
+    }
+}
+
+#[cfg(feature = "rustls")]
+fn protocol_version(version: compio::rustls::ProtocolVersion) -> String {
+    match version.as_str() {
+        // `TLSv1_3` to `TLSv1.3`.
+        Some(name) => name.replace('_', "."),
+        None => format!("{:#06x}", u16::from(version)),
+    }
+}
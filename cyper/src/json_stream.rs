@@ -0,0 +1,194 @@
+use compio::bytes::{Buf, BytesMut};
+use futures_util::{Stream, StreamExt};
+use serde::de::{DeserializeOwned, Error as _};
+
+use crate::{ResponseBody, Result};
+
+/// The record separator of JSON text sequences (RFC 7464).
+const RS: u8 = 0x1E;
+
+pub(crate) fn json_lines<T: DeserializeOwned>(
+    mut body: ResponseBody,
+) -> impl Stream<Item = Result<T>> {
+    async_stream::try_stream! {
+        let mut buf = BytesMut::new();
+        // The bytes before are known to contain no delimiter.
+        let mut searched = 0;
+        while let Some(chunk) = body.next().await {
+            buf.extend_from_slice(&chunk?);
+            while let Some(pos) = buf[searched..].iter().position(|&b| b == b'\n' || b == RS) {
+                let line = buf.split_to(searched + pos + 1);
+                searched = 0;
+                if let Some(value) = parse_line(&line[..line.len() - 1])? {
+                    yield value;
+                }
+            }
+            searched = buf.len();
+        }
+        if let Some(value) = parse_line(&buf)? {
+            yield value;
+        }
+    }
+}
+
+fn parse_line<T: DeserializeOwned>(line: &[u8]) -> Result<Option<T>> {
+    let line = line.trim_ascii();
+    if line.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(serde_json::from_slice(line)?))
+    }
+}
+
+pub(crate) fn json_array<T: DeserializeOwned>(
+    mut body: ResponseBody,
+) -> impl Stream<Item = Result<T>> {
+    async_stream::try_stream! {
+        let mut parser = ArrayParser::default();
+        while let Some(chunk) = body.next().await {
+            parser.buf.extend_from_slice(&chunk?);
+            while let Some(value) = parser.next()? {
+                yield serde_json::from_slice(&value)?;
+            }
+        }
+        parser.finish()?;
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Start,
+    First,
+    Element,
+    Value,
+    Comma,
+    End,
+}
+
+/// Splits the elements of a top-level JSON array, without parsing them.
+#[derive(Debug, Default)]
+struct ArrayParser {
+    buf: BytesMut,
+    state: State,
+    // The tokenizer state of the current element.
+    scanned: usize,
+    depth: usize,
+    in_string: bool,
+    escape: bool,
+}
+
+impl ArrayParser {
+    /// Get the next element, or `None` if more data is needed.
+    fn next(&mut self) -> Result<Option<BytesMut>, serde_json::Error> {
+        loop {
+            if self.state != State::Value {
+                let ws = self
+                    .buf
+                    .iter()
+                    .take_while(|b| b.is_ascii_whitespace())
+                    .count();
+                self.buf.advance(ws);
+            }
+            let Some(&b) = self.buf.first() else {
+                return Ok(None);
+            };
+            match (&self.state, b) {
+                (State::Start, b'[') => {
+                    self.buf.advance(1);
+                    self.state = State::First;
+                }
+                (State::Start, _) => return Err(serde_json::Error::custom("expected `[`")),
+                (State::First | State::Comma, b']') => {
+                    self.buf.advance(1);
+                    self.state = State::End;
+                }
+                (State::First | State::Element, _) => {
+                    self.state = State::Value;
+                    self.scanned = 0;
+                    self.depth = 0;
+                }
+                (State::Value, _) => return Ok(self.scan()),
+                (State::Comma, b',') => {
+                    self.buf.advance(1);
+                    self.state = State::Element;
+                }
+                (State::Comma, _) => {
+                    return Err(serde_json::Error::custom("expected `,` or `]`"));
+                }
+                (State::End, _) => return Err(serde_json::Error::custom("trailing characters")),
+            }
+        }
+    }
+
+    fn scan(&mut self) -> Option<BytesMut> {
+        for (i, &b) in self.buf.iter().enumerate().skip(self.scanned) {
+            if self.in_string {
+                if self.escape {
+                    self.escape = false;
+                } else if b == b'\\' {
+                    self.escape = true;
+                } else if b == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+            match b {
+                b'"' => self.in_string = true,
+                b'[' | b'{' => self.depth += 1,
+                b']' | b'}' if self.depth > 0 => self.depth -= 1,
+                b',' | b']' if self.depth == 0 => {
+                    self.state = State::Comma;
+                    return Some(self.buf.split_to(i));
+                }
+                _ => {}
+            }
+        }
+        self.scanned = self.buf.len();
+        None
+    }
+
+    fn finish(&self) -> Result<(), serde_json::Error> {
+        if self.state == State::End {
+            Ok(())
+        } else {
+            Err(serde_json::Error::custom("unexpected end of array"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn parse(chunks: &[&str]) -> Result<Vec<Value>, serde_json::Error> {
+        let mut parser = ArrayParser::default();
+        let mut values = vec![];
+        for chunk in chunks {
+            parser.buf.extend_from_slice(chunk.as_bytes());
+            while let Some(value) = parser.next()? {
+                values.push(serde_json::from_slice(&value)?);
+            }
+        }
+        parser.finish()?;
+        Ok(values)
+    }
+
+    #[test]
+    fn test_array_split() {
+        assert_eq!(parse(&[" [ ]\n"]).unwrap(), Vec::<Value>::new());
+        assert_eq!(
+            parse(&["[1, \"a,]\\\"", "\" , {\"b\": [2, ", "3]}", " ,null]"]).unwrap(),
+            [json!(1), json!("a,]\""), json!({"b": [2, 3]}), json!(null)]
+        );
+    }
+
+    #[test]
+    fn test_array_malformed() {
+        for chunks in [&["{}"][..], &["[1, 2"], &["[1 2]"], &["[1,]"], &["[1] 2"]] {
+            parse(chunks).unwrap_err();
+        }
+    }
+}
@@ -24,6 +24,9 @@ pub use response::*;
 mod into_url;
 pub use into_url::*;
 
+mod upgrade;
+pub use upgrade::*;
+
 mod backend;
 pub(crate) use backend::*;
 
@@ -31,8 +34,13 @@ mod connector;
 pub(crate) use connector::*;
 
 mod stream;
+pub use cyper_core::{DuplexReadHalf, DuplexStream, DuplexWriteHalf, duplex};
 pub(crate) use stream::*;
 
+mod info;
+pub(crate) use info::ConnectionInfo;
+pub use info::{Timings, TlsInfo};
+
 #[cfg(feature = "__decompression")]
 mod decompression;
 #[cfg(feature = "__decompression")]
@@ -47,13 +55,35 @@ pub mod redirect;
 /// Proxy support
 pub mod proxy;
 
+pub mod auth;
+
+pub mod metrics;
+
+mod netrc;
+
 mod util;
 
+#[cfg(all(feature = "json", feature = "stream"))]
+mod json_stream;
+
+#[cfg(feature = "digest-auth")]
+mod digest;
+
 #[cfg(feature = "http3")]
 mod http3;
+#[cfg(feature = "http3")]
+pub use http3::Http3Options;
 
 #[cfg(feature = "http3-altsvc")]
 mod altsvc;
+#[cfg(feature = "http3-altsvc")]
+pub use altsvc::AltSvcCache;
+
+#[cfg(feature = "cookies")]
+pub mod cookie;
+
+mod hsts;
+pub use hsts::HstsStore;
 
 #[cfg(feature = "multipart")]
 pub mod multipart;
@@ -61,6 +91,24 @@ pub mod multipart;
 #[cfg(feature = "nyquest")]
 pub mod nyquest;
 
+#[cfg(feature = "ws")]
+pub mod ws;
+
+#[cfg(feature = "sse")]
+pub mod sse;
+
+#[cfg(feature = "signatures")]
+pub mod signatures;
+
+#[cfg(feature = "tracing")]
+pub mod trace;
+
+#[cfg(feature = "har")]
+pub mod har;
+
+#[cfg(feature = "vcr")]
+pub mod vcr;
+
 cfg_if::cfg_if! {
     if #[cfg(feature = "sync")] {
         use synchrony::sync;
@@ -127,6 +175,11 @@ pub enum Error {
     #[cfg(feature = "http3")]
     #[error("HTTP3 client error: {0}")]
     H3Client(String),
+    /// An HTTP/3 connection error shared by the requests waiting for the
+    /// connection.
+    #[cfg(feature = "http3")]
+    #[error(transparent)]
+    H3Shared(std::sync::Arc<Error>),
     /// QUIC [`ConnectError`].
     ///
     /// [`ConnectError`]: compio::quic::ConnectError
@@ -145,6 +198,29 @@ pub enum Error {
     /// Proxy error.
     #[error("proxy: {0}")]
     Proxy(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// Authentication error.
+    #[error("authentication: {0}")]
+    Auth(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// Cookie store error.
+    #[cfg(feature = "cookies")]
+    #[error("cookie store: {0}")]
+    Cookie(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// WebSocket error.
+    #[cfg(feature = "ws")]
+    #[error("WebSocket error: {0}")]
+    WebSocket(#[from] async_tungstenite::tungstenite::Error),
+    /// Event source error.
+    #[cfg(feature = "sse")]
+    #[error("event source error: {0}")]
+    EventSource(String),
+    /// HTTP message signature error.
+    #[cfg(feature = "signatures")]
+    #[error("signature error: {0}")]
+    Signature(String),
+    /// Cassette error.
+    #[cfg(feature = "vcr")]
+    #[error("cassette: {0}")]
+    Cassette(String),
     /// Hickory error.
     #[cfg(feature = "hickory-dns")]
     #[error("hickory: {0}")]
@@ -0,0 +1,520 @@
+//! Metrics of requests and connections.
+//!
+//! A [`Metrics`] set with
+//! [`ClientBuilder::metrics`](crate::ClientBuilder::metrics) is notified of
+//! the requests and connections of the client. [`PrometheusMetrics`]
+//! aggregates them by host, in the Prometheus text format.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Write},
+    time::{Duration, Instant},
+};
+
+use http::{Method, StatusCode};
+use send_wrapper::SendWrapper;
+use url::Url;
+
+use crate::{
+    ConnectionInfo, Result,
+    sync::{mutex_blocking::Mutex, shared::Shared},
+};
+
+/// Trait for collecting the metrics of a client.
+///
+/// The methods are called in the thread of the client, and do nothing by
+/// default. The hosts are the ones in the URLs, with brackets for IPv6.
+pub trait Metrics {
+    /// Called when [`Client::execute`](crate::Client::execute) starts.
+    fn request_start(&self, method: &Method, url: &Url) {
+        let _ = (method, url);
+    }
+
+    /// Called when [`Client::execute`](crate::Client::execute) ends, with the
+    /// final status after redirects, or the error. The duration is until the
+    /// response headers are received.
+    fn request_end(
+        &self,
+        method: &Method,
+        url: &Url,
+        result: Result<StatusCode, &crate::Error>,
+        duration: Duration,
+    ) {
+        let _ = (method, url, result, duration);
+    }
+
+    /// Called instead of [`request_end`](Self::request_end) when the future of
+    /// [`Client::execute`](crate::Client::execute) is dropped before it ends,
+    /// e.g. on timeout.
+    fn request_cancel(&self, method: &Method, url: &Url, duration: Duration) {
+        let _ = (method, url, duration);
+    }
+
+    /// Called when a connection to the host is established.
+    fn connection_open(&self, host: &str) {
+        let _ = host;
+    }
+
+    /// Called when a connection to the host is closed, with how long it has
+    /// been open.
+    fn connection_close(&self, host: &str, duration: Duration) {
+        let _ = (host, duration);
+    }
+
+    /// Called when a response is received on a pooled connection.
+    fn pool_hit(&self, host: &str) {
+        let _ = host;
+    }
+
+    /// Called when a response is received on a new connection.
+    fn pool_miss(&self, host: &str) {
+        let _ = host;
+    }
+
+    /// Called when a TLS handshake completes, including the QUIC handshake.
+    fn tls_handshake(&self, host: &str, duration: Duration) {
+        let _ = (host, duration);
+    }
+
+    /// Called when a request to the host falls back from HTTP/3 to TCP.
+    fn http3_fallback(&self, host: &str) {
+        let _ = host;
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct SharedMetrics(SendWrapper<Shared<dyn Metrics>>);
+
+impl SharedMetrics {
+    pub(crate) fn new<M: Metrics + 'static>(metrics: M) -> Self {
+        Self(SendWrapper::new(Shared::new(metrics)))
+    }
+
+    /// Notify a new request, and returns the guard notifying when it ends, or
+    /// when it is canceled.
+    pub(crate) fn start(&self, method: &Method, url: &Url) -> RequestGuard {
+        self.request_start(method, url);
+        RequestGuard {
+            metrics: self.clone(),
+            method: method.clone(),
+            url: url.clone(),
+            start: Instant::now(),
+            ended: false,
+        }
+    }
+
+    /// Notify a new connection, and returns the guard notifying when it is
+    /// closed.
+    pub(crate) fn open(&self, host: &str, info: &ConnectionInfo) -> ConnectionGuard {
+        self.connection_open(host);
+        if let Some(duration) = info.tls_handshake {
+            self.tls_handshake(host, duration);
+        }
+        ConnectionGuard {
+            metrics: self.clone(),
+            host: host.to_string(),
+            opened: Instant::now(),
+        }
+    }
+
+    /// Notify whether the response is received on a pooled connection, for a
+    /// request started at `start`.
+    pub(crate) fn pool(&self, host: &str, start: Instant, info: Option<&ConnectionInfo>) {
+        if info.is_some_and(|info| info.is_reused(start)) {
+            self.pool_hit(host);
+        } else {
+            self.pool_miss(host);
+        }
+    }
+}
+
+impl std::ops::Deref for SharedMetrics {
+    type Target = dyn Metrics;
+
+    fn deref(&self) -> &Self::Target {
+        &**self.0
+    }
+}
+
+impl Debug for SharedMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_tuple("SharedMetrics").finish()
+    }
+}
+
+/// Notifies when the request holding it ends, or is canceled if dropped.
+pub(crate) struct RequestGuard {
+    metrics: SharedMetrics,
+    method: Method,
+    url: Url,
+    start: Instant,
+    ended: bool,
+}
+
+impl RequestGuard {
+    /// Notify the end of the request, with the final status or the error.
+    pub(crate) fn end(mut self, result: Result<StatusCode, &crate::Error>) {
+        self.ended = true;
+        self.metrics
+            .request_end(&self.method, &self.url, result, self.start.elapsed());
+    }
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        if !self.ended {
+            self.metrics
+                .request_cancel(&self.method, &self.url, self.start.elapsed());
+        }
+    }
+}
+
+/// Notifies when the connection holding it is closed.
+pub(crate) struct ConnectionGuard {
+    metrics: SharedMetrics,
+    host: String,
+    opened: Instant,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.metrics
+            .connection_close(&self.host, self.opened.elapsed());
+    }
+}
+
+/// The upper bounds of the histogram buckets, in seconds.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        for (bucket, le) in self.buckets.iter_mut().zip(BUCKETS) {
+            if secs <= le {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+}
+
+#[derive(Debug, Default)]
+struct HostMetrics {
+    /// The requests by method and status, `error` or `canceled`.
+    requests: BTreeMap<(String, String), u64>,
+    in_flight: u64,
+    duration: Histogram,
+    connections_opened: u64,
+    connections_closed: u64,
+    pool_hits: u64,
+    pool_misses: u64,
+    tls_handshake: Histogram,
+    http3_fallbacks: u64,
+}
+
+/// A [`Metrics`] aggregating the metrics by host, rendered in the Prometheus
+/// text format with [`render`](Self::render).
+///
+/// The metrics are shared between the clones, so one could be set on many
+/// clients. With the `sync` feature, the clones could be used in different
+/// threads.
+///
+/// ```no_run
+/// use cyper::{Client, metrics::PrometheusMetrics};
+///
+/// # fn run() -> cyper::Result<()> {
+/// let metrics = PrometheusMetrics::new();
+/// let client = Client::builder().metrics(metrics.clone()).build()?;
+/// // Serve `metrics.render()` to the scraper.
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PrometheusMetrics {
+    hosts: Shared<Mutex<BTreeMap<String, HostMetrics>>>,
+}
+
+impl Default for PrometheusMetrics {
+    fn default() -> Self {
+        Self {
+            hosts: Shared::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+}
+
+impl PrometheusMetrics {
+    /// Creates empty metrics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_host(&self, host: &str, f: impl FnOnce(&mut HostMetrics)) {
+        let mut hosts = self.hosts.lock();
+        match hosts.get_mut(host) {
+            Some(metrics) => f(metrics),
+            None => f(hosts.entry(host.to_string()).or_default()),
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let hosts = self.hosts.lock();
+        let mut out = String::new();
+
+        family(&mut out, "requests_total", "counter", "Completed requests.");
+        for (host, metrics) in hosts.iter() {
+            for ((method, status), count) in &metrics.requests {
+                sample(
+                    &mut out,
+                    "requests_total",
+                    &[("host", host), ("method", method), ("status", status)],
+                    *count,
+                );
+            }
+        }
+        family(
+            &mut out,
+            "requests_in_flight",
+            "gauge",
+            "Requests in flight.",
+        );
+        for (host, metrics) in hosts.iter() {
+            sample(
+                &mut out,
+                "requests_in_flight",
+                &[("host", host)],
+                metrics.in_flight,
+            );
+        }
+        histogram(
+            &mut out,
+            "request_duration_seconds",
+            "Time until the response headers are received.",
+            hosts
+                .iter()
+                .map(|(host, metrics)| (host, &metrics.duration)),
+        );
+        let mut counter = |name: &str, help: &str, value: fn(&HostMetrics) -> u64| {
+            family(&mut out, name, "counter", help);
+            for (host, metrics) in hosts.iter() {
+                sample(&mut out, name, &[("host", host)], value(metrics));
+            }
+        };
+        counter(
+            "connections_opened_total",
+            "Established connections.",
+            |m| m.connections_opened,
+        );
+        counter("connections_closed_total", "Closed connections.", |m| {
+            m.connections_closed
+        });
+        counter(
+            "pool_hits_total",
+            "Responses received on pooled connections.",
+            |m| m.pool_hits,
+        );
+        counter(
+            "pool_misses_total",
+            "Responses received on new connections.",
+            |m| m.pool_misses,
+        );
+        counter(
+            "http3_fallbacks_total",
+            "Requests falling back from HTTP/3 to TCP.",
+            |m| m.http3_fallbacks,
+        );
+        histogram(
+            &mut out,
+            "tls_handshake_duration_seconds",
+            "Time of the TLS handshakes.",
+            hosts
+                .iter()
+                .map(|(host, metrics)| (host, &metrics.tls_handshake)),
+        );
+        out
+    }
+}
+
+impl Metrics for PrometheusMetrics {
+    fn request_start(&self, _method: &Method, url: &Url) {
+        self.with_host(url.host_str().unwrap_or_default(), |m| m.in_flight += 1);
+    }
+
+    fn request_end(
+        &self,
+        method: &Method,
+        url: &Url,
+        result: Result<StatusCode, &crate::Error>,
+        duration: Duration,
+    ) {
+        let status = match result {
+            Ok(status) => status.as_str().to_string(),
+            Err(_) => "error".to_string(),
+        };
+        self.with_host(url.host_str().unwrap_or_default(), |m| {
+            m.in_flight = m.in_flight.saturating_sub(1);
+            *m.requests
+                .entry((method.as_str().to_string(), status))
+                .or_default() += 1;
+            m.duration.observe(duration);
+        });
+    }
+
+    fn request_cancel(&self, method: &Method, url: &Url, duration: Duration) {
+        self.with_host(url.host_str().unwrap_or_default(), |m| {
+            m.in_flight = m.in_flight.saturating_sub(1);
+            *m.requests
+                .entry((method.as_str().to_string(), "canceled".to_string()))
+                .or_default() += 1;
+            m.duration.observe(duration);
+        });
+    }
+
+    fn connection_open(&self, host: &str) {
+        self.with_host(host, |m| m.connections_opened += 1);
+    }
+
+    fn connection_close(&self, host: &str, _duration: Duration) {
+        self.with_host(host, |m| m.connections_closed += 1);
+    }
+
+    fn pool_hit(&self, host: &str) {
+        self.with_host(host, |m| m.pool_hits += 1);
+    }
+
+    fn pool_miss(&self, host: &str) {
+        self.with_host(host, |m| m.pool_misses += 1);
+    }
+
+    fn tls_handshake(&self, host: &str, duration: Duration) {
+        self.with_host(host, |m| m.tls_handshake.observe(duration));
+    }
+
+    fn http3_fallback(&self, host: &str) {
+        self.with_host(host, |m| m.http3_fallbacks += 1);
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP cyper_{name} {help}");
+    let _ = writeln!(out, "# TYPE cyper_{name} {kind}");
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    let _ = write!(out, "cyper_{name}{{");
+    for (i, (key, value)) in labels.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let _ = write!(out, "{key}=\"");
+        for c in value.chars() {
+            match c {
+                '\\' => out.push_str("\\\\"),
+                '"' => out.push_str("\\\""),
+                '\n' => out.push_str("\\n"),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+    let _ = writeln!(out, "}} {value}");
+}
+
+fn histogram<'a>(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    hosts: impl Iterator<Item = (&'a String, &'a Histogram)>,
+) {
+    family(out, name, "histogram", help);
+    let bucket = format!("{name}_bucket");
+    for (host, histogram) in hosts {
+        for (le, count) in BUCKETS.iter().zip(histogram.buckets) {
+            sample(
+                out,
+                &bucket,
+                &[("host", host), ("le", &le.to_string())],
+                count,
+            );
+        }
+        sample(
+            out,
+            &bucket,
+            &[("host", host), ("le", "+Inf")],
+            histogram.count,
+        );
+        sample(
+            out,
+            &format!("{name}_sum"),
+            &[("host", host)],
+            histogram.sum,
+        );
+        sample(
+            out,
+            &format!("{name}_count"),
+            &[("host", host)],
+            histogram.count,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = PrometheusMetrics::new();
+        let url = Url::parse("https://example.com/").unwrap();
+        metrics.request_start(&Method::GET, &url);
+        metrics.connection_open("example.com");
+        metrics.tls_handshake("example.com", Duration::from_millis(20));
+        metrics.pool_miss("example.com");
+        metrics.request_end(
+            &Method::GET,
+            &url,
+            Ok(StatusCode::OK),
+            Duration::from_millis(30),
+        );
+        metrics.request_start(&Method::GET, &url);
+        metrics.request_end(
+            &Method::GET,
+            &url,
+            Err(&crate::Error::Timeout),
+            Duration::from_secs(20),
+        );
+        metrics.connection_close("example.com", Duration::from_secs(1));
+
+        let out = metrics.render();
+        for line in [
+            "# TYPE cyper_requests_total counter",
+            r#"cyper_requests_total{host="example.com",method="GET",status="200"} 1"#,
+            r#"cyper_requests_total{host="example.com",method="GET",status="error"} 1"#,
+            r#"cyper_requests_in_flight{host="example.com"} 0"#,
+            r#"cyper_request_duration_seconds_bucket{host="example.com",le="0.025"} 0"#,
+            r#"cyper_request_duration_seconds_bucket{host="example.com",le="0.05"} 1"#,
+            r#"cyper_request_duration_seconds_bucket{host="example.com",le="10"} 1"#,
+            r#"cyper_request_duration_seconds_bucket{host="example.com",le="+Inf"} 2"#,
+            r#"cyper_request_duration_seconds_count{host="example.com"} 2"#,
+            r#"cyper_connections_opened_total{host="example.com"} 1"#,
+            r#"cyper_connections_closed_total{host="example.com"} 1"#,
+            r#"cyper_pool_hits_total{host="example.com"} 0"#,
+            r#"cyper_pool_misses_total{host="example.com"} 1"#,
+            r#"cyper_http3_fallbacks_total{host="example.com"} 0"#,
+            r#"cyper_tls_handshake_duration_seconds_bucket{host="example.com",le="0.025"} 1"#,
+            r#"cyper_tls_handshake_duration_seconds_sum{host="example.com"} 0.02"#,
+        ] {
+            assert!(out.lines().any(|l| l == line), "{line} not in\n{out}");
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! Credentials from the `.netrc` file.
+
+use std::{io, path::PathBuf};
+
+use http::HeaderValue;
+use url::Url;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Login {
+    login: String,
+    password: Option<String>,
+}
+
+/// The entries of a `.netrc` file, as read by curl and git.
+#[derive(Debug, Default)]
+pub(crate) struct Netrc {
+    machines: Vec<(String, Login)>,
+    default: Option<Login>,
+}
+
+impl Netrc {
+    /// Read the file at `$NETRC`, or `~/.netrc`. A missing file is empty.
+    pub(crate) fn load() -> io::Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(Self::parse(&content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os("NETRC").filter(|path| !path.is_empty()) {
+            return Some(path.into());
+        }
+        let home = std::env::home_dir()?;
+        let path = home.join(".netrc");
+        // Windows tools also use `_netrc`.
+        if cfg!(windows) && !path.exists() {
+            return Some(home.join("_netrc"));
+        }
+        Some(path)
+    }
+
+    fn parse(content: &str) -> Self {
+        let mut netrc = Self::default();
+        // The entry being parsed, `None` for the default one.
+        let mut current: Option<(Option<String>, Login)> = None;
+        let mut tokens = Tokens::new(content);
+        while let Some(token) = tokens.next() {
+            match token.as_str() {
+                "machine" | "default" => {
+                    netrc.push(current.take());
+                    let host = if token == "machine" {
+                        match tokens.next() {
+                            Some(host) => Some(host),
+                            None => break,
+                        }
+                    } else {
+                        None
+                    };
+                    current = Some((host, Login::default()));
+                }
+                "login" | "password" | "account" => {
+                    let value = tokens.next();
+                    if let (Some((_, login)), Some(value)) = (&mut current, value) {
+                        match token.as_str() {
+                            "login" => login.login = value,
+                            "password" => login.password = Some(value),
+                            _ => {}
+                        }
+                    }
+                }
+                "macdef" => {
+                    netrc.push(current.take());
+                    tokens.skip_macro();
+                }
+                _ => {}
+            }
+        }
+        netrc.push(current);
+        netrc
+    }
+
+    fn push(&mut self, entry: Option<(Option<String>, Login)>) {
+        match entry {
+            Some((Some(host), login)) => self.machines.push((host, login)),
+            // Only the first default entry is used.
+            Some((None, login)) if self.default.is_none() => self.default = Some(login),
+            _ => {}
+        }
+    }
+
+    fn find(&self, host: &str) -> Option<&Login> {
+        self.machines
+            .iter()
+            .find(|(machine, _)| machine.eq_ignore_ascii_case(host))
+            .map(|(_, login)| login)
+            .or(self.default.as_ref())
+    }
+
+    /// The `Authorization` header of the host in the URL.
+    pub(crate) fn authorization(&self, url: &Url) -> Option<HeaderValue> {
+        let host = url.host_str()?;
+        // IPv6 addresses are written without brackets.
+        let host = host
+            .strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+            .unwrap_or(host);
+        let login = self.find(host)?;
+        if login.login.is_empty() && login.password.is_none() {
+            return None;
+        }
+        Some(crate::util::basic_auth(
+            &login.login,
+            login.password.as_ref(),
+        ))
+    }
+}
+
+/// Whitespace separated tokens, with `#` comments and quoted strings.
+struct Tokens<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(content: &'a str) -> Self {
+        Self { rest: content }
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            self.rest = self.rest.trim_start();
+            if self.rest.starts_with('#') {
+                self.rest = self.rest.split_once('\n').map_or("", |(_, rest)| rest);
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Skip the macro definition, which ends with an empty line.
+    fn skip_macro(&mut self) {
+        let end = self
+            .rest
+            .find("\n\n")
+            .or_else(|| self.rest.find("\n\r\n"))
+            .unwrap_or(self.rest.len());
+        self.rest = &self.rest[end..];
+    }
+}
+
+impl Iterator for Tokens<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        if self.rest.is_empty() {
+            return None;
+        }
+        let mut token = String::new();
+        if let Some(rest) = self.rest.strip_prefix('"') {
+            let mut chars = rest.char_indices();
+            let mut end = rest.len();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        end = i + 1;
+                        break;
+                    }
+                    '\\' => match chars.next() {
+                        Some((_, 'n')) => token.push('\n'),
+                        Some((_, 'r')) => token.push('\r'),
+                        Some((_, 't')) => token.push('\t'),
+                        Some((_, c)) => token.push(c),
+                        None => {}
+                    },
+                    c => token.push(c),
+                }
+            }
+            self.rest = &rest[end..];
+        } else {
+            let end = self
+                .rest
+                .find(char::is_whitespace)
+                .unwrap_or(self.rest.len());
+            token.push_str(&self.rest[..end]);
+            self.rest = &self.rest[end..];
+        }
+        Some(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn login(login: &str, password: &str) -> Login {
+        Login {
+            login: login.to_string(),
+            password: Some(password.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let netrc = Netrc::parse(
+            r#"# comment
+machine example.com login user password pass
+machine other.com
+    login "with space"
+    password "quoted \"pass\"" # comment
+    account ignored
+
+macdef init
+machine macro.com login no password no
+
+default login anonymous password guest
+machine late.com login late password late
+"#,
+        );
+        assert_eq!(netrc.find("example.com"), Some(&login("user", "pass")));
+        assert_eq!(netrc.find("EXAMPLE.com"), Some(&login("user", "pass")));
+        assert_eq!(
+            netrc.find("other.com"),
+            Some(&login("with space", "quoted \"pass\""))
+        );
+        assert_eq!(netrc.find("late.com"), Some(&login("late", "late")));
+        assert_eq!(netrc.find("macro.com"), Some(&login("anonymous", "guest")));
+        assert_eq!(
+            netrc.find("unknown.com"),
+            Some(&login("anonymous", "guest"))
+        );
+    }
+
+    #[test]
+    fn test_authorization() {
+        let netrc = Netrc::parse(
+            "machine example.com login user password pass\nmachine ::1 login ipv6\nmachine \
+             empty.com",
+        );
+        let auth = |url: &str| netrc.authorization(&url.parse().unwrap());
+        assert_eq!(
+            auth("https://example.com:8443/path").unwrap(),
+            "Basic dXNlcjpwYXNz"
+        );
+        assert_eq!(auth("http://[::1]/").unwrap(), "Basic aXB2Njo=");
+        assert!(auth("http://example.org/").is_none());
+        assert!(auth("http://empty.com/").is_none());
+    }
+}
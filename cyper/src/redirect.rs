@@ -4,9 +4,9 @@
 //! maximum redirect chain of 10 hops. To customize this behavior, a
 //! `redirect::Policy` can be used with a `ClientBuilder`.
 
-use std::{error::Error as StdError, fmt};
+use std::{error::Error as StdError, fmt, sync::Arc, time::Duration};
 
-use http::{HeaderMap, HeaderValue, StatusCode};
+use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header};
 use hyper::header::{AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, WWW_AUTHENTICATE};
 use url::Url;
 
@@ -19,6 +19,12 @@ use url::Url;
 ///   the allowed maximum redirect hops in a chain.
 /// - `none` can be used to disable all redirect behavior.
 /// - `custom` can be used to create a customized policy.
+///
+/// A policy could be set for a client with
+/// [`ClientBuilder::redirect`](crate::ClientBuilder::redirect), or for a
+/// single request with
+/// [`RequestBuilder::redirect`](crate::RequestBuilder::redirect).
+#[derive(Clone)]
 pub struct Policy {
     inner: PolicyKind,
 }
@@ -30,9 +36,17 @@ pub struct Attempt<'a> {
     status: StatusCode,
     next: &'a Url,
     previous: &'a [Url],
+    method: &'a Method,
+    headers: &'a HeaderMap,
+    response_headers: &'a HeaderMap,
 }
 
 /// An action to perform when a redirect status code is found.
+///
+/// When following the redirect, the next request could be rewritten with
+/// [`method`](Self::method), [`keep_body`](Self::keep_body),
+/// [`header`](Self::header) and [`remove_header`](Self::remove_header). They
+/// are ignored by the other actions.
 #[derive(Debug)]
 pub struct Action {
     inner: ActionKind,
@@ -96,7 +110,7 @@ impl Policy {
         T: Fn(Attempt) -> Action + Send + Sync + 'static,
     {
         Self {
-            inner: PolicyKind::Custom(Box::new(policy)),
+            inner: PolicyKind::Custom(Arc::new(policy)),
         }
     }
 
@@ -134,13 +148,8 @@ impl Policy {
         }
     }
 
-    pub(crate) fn check(&self, status: StatusCode, next: &Url, previous: &[Url]) -> ActionKind {
-        self.redirect(Attempt {
-            status,
-            next,
-            previous,
-        })
-        .inner
+    pub(crate) fn check(&self, attempt: Attempt) -> ActionKind {
+        self.redirect(attempt).inner
     }
 }
 
@@ -158,6 +167,24 @@ impl fmt::Debug for Policy {
 }
 
 impl<'a> Attempt<'a> {
+    pub(crate) fn new(
+        status: StatusCode,
+        next: &'a Url,
+        previous: &'a [Url],
+        method: &'a Method,
+        headers: &'a HeaderMap,
+        response_headers: &'a HeaderMap,
+    ) -> Self {
+        Self {
+            status,
+            next,
+            previous,
+            method,
+            headers,
+            response_headers,
+        }
+    }
+
     /// Get the type of redirect.
     pub fn status(&self) -> StatusCode {
         self.status
@@ -174,10 +201,30 @@ impl<'a> Attempt<'a> {
         self.previous
     }
 
+    /// Get the method of the redirected request.
+    pub fn method(&self) -> &Method {
+        self.method
+    }
+
+    /// Get the headers of the redirected request.
+    pub fn headers(&self) -> &HeaderMap {
+        self.headers
+    }
+
+    /// Get the headers of the redirect response.
+    pub fn response_headers(&self) -> &HeaderMap {
+        self.response_headers
+    }
+
     /// Returns an action meaning the next URL should be followed.
+    ///
+    /// By default, the next request is sent with `GET` on
+    /// `301 Moved Permanently` and `302 Found`, and without the body on them
+    /// and `303 See Other`. The sensitive headers like `Authorization` and
+    /// `Cookie` are removed when redirecting to another host.
     pub fn follow(self) -> Action {
         Action {
-            inner: ActionKind::Follow,
+            inner: ActionKind::Follow(Rewrite::default()),
         }
     }
 
@@ -200,8 +247,139 @@ impl<'a> Attempt<'a> {
     }
 }
 
+/// A redirect response followed in a redirect chain.
+///
+/// See [`Response::redirect_history`](crate::Response::redirect_history).
+#[derive(Debug, Clone)]
+pub struct Hop {
+    method: Method,
+    url: Url,
+    status: StatusCode,
+    headers: HeaderMap,
+    elapsed: Duration,
+}
+
+/// The response headers kept in a [`Hop`].
+const HOP_HEADERS: [header::HeaderName; 6] = [
+    header::LOCATION,
+    header::DATE,
+    header::SERVER,
+    header::VIA,
+    header::CACHE_CONTROL,
+    header::EXPIRES,
+];
+
+impl Hop {
+    pub(crate) fn new(
+        method: Method,
+        url: Url,
+        status: StatusCode,
+        headers: &HeaderMap,
+        elapsed: Duration,
+    ) -> Self {
+        let mut kept = HeaderMap::new();
+        for name in HOP_HEADERS {
+            for value in headers.get_all(&name) {
+                kept.append(name.clone(), value.clone());
+            }
+        }
+        Self {
+            method,
+            url,
+            status,
+            headers: kept,
+            elapsed,
+        }
+    }
+
+    /// Get the method of the request.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Get the requested URL.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Get the redirect status.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Get the selected headers of the response: `Location`, `Date`,
+    /// `Server`, `Via`, `Cache-Control` and `Expires`.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Get the time from sending the request to receiving the response
+    /// headers.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+impl Action {
+    fn rewrite(mut self, f: impl FnOnce(&mut Rewrite)) -> Self {
+        if let ActionKind::Follow(rewrite) = &mut self.inner {
+            f(rewrite);
+        }
+        self
+    }
+
+    /// Send the next request with the method. Whether the body is kept still
+    /// depends on the status code.
+    pub fn method(self, method: Method) -> Self {
+        self.rewrite(|rewrite| rewrite.method = Some(method))
+    }
+
+    /// Keep the method and the body on `301 Moved Permanently` and
+    /// `302 Found`, like on `307 Temporary Redirect`. The redirect is not
+    /// followed if the body cannot be cloned.
+    pub fn keep_body(self) -> Self {
+        self.rewrite(|rewrite| rewrite.keep_body = true)
+    }
+
+    /// Set a header of the next request, after the sensitive headers are
+    /// removed.
+    pub fn header(self, name: HeaderName, value: HeaderValue) -> Self {
+        self.rewrite(|rewrite| rewrite.headers.push((name, Some(value))))
+    }
+
+    /// Remove a header from the next request.
+    pub fn remove_header(self, name: HeaderName) -> Self {
+        self.rewrite(|rewrite| rewrite.headers.push((name, None)))
+    }
+}
+
+/// The changes of the next request when following a redirect.
+#[derive(Debug, Default)]
+pub(crate) struct Rewrite {
+    pub(crate) method: Option<Method>,
+    pub(crate) keep_body: bool,
+    /// Headers to set, or to remove if `None`, in order.
+    pub(crate) headers: Vec<(HeaderName, Option<HeaderValue>)>,
+}
+
+impl Rewrite {
+    pub(crate) fn apply_headers(self, headers: &mut HeaderMap) {
+        for (name, value) in self.headers {
+            match value {
+                Some(value) => {
+                    headers.insert(name, value);
+                }
+                None => {
+                    headers.remove(name);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
 enum PolicyKind {
-    Custom(Box<dyn Fn(Attempt) -> Action + Send + Sync + 'static>),
+    Custom(Arc<dyn Fn(Attempt) -> Action + Send + Sync + 'static>),
     Limit(usize),
     None,
 }
@@ -218,7 +396,7 @@ impl fmt::Debug for PolicyKind {
 
 #[derive(Debug)]
 pub(crate) enum ActionKind {
-    Follow,
+    Follow(Rewrite),
     Stop,
     Error(Box<dyn StdError + Send + Sync>),
 }
@@ -260,6 +438,19 @@ pub(crate) fn make_referer(next: &Url, previous: &Url) -> Option<HeaderValue> {
     referer.as_str().parse().ok()
 }
 
+#[cfg(test)]
+fn attempt<'a>(next: &'a Url, previous: &'a [Url]) -> Attempt<'a> {
+    static HEADERS: std::sync::LazyLock<HeaderMap> = std::sync::LazyLock::new(HeaderMap::new);
+    Attempt::new(
+        StatusCode::FOUND,
+        next,
+        previous,
+        &Method::GET,
+        &HEADERS,
+        &HEADERS,
+    )
+}
+
 #[test]
 fn test_redirect_policy_limit() {
     let policy = Policy::default();
@@ -268,14 +459,14 @@ fn test_redirect_policy_limit() {
         .map(|i| Url::parse(&format!("http://a.b/c/{i}")).unwrap())
         .collect::<Vec<_>>();
 
-    match policy.check(StatusCode::FOUND, &next, &previous) {
-        ActionKind::Follow => (),
+    match policy.check(attempt(&next, &previous)) {
+        ActionKind::Follow(_) => (),
         other => panic!("unexpected {other:?}"),
     }
 
     previous.push(Url::parse("http://a.b.d/e/33").unwrap());
 
-    match policy.check(StatusCode::FOUND, &next, &previous) {
+    match policy.check(attempt(&next, &previous)) {
         ActionKind::Error(err) if err.is::<TooManyRedirects>() => (),
         other => panic!("unexpected {other:?}"),
     }
@@ -287,7 +478,7 @@ fn test_redirect_policy_limit_to_0() {
     let next = Url::parse("http://x.y/z").unwrap();
     let previous = vec![Url::parse("http://a.b/c").unwrap()];
 
-    match policy.check(StatusCode::FOUND, &next, &previous) {
+    match policy.check(attempt(&next, &previous)) {
         ActionKind::Error(err) if err.is::<TooManyRedirects>() => (),
         other => panic!("unexpected {other:?}"),
     }
@@ -304,13 +495,13 @@ fn test_redirect_policy_custom() {
     });
 
     let next = Url::parse("http://bar/baz").unwrap();
-    match policy.check(StatusCode::FOUND, &next, &[]) {
-        ActionKind::Follow => (),
+    match policy.check(attempt(&next, &[])) {
+        ActionKind::Follow(_) => (),
         other => panic!("unexpected {other:?}"),
     }
 
     let next = Url::parse("http://foo/baz").unwrap();
-    match policy.check(StatusCode::FOUND, &next, &[]) {
+    match policy.check(attempt(&next, &[])) {
         ActionKind::Stop => (),
         other => panic!("unexpected {other:?}"),
     }
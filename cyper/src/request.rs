@@ -9,7 +9,7 @@ use url::Url;
 
 #[cfg(feature = "multipart")]
 use crate::multipart;
-use crate::{Body, Client, Response, Result};
+use crate::{Body, Client, Response, Result, redirect};
 
 /// A request which can be executed with `Client::execute()`.
 #[derive(Debug)]
@@ -19,6 +19,12 @@ pub struct Request {
     headers: HeaderMap,
     body: Body,
     version: Version,
+    redirect_policy: Option<redirect::Policy>,
+    #[cfg(feature = "digest-auth")]
+    digest_auth: Option<crate::digest::Credentials>,
+    /// The protocol of an extended CONNECT, e.g. for WebSockets over HTTP/2.
+    #[cfg(all(feature = "ws", feature = "http2"))]
+    protocol: Option<hyper::ext::Protocol>,
 }
 
 impl Request {
@@ -31,6 +37,11 @@ impl Request {
             headers: HeaderMap::new(),
             body: Body::empty(),
             version: Version::default(),
+            redirect_policy: None,
+            #[cfg(feature = "digest-auth")]
+            digest_auth: None,
+            #[cfg(all(feature = "ws", feature = "http2"))]
+            protocol: None,
         }
     }
 
@@ -94,6 +105,53 @@ impl Request {
         &mut self.version
     }
 
+    /// Get the redirect policy overriding the one of the client.
+    #[inline]
+    pub fn redirect_policy(&self) -> Option<&redirect::Policy> {
+        self.redirect_policy.as_ref()
+    }
+
+    /// Get a mutable reference to the redirect policy overriding the one of
+    /// the client.
+    #[inline]
+    pub fn redirect_policy_mut(&mut self) -> &mut Option<redirect::Policy> {
+        &mut self.redirect_policy
+    }
+
+    /// Attempt to clone the request.
+    ///
+    /// `None` is returned if the request can not be cloned, i.e. if the body
+    /// is a stream.
+    pub fn try_clone(&self) -> Option<Request> {
+        let body = self.body.try_clone()?;
+        Some(Request {
+            method: self.method.clone(),
+            url: self.url.clone(),
+            headers: self.headers.clone(),
+            body,
+            version: self.version,
+            redirect_policy: self.redirect_policy.clone(),
+            #[cfg(feature = "digest-auth")]
+            digest_auth: self.digest_auth.clone(),
+            #[cfg(all(feature = "ws", feature = "http2"))]
+            protocol: self.protocol.clone(),
+        })
+    }
+
+    #[cfg(feature = "digest-auth")]
+    pub(crate) fn take_digest_auth(&mut self) -> Option<crate::digest::Credentials> {
+        self.digest_auth.take()
+    }
+
+    #[cfg(all(feature = "ws", feature = "http2"))]
+    pub(crate) fn protocol_mut(&mut self) -> &mut Option<hyper::ext::Protocol> {
+        &mut self.protocol
+    }
+
+    pub(crate) fn take_redirect_policy(&mut self) -> Option<redirect::Policy> {
+        self.redirect_policy.take()
+    }
+
     pub(super) fn pieces(self) -> (Method, Url, HeaderMap, Body, Version) {
         (self.method, self.url, self.headers, self.body, self.version)
     }
@@ -187,6 +245,36 @@ impl RequestBuilder {
         self.header_sensitive(AUTHORIZATION, header_value, true)
     }
 
+    /// Enable HTTP Digest authentication (RFC 7616).
+    ///
+    /// When the server responds with a `401` and a `Digest` challenge, the
+    /// request is sent again once with the computed credentials, so the body
+    /// must be clonable. The challenge is reused for later requests of the
+    /// same client to the same origin, so they are authorized without another
+    /// round trip.
+    ///
+    /// ```rust
+    /// # use cyper::Error;
+    /// #
+    /// # async fn run() -> Result<(), Error> {
+    /// let client = cyper::Client::new()?;
+    /// let resp = client
+    ///     .get("http://httpbin.org/digest-auth/auth/admin/secret")?
+    ///     .digest_auth("admin", "secret")
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "digest-auth")]
+    pub fn digest_auth<U: Display, P: Display>(mut self, username: U, password: P) -> Self {
+        self.request.digest_auth = Some(crate::digest::Credentials::new(
+            username.to_string(),
+            password.to_string(),
+        ));
+        self
+    }
+
     /// Set the request body.
     pub fn body<T: Into<Body>>(mut self, body: T) -> RequestBuilder {
         *self.request.body_mut() = body.into();
@@ -250,6 +338,27 @@ impl RequestBuilder {
         self
     }
 
+    /// Set the redirect policy of this request, overriding the one of the
+    /// client.
+    ///
+    /// ```rust
+    /// # async fn run() -> cyper::Result<()> {
+    /// use cyper::redirect::Policy;
+    ///
+    /// let client = cyper::Client::new()?;
+    /// let res = client
+    ///     .get("http://httpbin.org/redirect/1")?
+    ///     .redirect(Policy::none())
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn redirect(mut self, policy: redirect::Policy) -> RequestBuilder {
+        self.request.redirect_policy = Some(policy);
+        self
+    }
+
     /// Send a form body.
     ///
     /// Sets the body to the url encoded serialization of the passed value,
@@ -324,4 +433,27 @@ impl RequestBuilder {
     pub async fn send(self) -> Result<Response> {
         self.client.execute(self.request).await
     }
+
+    /// Open an [`EventSource`](crate::sse::EventSource) to receive
+    /// Server-Sent Events from the target URL.
+    ///
+    /// The request is sent again to reconnect, so the body must be clonable
+    /// to reconnect more than once.
+    #[cfg(feature = "sse")]
+    pub fn event_source(self) -> crate::sse::EventSource {
+        crate::sse::EventSource::new(self.client, self.request)
+    }
+
+    /// Open a WebSocket to the target URL, which could be either `ws`, `wss`,
+    /// `http` or `https`.
+    ///
+    /// The method and body of the request are ignored. The WebSocket is opened
+    /// with an HTTP/1.1 upgrade, or with an extended CONNECT if the connection
+    /// is HTTP/2. Set the version to HTTP/2 to use the latter directly.
+    /// Subprotocols could be requested with the `Sec-WebSocket-Protocol`
+    /// header.
+    #[cfg(feature = "ws")]
+    pub async fn websocket(self) -> Result<crate::ws::WebSocket> {
+        crate::ws::connect(self.client, self.request).await
+    }
 }
@@ -17,6 +17,31 @@ pub trait Resolve {
     /// Performs DNS resolution on a [`Uri`] and return a [`Stream`] of
     /// [`IpAddr`].
     async fn resolve(&self, uri: &Uri) -> Result<impl Stream<Item = IpAddr> + '_, Self::Err>;
+
+    /// Looks up the HTTPS service binding ([RFC 9460]) of a [`Uri`]. It is
+    /// used to discover HTTP/3 support before the first request.
+    ///
+    /// The default implementation returns `None`.
+    ///
+    /// [RFC 9460]: https://www.rfc-editor.org/rfc/rfc9460
+    async fn resolve_https(&self, uri: &Uri) -> Result<Option<HttpsRecord>, Self::Err> {
+        let _ = uri;
+        Ok(None)
+    }
+}
+
+/// The service binding of an origin, from an HTTPS DNS record.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HttpsRecord {
+    /// The target host, or `None` if it is the origin host.
+    pub target: Option<String>,
+    /// The port of the service, or `None` if it is the origin port.
+    pub port: Option<u16>,
+    /// The supported ALPN protocol IDs.
+    pub alpn: Vec<String>,
+    /// The address hints of the target.
+    pub addrs: Vec<IpAddr>,
 }
 
 #[derive(Clone)]
@@ -33,6 +58,14 @@ impl SharedResolver {
     ) -> Result<impl Stream<Item = IpAddr> + 'a, crate::Error> {
         self.0.type_erased_resolve(uri).await
     }
+
+    #[cfg(feature = "http3")]
+    pub(crate) async fn resolve_https(
+        &self,
+        uri: &Uri,
+    ) -> Result<Option<HttpsRecord>, crate::Error> {
+        self.0.type_erased_resolve_https(uri).await
+    }
 }
 
 impl Debug for SharedResolver {
@@ -46,6 +79,12 @@ trait TypeErasedResolve {
         &'a self,
         uri: &'a Uri,
     ) -> LocalBoxFuture<'a, Result<LocalBoxStream<'a, IpAddr>, crate::Error>>;
+
+    #[cfg(feature = "http3")]
+    fn type_erased_resolve_https<'a>(
+        &'a self,
+        uri: &'a Uri,
+    ) -> LocalBoxFuture<'a, Result<Option<HttpsRecord>, crate::Error>>;
 }
 
 impl<T: Resolve> TypeErasedResolve for T {
@@ -58,6 +97,14 @@ impl<T: Resolve> TypeErasedResolve for T {
             .map_ok(|stream| stream.boxed_local())
             .boxed_local()
     }
+
+    #[cfg(feature = "http3")]
+    fn type_erased_resolve_https<'a>(
+        &'a self,
+        uri: &'a Uri,
+    ) -> LocalBoxFuture<'a, Result<Option<HttpsRecord>, crate::Error>> {
+        self.resolve_https(uri).map_err(Into::into).boxed_local()
+    }
 }
 
 #[cfg(feature = "hickory-dns")]
@@ -66,12 +113,24 @@ mod hickory {
 
     use cyper_hickory::CompioConnectionProvider;
     use futures_util::Stream;
+    use hickory_net::proto::rr::{
+        RData, RecordType,
+        rdata::{
+            HTTPS, SVCB,
+            svcb::{IpHint, Mandatory, SvcParamKey, SvcParamValue},
+        },
+    };
     use hickory_resolver::Resolver;
     use http::Uri;
 
+    use super::HttpsRecord;
+
+    /// A resolver backed by [`hickory_resolver`]. It also looks up HTTPS
+    /// records.
     pub struct HickoryResolver(Resolver<CompioConnectionProvider>);
 
     impl HickoryResolver {
+        /// Creates a resolver with the system configuration.
         pub fn new() -> crate::Result<Self> {
             Ok(Self(
                 Resolver::builder(CompioConnectionProvider::default())?.build()?,
@@ -79,6 +138,49 @@ mod hickory {
         }
     }
 
+    impl From<Resolver<CompioConnectionProvider>> for HickoryResolver {
+        fn from(resolver: Resolver<CompioConnectionProvider>) -> Self {
+            Self(resolver)
+        }
+    }
+
+    fn https_record(svcb: &SVCB) -> Option<HttpsRecord> {
+        let mut record = HttpsRecord::default();
+        if !svcb.target_name.is_root() {
+            let target = svcb.target_name.to_utf8();
+            record.target = Some(target.trim_end_matches('.').to_string());
+        }
+        for (_, value) in &svcb.svc_params {
+            match value {
+                // The record must be ignored if any mandatory key is not supported.
+                SvcParamValue::Mandatory(Mandatory(keys))
+                    if !keys.iter().all(|key| {
+                        matches!(
+                            key,
+                            SvcParamKey::Alpn
+                                | SvcParamKey::NoDefaultAlpn
+                                | SvcParamKey::Port
+                                | SvcParamKey::Ipv4Hint
+                                | SvcParamKey::Ipv6Hint
+                        )
+                    }) =>
+                {
+                    return None;
+                }
+                SvcParamValue::Alpn(alpn) => record.alpn = alpn.0.clone(),
+                SvcParamValue::Port(port) => record.port = Some(*port),
+                SvcParamValue::Ipv4Hint(IpHint(hints)) => {
+                    record.addrs.extend(hints.iter().map(|a| IpAddr::V4(a.0)))
+                }
+                SvcParamValue::Ipv6Hint(IpHint(hints)) => {
+                    record.addrs.extend(hints.iter().map(|a| IpAddr::V6(a.0)))
+                }
+                _ => {}
+            }
+        }
+        Some(record)
+    }
+
     impl super::Resolve for HickoryResolver {
         type Err = crate::Error;
 
@@ -92,8 +194,42 @@ mod hickory {
                 lookup.iter().collect::<Vec<_>>(),
             ))
         }
+
+        async fn resolve_https(&self, uri: &Uri) -> Result<Option<HttpsRecord>, Self::Err> {
+            let Some(host) = uri.host() else {
+                return Ok(None);
+            };
+            // There are no service bindings for IP addresses.
+            if host.starts_with('[') || host.parse::<IpAddr>().is_ok() {
+                return Ok(None);
+            }
+            let name = match uri.port_u16() {
+                None | Some(443) => host.to_string(),
+                Some(port) => format!("_{port}._https.{host}"),
+            };
+            let lookup = match self.0.lookup(name, RecordType::HTTPS).await {
+                Ok(lookup) => lookup,
+                Err(e) if e.is_no_records_found() => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            let mut records = lookup
+                .answers()
+                .iter()
+                .filter_map(|record| match &record.data {
+                    RData::HTTPS(HTTPS(svcb)) => Some(svcb),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            // AliasMode records are not followed, and ServiceMode records
+            // must be ignored if there are any.
+            if records.iter().any(|svcb| svcb.svc_priority == 0) {
+                return Ok(None);
+            }
+            records.sort_by_key(|svcb| svcb.svc_priority);
+            Ok(records.into_iter().find_map(https_record))
+        }
     }
 }
 
 #[cfg(feature = "hickory-dns")]
-pub(crate) use hickory::HickoryResolver;
+pub use hickory::HickoryResolver;
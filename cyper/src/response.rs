@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, net::SocketAddr, time::Instant};
 
 use compio::bytes::Bytes;
 #[cfg(feature = "cookies")]
@@ -10,13 +10,21 @@ use hyper::body::{Body, Incoming};
 use mime::Mime;
 use url::Url;
 
-use crate::{ResponseBody, Result};
+use crate::{ConnectionInfo, ResponseBody, Result, TlsInfo, Upgraded};
 
 /// A Response to a submitted `Request`.
 pub struct Response {
     pub(super) res: hyper::Response<()>,
     pub(crate) body: ResponseBody,
+    trailers: Option<HeaderMap>,
     url: Url,
+    pub(crate) redirect_history: Vec<crate::redirect::Hop>,
+    /// When the request headers are sent, if known.
+    pub(crate) sent: Option<Instant>,
+    /// When the response headers are received.
+    pub(crate) received: Instant,
+    /// How many times the request is sent again, e.g. with credentials.
+    pub(crate) retries: u32,
 }
 
 impl Response {
@@ -24,13 +32,36 @@ impl Response {
         let (res, body) = res.into_parts();
         let mut res = hyper::Response::from_parts(res, ());
         let body = ResponseBody::Incoming(body).decompress(res.headers_mut());
-        Self { res, body, url }
+        Self {
+            res,
+            body,
+            trailers: None,
+            url,
+            redirect_history: Vec::new(),
+            sent: None,
+            received: Instant::now(),
+            retries: 0,
+        }
     }
 
-    #[cfg(feature = "http3")]
-    pub(crate) fn with_body(mut res: hyper::Response<()>, body: Bytes, url: Url) -> Self {
-        let body = ResponseBody::Blob(Some(Ok(body))).decompress(res.headers_mut());
-        Self { res, body, url }
+    #[cfg(any(feature = "http3", feature = "vcr"))]
+    pub(crate) fn with_body(
+        mut res: hyper::Response<()>,
+        body: Bytes,
+        trailers: Option<HeaderMap>,
+        url: Url,
+    ) -> Self {
+        let body = ResponseBody::Blob(Some(Ok(body)), trailers).decompress(res.headers_mut());
+        Self {
+            res,
+            body,
+            trailers: None,
+            url,
+            redirect_history: Vec::new(),
+            sent: None,
+            received: Instant::now(),
+            retries: 0,
+        }
     }
 
     /// Get the `StatusCode` of this `Response`.
@@ -74,6 +105,44 @@ impl Response {
         &self.url
     }
 
+    /// Get the redirect responses followed before this one, in order.
+    ///
+    /// It is also kept when the redirect policy stops, and this response is
+    /// the last redirect.
+    pub fn redirect_history(&self) -> &[crate::redirect::Hop] {
+        &self.redirect_history
+    }
+
+    fn connection_info(&self) -> Option<&ConnectionInfo> {
+        self.res.extensions().get::<ConnectionInfo>()
+    }
+
+    /// Get the remote address of the connection.
+    ///
+    /// It is the address of the proxy if the request is sent through one.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.connection_info()?.remote_addr
+    }
+
+    /// Get the local address of the connection.
+    ///
+    /// Over HTTP/3, the IP address may be unspecified, as the UDP socket is
+    /// bound to all interfaces.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.connection_info()?.local_addr
+    }
+
+    /// Get the details of the TLS session, only recorded with the rustls
+    /// backend.
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.connection_info()?.tls.as_ref()
+    }
+
+    /// Get the protocol negotiated with ALPN, e.g. `b"h2"`.
+    pub fn negotiated_protocol(&self) -> Option<&[u8]> {
+        self.connection_info()?.alpn.as_deref()
+    }
+
     /// Returns a reference to the associated extensions.
     pub fn extensions(&self) -> &http::Extensions {
         self.res.extensions()
@@ -84,8 +153,67 @@ impl Response {
         self.res.extensions_mut()
     }
 
+    /// Take over the connection of a `101 Switching Protocols` response, or
+    /// a successful response to a `CONNECT` request.
+    ///
+    /// The connection is no longer used for HTTP afterwards. Over HTTP/2, the
+    /// stream of an extended `CONNECT` is taken over instead.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use compio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+    /// use http::header;
+    ///
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = cyper::Client::new().unwrap();
+    /// let res = client
+    ///     .get("http://example.com/chat")?
+    ///     .header(header::CONNECTION, "upgrade")?
+    ///     .header(header::UPGRADE, "foo/1")?
+    ///     .send()
+    ///     .await?;
+    /// let mut stream = res.upgrade().await?;
+    /// stream.write_all("hello").await.0?;
+    /// stream.flush().await?;
+    /// let (_, buf) = stream.read_exact(Vec::with_capacity(5)).await.unwrap();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn upgrade(self) -> Result<Upgraded> {
+        Ok(Upgraded::new(hyper::upgrade::on(self.res).await?))
+    }
+
     // body methods
 
+    /// Get the trailers of the response.
+    ///
+    /// The trailers are received after the body, so the rest of the body is
+    /// read and discarded. Returns [`None`] if the server sent no trailers.
+    /// Note that HTTP/1.1 servers may only send trailers to requests with the
+    /// `TE: trailers` header.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn run() -> cyper::Result<()> {
+    /// let client = cyper::Client::new().unwrap();
+    /// let mut res = client.get("http://example.com/grpc")?.send().await?;
+    /// if let Some(trailers) = res.trailers().await? {
+    ///     println!("grpc-status: {:?}", trailers.get("grpc-status"));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn trailers(&mut self) -> Result<Option<&HeaderMap>> {
+        while let Some(frame) = self.body.frame().await {
+            if let Ok(trailers) = frame?.into_trailers() {
+                self.trailers = Some(trailers);
+            }
+        }
+        Ok(self.trailers.as_ref())
+    }
+
     /// Get the full response text.
     ///
     /// This method decodes the response body with BOM sniffing
@@ -213,6 +341,66 @@ impl Response {
         Ok(serde_json::from_slice(&full)?)
     }
 
+    /// Deserialize the response body as a stream of JSON values, separated by
+    /// newlines (NDJSON / JSON Lines) or record separators (JSON text
+    /// sequences, RFC 7464).
+    ///
+    /// The values are parsed as soon as they are received, and the blank
+    /// lines are skipped.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `json` and `stream` features enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// use futures_util::TryStreamExt;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Record {
+    ///     id: u64,
+    /// }
+    ///
+    /// # async fn run() -> cyper::Result<()> {
+    /// let client = cyper::Client::new().unwrap();
+    /// let mut records = std::pin::pin!(
+    ///     client
+    ///         .get("http://example.com/export.ndjson")?
+    ///         .send()
+    ///         .await?
+    ///         .json_lines::<Record>()
+    /// );
+    /// while let Some(record) = records.try_next().await? {
+    ///     println!("id: {}", record.id);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(all(feature = "json", feature = "stream"))]
+    pub fn json_lines<T: serde::de::DeserializeOwned>(
+        self,
+    ) -> impl futures_util::Stream<Item = Result<T>> {
+        crate::json_stream::json_lines(self.body)
+    }
+
+    /// Deserialize the elements of a top-level JSON array in the response
+    /// body as a stream.
+    ///
+    /// Each element is parsed as soon as it is received, so the whole array
+    /// is never buffered.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `json` and `stream` features enabled.
+    #[cfg(all(feature = "json", feature = "stream"))]
+    pub fn json_array<T: serde::de::DeserializeOwned>(
+        self,
+    ) -> impl futures_util::Stream<Item = Result<T>> {
+        crate::json_stream::json_array(self.body)
+    }
+
     /// Retrieve the cookies contained in the response.
     ///
     /// Note that invalid 'Set-Cookie' headers will be ignored.
@@ -247,6 +435,17 @@ impl Response {
         Ok(self.body.collect().await?.to_bytes())
     }
 
+    /// Parse the response body as `text/event-stream`, and convert it into a
+    /// [`futures_util::Stream`] of [`Event`](crate::sse::Event)s.
+    ///
+    /// The content type isn't checked, and the stream ends with the response.
+    /// Use [`RequestBuilder::event_source`](crate::RequestBuilder::event_source)
+    /// to reconnect automatically.
+    #[cfg(feature = "sse")]
+    pub fn sse(self) -> crate::sse::EventStream {
+        crate::sse::EventStream::new(self.body, None)
+    }
+
     /// Convert the response into a [`futures_util::Stream`] of [`Bytes`]
     ///
     /// # Example
@@ -285,7 +484,23 @@ impl futures_util::Stream for Response {
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        std::pin::Pin::new(&mut self.body).poll_next(cx)
+        // Keep the trailers for `Response::trailers`.
+        loop {
+            let frame = std::task::ready!(std::pin::Pin::new(&mut self.body).poll_frame(cx));
+            return std::task::Poll::Ready(match frame {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => Some(Ok(data)),
+                    Err(frame) => {
+                        if let Ok(trailers) = frame.into_trailers() {
+                            self.trailers = Some(trailers);
+                        }
+                        continue;
+                    }
+                },
+                Some(Err(e)) => Some(Err(e)),
+                None => None,
+            });
+        }
     }
 }
 
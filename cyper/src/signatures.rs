@@ -0,0 +1,923 @@
+//! HTTP Message Signatures (RFC 9421).
+//!
+//! A [`Signer`] set for a host with
+//! [`ClientBuilder::sign_requests`](crate::ClientBuilder::sign_requests)
+//! signs every request to that host, including each redirect hop, right
+//! before it is sent. A [`Verifier`] checks the signatures of responses.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use cyper::signatures::{Signer, SigningKey};
+//!
+//! # async fn run() -> cyper::Result<()> {
+//! let signer = Signer::new("my-key", SigningKey::hmac_sha256(b"secret")).components([
+//!     "@method",
+//!     "@target-uri",
+//!     "content-digest",
+//!     "content-type",
+//! ]);
+//! let client = cyper::Client::builder()
+//!     .sign_requests("api.example.com", signer)
+//!     .build()?;
+//! let res = client
+//!     .post("https://api.example.com/payments")?
+//!     .header("content-type", "application/json")?
+//!     .body(r#"{"amount":1}"#)
+//!     .send()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use ring::{
+    digest, hmac,
+    rand::SystemRandom,
+    signature::{self, EcdsaKeyPair, Ed25519KeyPair, KeyPair},
+};
+use url::Url;
+
+use crate::{Body, Error, Response, Result};
+
+const SIGNATURE: &str = "signature";
+const SIGNATURE_INPUT: &str = "signature-input";
+const CONTENT_DIGEST: &str = "content-digest";
+
+fn error(msg: impl Into<String>) -> Error {
+    Error::Signature(msg.into())
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+enum SigningKeyInner {
+    Hmac(hmac::Key),
+    Ed25519(Ed25519KeyPair),
+    Ecdsa(EcdsaKeyPair, &'static str),
+}
+
+/// A key to sign requests.
+pub struct SigningKey(SigningKeyInner);
+
+impl SigningKey {
+    /// An `hmac-sha256` shared secret.
+    pub fn hmac_sha256(secret: &[u8]) -> Self {
+        Self(SigningKeyInner::Hmac(hmac::Key::new(
+            hmac::HMAC_SHA256,
+            secret,
+        )))
+    }
+
+    /// An `ed25519` private key in PKCS#8 DER.
+    pub fn ed25519_from_pkcs8(pkcs8: &[u8]) -> Result<Self> {
+        Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8)
+            .map(|key| Self(SigningKeyInner::Ed25519(key)))
+            .map_err(|e| error(format!("invalid ed25519 key: {e}")))
+    }
+
+    /// An `ed25519` private key from its 32-byte seed.
+    pub fn ed25519_from_seed(seed: &[u8]) -> Result<Self> {
+        Ed25519KeyPair::from_seed_unchecked(seed)
+            .map(|key| Self(SigningKeyInner::Ed25519(key)))
+            .map_err(|e| error(format!("invalid ed25519 key: {e}")))
+    }
+
+    /// An `ecdsa-p256-sha256` private key in PKCS#8 DER.
+    pub fn ecdsa_p256_sha256_from_pkcs8(pkcs8: &[u8]) -> Result<Self> {
+        Self::ecdsa(
+            &signature::ECDSA_P256_SHA256_FIXED_SIGNING,
+            pkcs8,
+            "ecdsa-p256-sha256",
+        )
+    }
+
+    /// An `ecdsa-p384-sha384` private key in PKCS#8 DER.
+    pub fn ecdsa_p384_sha384_from_pkcs8(pkcs8: &[u8]) -> Result<Self> {
+        Self::ecdsa(
+            &signature::ECDSA_P384_SHA384_FIXED_SIGNING,
+            pkcs8,
+            "ecdsa-p384-sha384",
+        )
+    }
+
+    fn ecdsa(
+        alg: &'static signature::EcdsaSigningAlgorithm,
+        pkcs8: &[u8],
+        name: &'static str,
+    ) -> Result<Self> {
+        EcdsaKeyPair::from_pkcs8(alg, pkcs8, &SystemRandom::new())
+            .map(|key| Self(SigningKeyInner::Ecdsa(key, name)))
+            .map_err(|e| error(format!("invalid {name} key: {e}")))
+    }
+
+    /// The public key of an asymmetric key, to be shared with the verifier.
+    pub fn public_key(&self) -> Option<&[u8]> {
+        match &self.0 {
+            SigningKeyInner::Hmac(_) => None,
+            SigningKeyInner::Ed25519(key) => Some(key.public_key().as_ref()),
+            SigningKeyInner::Ecdsa(key, _) => Some(key.public_key().as_ref()),
+        }
+    }
+
+    fn alg(&self) -> &'static str {
+        match &self.0 {
+            SigningKeyInner::Hmac(_) => "hmac-sha256",
+            SigningKeyInner::Ed25519(_) => "ed25519",
+            SigningKeyInner::Ecdsa(_, name) => name,
+        }
+    }
+
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        match &self.0 {
+            SigningKeyInner::Hmac(key) => Ok(hmac::sign(key, msg).as_ref().to_vec()),
+            SigningKeyInner::Ed25519(key) => Ok(key.sign(msg).as_ref().to_vec()),
+            SigningKeyInner::Ecdsa(key, _) => key
+                .sign(&SystemRandom::new(), msg)
+                .map(|sig| sig.as_ref().to_vec())
+                .map_err(|_| error("failed to sign")),
+        }
+    }
+}
+
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SigningKey").field(&self.alg()).finish()
+    }
+}
+
+enum VerifyingKeyInner {
+    Hmac(hmac::Key),
+    Public(
+        &'static dyn signature::VerificationAlgorithm,
+        Vec<u8>,
+        &'static str,
+    ),
+}
+
+/// A key to verify signatures.
+pub struct VerifyingKey(VerifyingKeyInner);
+
+impl VerifyingKey {
+    /// An `hmac-sha256` shared secret.
+    pub fn hmac_sha256(secret: &[u8]) -> Self {
+        Self(VerifyingKeyInner::Hmac(hmac::Key::new(
+            hmac::HMAC_SHA256,
+            secret,
+        )))
+    }
+
+    /// An `ed25519` public key of 32 bytes.
+    pub fn ed25519(public_key: &[u8]) -> Self {
+        Self(VerifyingKeyInner::Public(
+            &signature::ED25519,
+            public_key.to_vec(),
+            "ed25519",
+        ))
+    }
+
+    /// An `ecdsa-p256-sha256` public key, as an uncompressed point.
+    pub fn ecdsa_p256_sha256(public_key: &[u8]) -> Self {
+        Self(VerifyingKeyInner::Public(
+            &signature::ECDSA_P256_SHA256_FIXED,
+            public_key.to_vec(),
+            "ecdsa-p256-sha256",
+        ))
+    }
+
+    /// An `ecdsa-p384-sha384` public key, as an uncompressed point.
+    pub fn ecdsa_p384_sha384(public_key: &[u8]) -> Self {
+        Self(VerifyingKeyInner::Public(
+            &signature::ECDSA_P384_SHA384_FIXED,
+            public_key.to_vec(),
+            "ecdsa-p384-sha384",
+        ))
+    }
+
+    fn alg(&self) -> &'static str {
+        match &self.0 {
+            VerifyingKeyInner::Hmac(_) => "hmac-sha256",
+            VerifyingKeyInner::Public(_, _, name) => name,
+        }
+    }
+
+    fn verify(&self, msg: &[u8], sig: &[u8]) -> bool {
+        match &self.0 {
+            VerifyingKeyInner::Hmac(key) => hmac::verify(key, msg, sig).is_ok(),
+            VerifyingKeyInner::Public(alg, key, _) => signature::UnparsedPublicKey::new(*alg, key)
+                .verify(msg, sig)
+                .is_ok(),
+        }
+    }
+}
+
+impl std::fmt::Debug for VerifyingKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("VerifyingKey").field(&self.alg()).finish()
+    }
+}
+
+/// Signs the requests with a key and the covered components.
+///
+/// The covered components are `@method` and `@target-uri` by default.
+/// The derived components `@method`, `@target-uri`, `@authority`, `@scheme`,
+/// `@request-target`, `@path` and `@query`, and the header fields are
+/// supported. If `content-digest` is covered and the request doesn't have
+/// the header, a SHA-256 digest of the body is added, which requires the body
+/// not to be a stream. Signing fails if a covered header is missing.
+#[derive(Debug)]
+pub struct Signer {
+    key_id: String,
+    key: SigningKey,
+    components: Vec<String>,
+    label: String,
+    expires_in: Option<Duration>,
+    tag: Option<String>,
+    include_alg: bool,
+}
+
+impl Signer {
+    /// Create a signer with the key ID and the key.
+    pub fn new(key_id: impl Into<String>, key: SigningKey) -> Self {
+        Self {
+            key_id: key_id.into(),
+            key,
+            components: vec!["@method".into(), "@target-uri".into()],
+            label: "sig1".into(),
+            expires_in: None,
+            tag: None,
+            include_alg: true,
+        }
+    }
+
+    /// Set the covered components. The header names are case-insensitive.
+    pub fn components<I, S>(mut self, components: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.components = components
+            .into_iter()
+            .map(|c| c.into().to_ascii_lowercase())
+            .collect();
+        self
+    }
+
+    /// Set the label of the signature, `sig1` by default.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = label.into();
+        self
+    }
+
+    /// Add the `expires` parameter, the duration after the creation.
+    pub fn expires_in(mut self, duration: Duration) -> Self {
+        self.expires_in = Some(duration);
+        self
+    }
+
+    /// Add the `tag` parameter, identifying the application of the signature.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    /// Whether to add the `alg` parameter, `true` by default.
+    pub fn include_alg(mut self, include: bool) -> Self {
+        self.include_alg = include;
+        self
+    }
+
+    pub(crate) fn sign(&self, request: &mut http::Request<Body>, url: &Url) -> Result<()> {
+        self.sign_at(request, url, unix_time())
+    }
+
+    fn sign_at(&self, request: &mut http::Request<Body>, url: &Url, created: u64) -> Result<()> {
+        if self.components.iter().any(|c| c == CONTENT_DIGEST)
+            && !request.headers().contains_key(CONTENT_DIGEST)
+        {
+            let body = request
+                .body()
+                .as_bytes()
+                .ok_or_else(|| error("content-digest cannot be computed for a stream body"))?;
+            let value = content_digest(body);
+            request.headers_mut().insert(
+                CONTENT_DIGEST,
+                HeaderValue::from_str(&value).expect("base64 is valid"),
+            );
+        }
+
+        let mut params = format!(
+            "({})",
+            self.components
+                .iter()
+                .map(|c| quote(c))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        let _ = write!(params, ";created={created}");
+        if let Some(expires_in) = self.expires_in {
+            let _ = write!(params, ";expires={}", created + expires_in.as_secs());
+        }
+        let _ = write!(params, ";keyid={}", quote(&self.key_id));
+        if self.include_alg {
+            let _ = write!(params, ";alg={}", quote(self.key.alg()));
+        }
+        if let Some(tag) = &self.tag {
+            let _ = write!(params, ";tag={}", quote(tag));
+        }
+
+        let mut base = String::new();
+        for component in &self.components {
+            let value = request_component(request, url, component)?;
+            let _ = writeln!(base, "{}: {value}", quote(component));
+        }
+        let _ = write!(base, "\"@signature-params\": {params}");
+        let signature = self.key.sign(base.as_bytes())?;
+
+        let input = HeaderValue::from_str(&format!("{}={params}", self.label))
+            .map_err(|_| error("invalid signature parameters"))?;
+        let signature = HeaderValue::from_str(&format!(
+            "{}=:{}:",
+            self.label,
+            BASE64_STANDARD.encode(signature)
+        ))
+        .map_err(|_| error("invalid signature label"))?;
+        let headers = request.headers_mut();
+        headers.insert(SIGNATURE_INPUT, input);
+        headers.insert(SIGNATURE, signature);
+        Ok(())
+    }
+}
+
+fn content_digest(body: &[u8]) -> String {
+    format!(
+        "sha-256=:{}:",
+        BASE64_STANDARD.encode(digest::digest(&digest::SHA256, body))
+    )
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn request_component(request: &http::Request<Body>, url: &Url, name: &str) -> Result<String> {
+    let value = match name {
+        "@method" => request.method().to_string(),
+        "@target-uri" => {
+            let mut url = url.clone();
+            url.set_fragment(None);
+            url.to_string()
+        }
+        "@authority" => {
+            let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+            match url.port() {
+                Some(port) => format!("{host}:{port}"),
+                None => host,
+            }
+        }
+        "@scheme" => url.scheme().to_string(),
+        "@request-target" => match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
+        },
+        "@path" => url.path().to_string(),
+        "@query" => format!("?{}", url.query().unwrap_or_default()),
+        _ if name.starts_with('@') => {
+            return Err(error(format!("unsupported component: {name}")));
+        }
+        _ => header_component(request.headers(), name)?,
+    };
+    Ok(value)
+}
+
+fn header_component(headers: &HeaderMap, name: &str) -> Result<String> {
+    let name =
+        HeaderName::try_from(name).map_err(|_| error(format!("invalid component: {name}")))?;
+    let mut values = headers.get_all(&name).iter().peekable();
+    if values.peek().is_none() {
+        return Err(error(format!("missing header: {name}")));
+    }
+    let values = values
+        .map(|value| {
+            value
+                .to_str()
+                .map(str::trim)
+                .map_err(|_| error(format!("invalid header: {name}")))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(values.join(", "))
+}
+
+/// Verifies the signatures of responses.
+///
+/// A response is accepted if any of its signatures is valid, made with a
+/// known key, and covers the required components. The components `@status`
+/// and the header fields are supported; the request-bound components (with
+/// the `req` parameter) are not.
+///
+/// The digest of the body isn't checked. If `content-digest` is covered,
+/// compare it with the body after receiving it.
+#[derive(Debug, Default)]
+pub struct Verifier {
+    keys: HashMap<String, VerifyingKey>,
+    required: Vec<String>,
+    max_age: Option<Duration>,
+}
+
+impl Verifier {
+    /// Create a verifier without keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a key, identified by the `keyid` parameter of the signatures.
+    pub fn key(mut self, key_id: impl Into<String>, key: VerifyingKey) -> Self {
+        self.keys.insert(key_id.into(), key);
+        self
+    }
+
+    /// Set the components that a signature must cover.
+    pub fn require_components<I, S>(mut self, components: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.required = components
+            .into_iter()
+            .map(|c| c.into().to_ascii_lowercase())
+            .collect();
+        self
+    }
+
+    /// Reject the signatures created longer ago than the duration, or
+    /// without the `created` parameter.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Verify the signatures of a response.
+    pub fn verify(&self, response: &Response) -> Result<()> {
+        self.verify_parts(response.status(), response.headers(), unix_time())
+    }
+
+    fn verify_parts(&self, status: StatusCode, headers: &HeaderMap, now: u64) -> Result<()> {
+        let inputs = parse_dictionary(&header_component(headers, SIGNATURE_INPUT)?)?;
+        let signatures = parse_dictionary(&header_component(headers, SIGNATURE)?)?;
+        let mut last_error = error("no signature");
+        for input in inputs {
+            match self.verify_one(&input, &signatures, status, headers, now) {
+                Ok(()) => return Ok(()),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    fn verify_one(
+        &self,
+        input: &Member,
+        signatures: &[Member],
+        status: StatusCode,
+        headers: &HeaderMap,
+        now: u64,
+    ) -> Result<()> {
+        let Value::InnerList(components) = &input.value else {
+            return Err(error("invalid signature input"));
+        };
+        let signature = signatures
+            .iter()
+            .find(|sig| sig.key == input.key)
+            .and_then(|sig| match &sig.value {
+                Value::Bytes(bytes) => Some(bytes),
+                _ => None,
+            })
+            .ok_or_else(|| error(format!("missing signature: {}", input.key)))?;
+        let param = |name: &str| input.params.iter().find(|(k, _)| k == name).map(|(_, v)| v);
+
+        let key_id = match param("keyid") {
+            Some(Item::String(key_id)) => key_id,
+            _ => return Err(error("missing keyid")),
+        };
+        let key = self
+            .keys
+            .get(key_id)
+            .ok_or_else(|| error(format!("unknown key: {key_id}")))?;
+        match param("alg") {
+            Some(Item::String(alg)) if alg != key.alg() => {
+                return Err(error(format!("unexpected algorithm: {alg}")));
+            }
+            _ => {}
+        }
+        if let Some(Item::Integer(expires)) = param("expires")
+            && *expires < now as i64
+        {
+            return Err(error("signature expired"));
+        }
+        if let Some(max_age) = self.max_age {
+            match param("created") {
+                Some(Item::Integer(created))
+                    if now as i64 - *created <= max_age.as_secs() as i64 => {}
+                _ => return Err(error("signature too old")),
+            }
+        }
+        if let Some(missing) = self
+            .required
+            .iter()
+            .find(|required| !components.iter().any(|(c, _)| c == *required))
+        {
+            return Err(error(format!("component not covered: {missing}")));
+        }
+
+        let mut base = String::new();
+        for (component, has_params) in components {
+            if *has_params {
+                return Err(error(format!("unsupported component: {component}")));
+            }
+            let value = match component.as_str() {
+                "@status" => status.as_str().to_string(),
+                _ if component.starts_with('@') => {
+                    return Err(error(format!("unsupported component: {component}")));
+                }
+                _ => header_component(headers, component)?,
+            };
+            let _ = writeln!(base, "{}: {value}", quote(component));
+        }
+        let _ = write!(base, "\"@signature-params\": {}", input.raw);
+        if key.verify(base.as_bytes(), signature) {
+            Ok(())
+        } else {
+            Err(error("signature mismatch"))
+        }
+    }
+}
+
+/// A member of a structured field dictionary (RFC 8941), with the subset of
+/// values used by the signature fields.
+#[derive(Debug)]
+struct Member {
+    key: String,
+    value: Value,
+    params: Vec<(String, Item)>,
+    /// The serialized value with the parameters.
+    raw: String,
+}
+
+#[derive(Debug)]
+enum Value {
+    /// The strings of the inner list, and whether they have parameters.
+    InnerList(Vec<(String, bool)>),
+    Bytes(Vec<u8>),
+    Item,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Item {
+    Integer(i64),
+    String(String),
+    Other,
+}
+
+fn parse_dictionary(s: &str) -> Result<Vec<Member>> {
+    let invalid = || error("invalid structured field");
+    let mut p = SfParser { s, pos: 0 };
+    let mut members = vec![];
+    p.skip_ws();
+    while !p.eof() {
+        let key = p.key().ok_or_else(invalid)?;
+        if !p.eat(b'=') {
+            return Err(invalid());
+        }
+        let start = p.pos;
+        let value = if p.eat(b'(') {
+            let mut items = vec![];
+            loop {
+                p.skip(b' ');
+                if p.eat(b')') {
+                    break;
+                }
+                let Some(Item::String(item)) = p.bare_item() else {
+                    return Err(invalid());
+                };
+                let params = p.params().ok_or_else(invalid)?;
+                items.push((item, !params.is_empty()));
+            }
+            Value::InnerList(items)
+        } else if p.peek() == Some(b':') {
+            p.pos += 1;
+            let end = p.s[p.pos..].find(':').ok_or_else(invalid)?;
+            let bytes = BASE64_STANDARD
+                .decode(&p.s[p.pos..p.pos + end])
+                .map_err(|_| invalid())?;
+            p.pos += end + 1;
+            Value::Bytes(bytes)
+        } else {
+            p.bare_item().ok_or_else(invalid)?;
+            Value::Item
+        };
+        let params = p.params().ok_or_else(invalid)?;
+        members.push(Member {
+            key,
+            value,
+            params,
+            raw: p.s[start..p.pos].to_string(),
+        });
+        p.skip_ws();
+        if p.eof() {
+            break;
+        }
+        if !p.eat(b',') {
+            return Err(invalid());
+        }
+        p.skip_ws();
+    }
+    Ok(members)
+}
+
+struct SfParser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl SfParser<'_> {
+    fn eof(&self) -> bool {
+        self.pos >= self.s.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.as_bytes().get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        let eaten = self.peek() == Some(c);
+        if eaten {
+            self.pos += 1;
+        }
+        eaten
+    }
+
+    fn skip(&mut self, c: u8) {
+        while self.eat(c) {}
+    }
+
+    fn skip_ws(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(u8) -> bool) -> &str {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.pos += 1;
+        }
+        &self.s[start..self.pos]
+    }
+
+    fn key(&mut self) -> Option<String> {
+        if !self.peek()?.is_ascii_lowercase() && self.peek()? != b'*' {
+            return None;
+        }
+        let key = self
+            .take_while(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || b"_-.*".contains(&c));
+        Some(key.to_string())
+    }
+
+    fn bare_item(&mut self) -> Option<Item> {
+        match self.peek()? {
+            b'"' => {
+                self.pos += 1;
+                let mut value = String::new();
+                loop {
+                    match self.peek()? {
+                        b'"' => {
+                            self.pos += 1;
+                            return Some(Item::String(value));
+                        }
+                        b'\\' => {
+                            self.pos += 1;
+                            value.push(self.peek()? as char);
+                        }
+                        c if (0x20..0x7f).contains(&c) => value.push(c as char),
+                        _ => return None,
+                    }
+                    self.pos += 1;
+                }
+            }
+            b'-' | b'0'..=b'9' => {
+                let start = self.pos;
+                self.pos += 1;
+                self.take_while(|c| c.is_ascii_digit() || c == b'.');
+                let number = &self.s[start..self.pos];
+                Some(number.parse().map(Item::Integer).unwrap_or(Item::Other))
+            }
+            b'?' => {
+                self.pos += 1;
+                self.take_while(|c| c == b'0' || c == b'1');
+                Some(Item::Other)
+            }
+            c if c.is_ascii_alphabetic() || c == b'*' => {
+                self.take_while(|c| c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~:/".contains(&c));
+                Some(Item::Other)
+            }
+            _ => None,
+        }
+    }
+
+    fn params(&mut self) -> Option<Vec<(String, Item)>> {
+        let mut params = vec![];
+        while self.eat(b';') {
+            self.skip(b' ');
+            let key = self.key()?;
+            let value = if self.eat(b'=') {
+                self.bare_item()?
+            } else {
+                Item::Other
+            };
+            params.push((key, value));
+        }
+        Some(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{Method, Request};
+
+    use super::*;
+
+    // The test request of RFC 9421 appendix B.2.
+    fn request() -> (Request<Body>, Url) {
+        let url = Url::parse("https://example.com/foo?param=Value&Pet=dog").unwrap();
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(url.as_str())
+            .header("host", "example.com")
+            .header("date", "Tue, 20 Apr 2021 02:07:55 GMT")
+            .header("content-type", "application/json")
+            .header(
+                "content-digest",
+                "sha-512=:WZDPaVn/\
+                 7XgHaAy8pmojAkGWoRx2UFChF41A2svX+TaPm+AbwAgBWnrIiYllu7BNNyealdVLvRwEmTHWXvJwew==:",
+            )
+            .header("content-length", "18")
+            .body(Body::from(r#"{"hello": "world"}"#))
+            .unwrap();
+        (req, url)
+    }
+
+    fn signature(req: &Request<Body>) -> &str {
+        req.headers()[SIGNATURE].to_str().unwrap()
+    }
+
+    #[test]
+    fn test_rfc9421_hmac() {
+        let key = BASE64_STANDARD
+            .decode("uzvJfB4u3N0Jy4T7NZ75MDVcr8zSTInedJtkgcu46YW4XByzNJjxBdtjUkdJPBtbmHhIDi6pcl8jsasjlTMtDQ==")
+            .unwrap();
+        let signer = Signer::new("test-shared-secret", SigningKey::hmac_sha256(&key))
+            .label("sig-b25")
+            .components(["date", "@authority", "content-type"])
+            .include_alg(false);
+        let (mut req, url) = request();
+        signer.sign_at(&mut req, &url, 1618884473).unwrap();
+        assert_eq!(
+            req.headers()[SIGNATURE_INPUT],
+            r#"sig-b25=("date" "@authority" "content-type");created=1618884473;keyid="test-shared-secret""#
+        );
+        assert_eq!(
+            signature(&req),
+            "sig-b25=:pxcQw6G3AjtMBQjwo8XzkZf/bws5LelbaMk5rGIGtE8=:"
+        );
+    }
+
+    #[test]
+    fn test_rfc9421_ed25519() {
+        let pkcs8 = BASE64_STANDARD
+            .decode("MC4CAQAwBQYDK2VwBCIEIJ+DYvh6SEqVTm50DFtMDoQikTmiCqirVv9mWG9qfSnF")
+            .unwrap();
+        let signer = Signer::new(
+            "test-key-ed25519",
+            SigningKey::ed25519_from_pkcs8(&pkcs8).unwrap(),
+        )
+        .label("sig-b26")
+        .components([
+            "date",
+            "@method",
+            "@path",
+            "@authority",
+            "content-type",
+            "content-length",
+        ])
+        .include_alg(false);
+        let (mut req, url) = request();
+        signer.sign_at(&mut req, &url, 1618884473).unwrap();
+        assert_eq!(
+            signature(&req),
+            "sig-b26=:wqcAqbmYJ2ji2glfAMaRy4gruYYnx2nEFN2HN6jrnDnQCK1u02Gb04v9EDgwUPiu4A0w6vuQv5lIp5WPpBKRCw==:"
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let signer = Signer::new("key", SigningKey::hmac_sha256(b"secret"))
+            .components(["content-type", "content-length"])
+            .expires_in(Duration::from_secs(60));
+        let (mut req, url) = request();
+        signer.sign_at(&mut req, &url, 1000).unwrap();
+        let headers = req.headers_mut();
+
+        let verifier = Verifier::new()
+            .key("key", VerifyingKey::hmac_sha256(b"secret"))
+            .require_components(["content-type"])
+            .max_age(Duration::from_secs(30));
+        verifier
+            .verify_parts(StatusCode::OK, headers, 1010)
+            .unwrap();
+        // Too old.
+        verifier
+            .verify_parts(StatusCode::OK, headers, 1040)
+            .unwrap_err();
+        // Expired.
+        let verifier = Verifier::new().key("key", VerifyingKey::hmac_sha256(b"secret"));
+        verifier
+            .verify_parts(StatusCode::OK, headers, 1040)
+            .unwrap();
+        verifier
+            .verify_parts(StatusCode::OK, headers, 1070)
+            .unwrap_err();
+        // A required component isn't covered.
+        Verifier::new()
+            .key("key", VerifyingKey::hmac_sha256(b"secret"))
+            .require_components(["@status"])
+            .verify_parts(StatusCode::OK, headers, 1000)
+            .unwrap_err();
+        // Tampered.
+        headers.insert("content-length", HeaderValue::from_static("19"));
+        verifier
+            .verify_parts(StatusCode::OK, headers, 1000)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_ecdsa_round_trip() {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+            .unwrap();
+        let key = SigningKey::ecdsa_p256_sha256_from_pkcs8(pkcs8.as_ref()).unwrap();
+        let public_key = key.public_key().unwrap().to_vec();
+        let signer = Signer::new("ecdsa", key).components(["content-type"]);
+        let (mut req, url) = request();
+        signer.sign_at(&mut req, &url, 1618884473).unwrap();
+        Verifier::new()
+            .key("ecdsa", VerifyingKey::ecdsa_p256_sha256(&public_key))
+            .verify_parts(StatusCode::OK, req.headers(), 1618884473)
+            .unwrap();
+        // The algorithm doesn't match.
+        Verifier::new()
+            .key("ecdsa", VerifyingKey::ed25519(&public_key))
+            .verify_parts(StatusCode::OK, req.headers(), 1618884473)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_sign_errors() {
+        let signer = Signer::new("key", SigningKey::hmac_sha256(b"secret"));
+        let (mut req, url) = request();
+        for components in [&["x-missing"][..], &["@unknown"]] {
+            let signer = Signer::new("key", SigningKey::hmac_sha256(b"secret"))
+                .components(components.iter().copied());
+            signer.sign_at(&mut req, &url, 0).unwrap_err();
+        }
+        signer.sign_at(&mut req, &url, 0).unwrap();
+    }
+
+    #[test]
+    fn test_content_digest() {
+        // The example of RFC 9530 section 2.
+        assert_eq!(
+            content_digest(br#"{"hello": "world"}"#),
+            "sha-256=:X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE=:"
+        );
+    }
+}
@@ -0,0 +1,385 @@
+//! Server-Sent Events.
+//!
+//! [`Response::sse`] parses a `text/event-stream` response into [`Event`]s,
+//! and [`RequestBuilder::event_source`] opens an [`EventSource`], which
+//! reconnects automatically with `Last-Event-ID`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use futures_util::StreamExt;
+//!
+//! # async fn run() -> cyper::Result<()> {
+//! let client = cyper::Client::new()?;
+//! let mut events = client.get("http://example.com/events")?.event_source();
+//! while let Some(event) = events.next().await {
+//!     let event = event?;
+//!     println!("{}: {}", event.event, event.data);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Response::sse`]: crate::Response::sse
+//! [`RequestBuilder::event_source`]: crate::RequestBuilder::event_source
+
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{Context, Poll, ready},
+    time::Duration,
+};
+
+use compio::bytes::{Buf, BytesMut};
+use futures_util::{Stream, StreamExt};
+use http::{HeaderValue, StatusCode, header};
+
+use crate::{Client, Request, ResponseBody, Result};
+
+/// The reconnection time used before the server sends `retry`.
+const DEFAULT_RETRY: Duration = Duration::from_secs(3);
+
+/// An event of the event stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Event {
+    /// The last event ID, which may be sent with an earlier event.
+    pub id: Option<String>,
+    /// The event type, `message` by default.
+    pub event: String,
+    /// The data of the event. Multiple `data` lines are joined with `\n`.
+    pub data: String,
+    /// The reconnection time sent with the event.
+    pub retry: Option<Duration>,
+}
+
+/// An incremental parser of `text/event-stream`.
+#[derive(Debug, Default)]
+struct Parser {
+    buf: BytesMut,
+    started: bool,
+    // The last line ended with `\r`, and a following `\n` should be skipped.
+    after_cr: bool,
+    data: String,
+    event: String,
+    last_event_id: Option<String>,
+    // The reconnection time sent in the current event.
+    retry: Option<Duration>,
+    reconnection_time: Option<Duration>,
+}
+
+impl Parser {
+    fn new(last_event_id: Option<String>) -> Self {
+        Self {
+            last_event_id,
+            ..Default::default()
+        }
+    }
+
+    fn feed(&mut self, chunk: &[u8], events: &mut VecDeque<Event>) {
+        self.buf.extend_from_slice(chunk);
+        if !self.started {
+            if self.buf.len() < 3 && b"\xEF\xBB\xBF".starts_with(&self.buf) {
+                return;
+            }
+            if self.buf.starts_with(b"\xEF\xBB\xBF") {
+                self.buf.advance(3);
+            }
+            self.started = true;
+        }
+        loop {
+            if self.after_cr {
+                match self.buf.first() {
+                    Some(b'\n') => self.buf.advance(1),
+                    Some(_) => {}
+                    None => break,
+                }
+                self.after_cr = false;
+            }
+            let Some(pos) = self.buf.iter().position(|&b| b == b'\n' || b == b'\r') else {
+                break;
+            };
+            self.after_cr = self.buf[pos] == b'\r';
+            let line = self.buf.split_to(pos + 1);
+            self.line(&line[..pos], events);
+        }
+    }
+
+    fn line(&mut self, line: &[u8], events: &mut VecDeque<Event>) {
+        if line.is_empty() {
+            self.dispatch(events);
+            return;
+        }
+        if line[0] == b':' {
+            return;
+        }
+        let line = String::from_utf8_lossy(line);
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line.as_ref(), ""),
+        };
+        match field {
+            "event" => value.clone_into(&mut self.event),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => {
+                self.last_event_id = (!value.is_empty()).then(|| value.to_string());
+            }
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                if let Ok(ms) = value.parse() {
+                    self.retry = Some(Duration::from_millis(ms));
+                    self.reconnection_time = self.retry;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn dispatch(&mut self, events: &mut VecDeque<Event>) {
+        let retry = self.retry.take();
+        let event = std::mem::take(&mut self.event);
+        if self.data.is_empty() {
+            return;
+        }
+        let mut data = std::mem::take(&mut self.data);
+        data.pop();
+        events.push_back(Event {
+            id: self.last_event_id.clone(),
+            event: if event.is_empty() {
+                "message".to_string()
+            } else {
+                event
+            },
+            data,
+            retry,
+        });
+    }
+}
+
+/// A stream of [`Event`]s parsed from a response, created by
+/// [`Response::sse`](crate::Response::sse).
+///
+/// It doesn't reconnect when the response ends. An incomplete event at the
+/// end of the response is discarded.
+pub struct EventStream {
+    body: Option<ResponseBody>,
+    parser: Parser,
+    events: VecDeque<Event>,
+}
+
+impl EventStream {
+    pub(crate) fn new(body: ResponseBody, last_event_id: Option<String>) -> Self {
+        Self {
+            body: Some(body),
+            parser: Parser::new(last_event_id),
+            events: VecDeque::new(),
+        }
+    }
+
+    /// The last event ID received, to be sent as `Last-Event-ID` when
+    /// reconnecting.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.parser.last_event_id.as_deref()
+    }
+
+    /// The latest reconnection time sent by the server.
+    pub fn reconnection_time(&self) -> Option<Duration> {
+        self.parser.reconnection_time
+    }
+}
+
+impl std::fmt::Debug for EventStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventStream")
+            .field("parser", &self.parser)
+            .field("events", &self.events)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Result<Event>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if let Some(event) = this.events.pop_front() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+            let Some(body) = &mut this.body else {
+                return Poll::Ready(None);
+            };
+            match ready!(body.poll_next_unpin(cx)) {
+                Some(Ok(chunk)) => this.parser.feed(&chunk, &mut this.events),
+                Some(Err(e)) => {
+                    this.body = None;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                None => this.body = None,
+            }
+        }
+    }
+}
+
+/// A stream of [`Event`]s which reconnects automatically, created by
+/// [`RequestBuilder::event_source`](crate::RequestBuilder::event_source).
+///
+/// When the response ends or fails, the request is sent again after the
+/// reconnection time, with the `Last-Event-ID` header. Connection errors are
+/// yielded and followed by a reconnection. The stream ends after yielding an
+/// error, if the server responds with a status other than `200 OK` or with a
+/// content type other than `text/event-stream`, and ends silently with
+/// `204 No Content`.
+pub struct EventSource {
+    inner: Pin<Box<dyn Stream<Item = Result<Event>>>>,
+}
+
+impl EventSource {
+    pub(crate) fn new(client: Client, mut request: Request) -> Self {
+        let headers = request.headers_mut();
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("text/event-stream"),
+        );
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        let inner = async_stream::stream! {
+            let mut last_event_id: Option<String> = None;
+            let mut retry = DEFAULT_RETRY;
+            let mut next = Some(request);
+            while let Some(mut request) = next.take() {
+                next = request.try_clone();
+                if let Some(id) = last_event_id.as_deref().and_then(|id| HeaderValue::from_str(id).ok()) {
+                    request.headers_mut().insert("last-event-id", id);
+                }
+                match client.execute(request).await {
+                    Ok(res) => {
+                        if res.status() == StatusCode::NO_CONTENT {
+                            break;
+                        }
+                        if let Err(e) = check_response(&res) {
+                            yield Err(e);
+                            break;
+                        }
+                        let mut events = EventStream::new(res.body, last_event_id.take());
+                        while let Some(event) = events.next().await {
+                            yield event;
+                        }
+                        last_event_id = events.last_event_id().map(ToString::to_string);
+                        retry = events.reconnection_time().unwrap_or(retry);
+                    }
+                    Err(e) => yield Err(e),
+                }
+                if next.is_some() {
+                    compio::time::sleep(retry).await;
+                }
+            }
+        };
+        Self {
+            inner: Box::pin(inner),
+        }
+    }
+}
+
+impl std::fmt::Debug for EventSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventSource").finish_non_exhaustive()
+    }
+}
+
+impl Stream for EventSource {
+    type Item = Result<Event>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+fn check_response(res: &crate::Response) -> Result<()> {
+    if res.status() != StatusCode::OK {
+        return Err(crate::Error::EventSource(format!(
+            "unexpected status {}",
+            res.status()
+        )));
+    }
+    let is_event_stream = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+        .is_some_and(|mime| mime.essence_str() == "text/event-stream");
+    if !is_event_stream {
+        return Err(crate::Error::EventSource(
+            "unexpected content type".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(chunks: &[&[u8]]) -> Vec<Event> {
+        let mut parser = Parser::default();
+        let mut events = VecDeque::new();
+        for chunk in chunks {
+            parser.feed(chunk, &mut events);
+        }
+        events.into()
+    }
+
+    fn event(id: Option<&str>, event: &str, data: &str) -> Event {
+        Event {
+            id: id.map(ToString::to_string),
+            event: event.to_string(),
+            data: data.to_string(),
+            retry: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        let events = parse(&[
+            b": comment\n",
+            b"data: first\ndata:second\n\n",
+            b"event: update\nid: 1\ndata\n\n",
+            b"data: no id change\n\n",
+            b"id\nretry: 1500\ndata: reset\n\n",
+            b"unknown: field\nretry: 1s\n\n",
+            b"data: incomplete",
+        ]);
+        let mut retried = event(None, "message", "reset");
+        retried.retry = Some(Duration::from_millis(1500));
+        assert_eq!(
+            events,
+            [
+                event(None, "message", "first\nsecond"),
+                event(Some("1"), "update", ""),
+                event(Some("1"), "message", "no id change"),
+                retried,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_split_lines() {
+        let events = parse(&[
+            b"\xEF\xBB",
+            b"\xBFdata: a\r",
+            b"\ndata: b\r\r",
+            b"data: c\n",
+            b"\nid: 2\0\ndata: \xE4\xBD",
+            b"\xA0\n\n",
+        ]);
+        assert_eq!(
+            events,
+            [
+                event(None, "message", "a\nb"),
+                event(None, "message", "c"),
+                event(None, "message", "你"),
+            ]
+        );
+    }
+}
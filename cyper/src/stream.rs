@@ -3,21 +3,27 @@ use std::{
     net::SocketAddr,
     pin::Pin,
     task::{Context, Poll, ready},
+    time::Instant,
 };
 
 use compio::{
     BufResult,
     buf::{IoBuf, IoBufMut, IoVectoredBuf},
     io::{AsyncRead, AsyncWrite, util::Splittable},
-    net::TcpStream,
-    tls::TlsConnector,
+    net::{TcpStream, ToSocketAddrsAsync},
 };
 use cyper_core::HyperStream;
 use futures_util::StreamExt;
 use hyper::Uri;
 use hyper_util::client::legacy::connect::{Connected, Connection};
 
-use crate::{Error, Result, resolve::SharedResolver};
+#[cfg(feature = "http3")]
+use crate::connector::OpenGuard;
+use crate::{
+    ConnectionInfo, Error, Result, TlsConnector,
+    metrics::{ConnectionGuard, SharedMetrics},
+    resolve::SharedResolver,
+};
 
 /// A HTTP stream wrapper, based on compio, and exposes [`hyper::rt`]
 /// interfaces.
@@ -28,6 +34,10 @@ where
     inner: HyperStream<S>,
     is_proxy: bool,
     is_h2: bool,
+    info: ConnectionInfo,
+    guard: Option<ConnectionGuard>,
+    #[cfg(feature = "http3")]
+    open: Option<OpenGuard>,
 }
 
 impl HttpStream {
@@ -46,10 +56,11 @@ impl HttpStream {
             .and_then(|h| h.strip_suffix(']'))
             .unwrap_or(host);
         let port = uri.port_u16();
+        let mut info = ConnectionInfo::default();
         let stream = match scheme {
             "http" => {
                 let port = port.unwrap_or(80);
-                let stream = Self::connect_tcp(&uri, host, port, resolver).await?;
+                let stream = Self::connect_tcp(&uri, host, port, resolver, &mut info).await?;
                 // Ignore it.
                 let _tls = tls;
                 HyperStream::new_plain(stream)
@@ -57,21 +68,18 @@ impl HttpStream {
             #[cfg(tls)]
             "https" => {
                 let port = port.unwrap_or(443);
-                let stream = Self::connect_tcp(&uri, host, port, resolver).await?;
+                let stream = Self::connect_tcp(&uri, host, port, resolver, &mut info).await?;
                 let connector = tls.ok_or_else(|| Error::NoTlsBackend)?;
-                HyperStream::new_tls(connector.connect(host, stream).await?)
+                let start = Instant::now();
+                let (stream, tls_info) = connector.connect(host, stream).await?;
+                info.tls = tls_info;
+                info.handshaken = Some(Instant::now());
+                info.tls_handshake = Some(start.elapsed());
+                HyperStream::new_tls(stream)
             }
             _ => return Err(Error::BadScheme(scheme.to_string())),
         };
-        let is_h2 = stream
-            .negotiated_alpn()
-            .map(|alpn| *alpn == *b"h2")
-            .unwrap_or_default();
-        Ok(Self {
-            inner: stream,
-            is_proxy,
-            is_h2,
-        })
+        Ok(Self::new(stream, is_proxy, info))
     }
 
     async fn connect_tcp(
@@ -79,34 +87,44 @@ impl HttpStream {
         host: &str,
         port: u16,
         resolver: Option<SharedResolver>,
+        info: &mut ConnectionInfo,
     ) -> Result<TcpStream> {
-        let stream = match resolver {
-            None => TcpStream::connect((host, port)).await?,
+        let addrs = match resolver {
+            None => (host, port).to_socket_addrs_async().await?.collect(),
 
             Some(resolver) => {
-                let addrs = resolver
+                resolver
                     .resolve(uri)
                     .await?
                     .map(|ip| SocketAddr::new(ip, port))
                     .collect::<Vec<_>>()
-                    .await;
-
-                TcpStream::connect(addrs.as_slice()).await?
+                    .await
             }
         };
+        info.resolved = Some(Instant::now());
+        let stream = TcpStream::connect(addrs.as_slice()).await?;
+        info.connected = Some(Instant::now());
+        info.remote_addr = stream.peer_addr().ok();
+        info.local_addr = stream.local_addr().ok();
 
         Ok(stream)
     }
 
     pub fn into_wrapped(self) -> WrappedHttpStream {
-        WrappedHttpStream::Plain(self)
+        WrappedHttpStream::Plain(Box::new(self))
+    }
+
+    /// The details of the connection.
+    #[cfg(tls)]
+    pub(crate) fn info(&self) -> &ConnectionInfo {
+        &self.info
     }
 }
 
 #[cfg(tls)]
 impl HttpStream<HttpStream> {
     pub fn into_wrapped(self) -> WrappedHttpStream {
-        WrappedHttpStream::Embedded(self)
+        WrappedHttpStream::Embedded(Box::new(self))
     }
 }
 
@@ -116,10 +134,13 @@ where
     S::ReadHalf: AsyncRead + Unpin,
     S::WriteHalf: AsyncWrite + Unpin,
 {
+    /// Connect TLS over an established tunnel. The addresses in `info` are
+    /// the ones of the tunnel.
     pub async fn connect_with_https(
         stream: S,
         uri: Uri,
         tls: Option<TlsConnector>,
+        mut info: ConnectionInfo,
     ) -> Result<Self> {
         let host = uri.host().expect("there should be host");
         // `Uri::host()` includes brackets for IPv6, we must strip them.
@@ -128,16 +149,66 @@ where
             .and_then(|h| h.strip_suffix(']'))
             .unwrap_or(host);
         let connector = tls.ok_or_else(|| Error::NoTlsBackend)?;
-        let stream = HyperStream::new_tls(connector.connect(host, stream).await?);
-        let is_h2 = stream
-            .negotiated_alpn()
-            .map(|alpn| *alpn == *b"h2")
-            .unwrap_or_default();
-        Ok(Self {
+        let start = Instant::now();
+        let (stream, tls_info) = connector.connect(host, stream).await?;
+        info.tls = tls_info;
+        info.handshaken = Some(Instant::now());
+        info.tls_handshake = Some(start.elapsed());
+        Ok(Self::new(HyperStream::new_tls(stream), false, info))
+    }
+}
+
+impl<S: Splittable + 'static> HttpStream<S>
+where
+    S::ReadHalf: AsyncRead + Unpin,
+    S::WriteHalf: AsyncWrite + Unpin,
+{
+    /// Wrap the stream of a custom connector, with TLS for `https`.
+    pub(crate) async fn connect_custom(
+        stream: S,
+        uri: Uri,
+        tls: Option<TlsConnector>,
+    ) -> Result<Self> {
+        let info = ConnectionInfo {
+            connected: Some(Instant::now()),
+            ..Default::default()
+        };
+        match uri.scheme_str() {
+            #[cfg(tls)]
+            Some("https") => Self::connect_with_https(stream, uri, tls, info).await,
+            Some("http") => {
+                // Ignore it.
+                let _tls = tls;
+                Ok(Self::new(HyperStream::new_plain(stream), false, info))
+            }
+            scheme => Err(Error::BadScheme(scheme.unwrap_or_default().to_string())),
+        }
+    }
+
+    /// Notify the metrics of the connection to the host, until it is closed.
+    fn track(&mut self, host: &str, metrics: &SharedMetrics) {
+        self.guard = Some(metrics.open(host, &self.info));
+    }
+
+    /// Count the connection as open for the connector, until it is closed.
+    #[cfg(feature = "http3")]
+    fn hold(&mut self, guard: OpenGuard) {
+        self.open = Some(guard);
+    }
+
+    fn new(stream: HyperStream<S>, is_proxy: bool, mut info: ConnectionInfo) -> Self {
+        info.alpn = stream.negotiated_alpn().map(|alpn| alpn.into_owned());
+        info.ready = Some(Instant::now());
+        let is_h2 = info.alpn.as_deref() == Some(b"h2");
+        Self {
             inner: stream,
-            is_proxy: false,
+            is_proxy,
             is_h2,
-        })
+            info,
+            guard: None,
+            #[cfg(feature = "http3")]
+            open: None,
+        }
     }
 }
 
@@ -205,7 +276,9 @@ where
     S::WriteHalf: AsyncWrite + Unpin,
 {
     fn connected(&self) -> Connected {
-        let conn = Connected::new().proxy(self.is_proxy);
+        let conn = Connected::new()
+            .proxy(self.is_proxy)
+            .extra(self.info.clone());
         if self.is_h2 {
             conn.negotiated_h2()
         } else {
@@ -272,10 +345,59 @@ where
     }
 }
 
+/// The stream of a custom connector, with the type erased.
+pub(crate) trait CustomStream:
+    hyper::rt::Read + hyper::rt::Write + Connection + Send + Unpin
+{
+    fn track(&mut self, host: &str, metrics: &SharedMetrics);
+
+    #[cfg(feature = "http3")]
+    fn hold(&mut self, guard: OpenGuard);
+}
+
+impl<S: Splittable + Unpin + 'static> CustomStream for HttpStream<S>
+where
+    S::ReadHalf: AsyncRead + Unpin,
+    S::WriteHalf: AsyncWrite + Unpin,
+{
+    fn track(&mut self, host: &str, metrics: &SharedMetrics) {
+        HttpStream::track(self, host, metrics);
+    }
+
+    #[cfg(feature = "http3")]
+    fn hold(&mut self, guard: OpenGuard) {
+        HttpStream::hold(self, guard);
+    }
+}
+
 pub enum WrappedHttpStream {
-    Plain(HttpStream),
+    Plain(Box<HttpStream>),
     #[cfg(tls)]
-    Embedded(HttpStream<HttpStream>),
+    Embedded(Box<HttpStream<HttpStream>>),
+    Custom(Box<dyn CustomStream>),
+}
+
+impl WrappedHttpStream {
+    /// Notify the metrics of the connection to the host, until it is closed.
+    pub(crate) fn track(&mut self, host: &str, metrics: &SharedMetrics) {
+        match self {
+            WrappedHttpStream::Plain(s) => s.track(host, metrics),
+            #[cfg(tls)]
+            WrappedHttpStream::Embedded(s) => s.track(host, metrics),
+            WrappedHttpStream::Custom(s) => s.track(host, metrics),
+        }
+    }
+
+    /// Count the connection as open for the connector, until it is closed.
+    #[cfg(feature = "http3")]
+    pub(crate) fn hold(&mut self, guard: OpenGuard) {
+        match self {
+            WrappedHttpStream::Plain(s) => s.hold(guard),
+            #[cfg(tls)]
+            WrappedHttpStream::Embedded(s) => s.hold(guard),
+            WrappedHttpStream::Custom(s) => s.hold(guard),
+        }
+    }
 }
 
 impl hyper::rt::Read for WrappedHttpStream {
@@ -288,6 +410,7 @@ impl hyper::rt::Read for WrappedHttpStream {
             WrappedHttpStream::Plain(s) => Pin::new(s).poll_read(cx, buf),
             #[cfg(tls)]
             WrappedHttpStream::Embedded(s) => Pin::new(s).poll_read(cx, buf),
+            WrappedHttpStream::Custom(s) => Pin::new(s).poll_read(cx, buf),
         }
     }
 }
@@ -302,6 +425,7 @@ impl hyper::rt::Write for WrappedHttpStream {
             WrappedHttpStream::Plain(s) => Pin::new(s).poll_write(cx, buf),
             #[cfg(tls)]
             WrappedHttpStream::Embedded(s) => Pin::new(s).poll_write(cx, buf),
+            WrappedHttpStream::Custom(s) => Pin::new(s).poll_write(cx, buf),
         }
     }
 
@@ -314,6 +438,7 @@ impl hyper::rt::Write for WrappedHttpStream {
             WrappedHttpStream::Plain(s) => Pin::new(s).poll_write_vectored(cx, bufs),
             #[cfg(tls)]
             WrappedHttpStream::Embedded(s) => Pin::new(s).poll_write_vectored(cx, bufs),
+            WrappedHttpStream::Custom(s) => Pin::new(s).poll_write_vectored(cx, bufs),
         }
     }
 
@@ -322,6 +447,7 @@ impl hyper::rt::Write for WrappedHttpStream {
             WrappedHttpStream::Plain(s) => s.is_write_vectored(),
             #[cfg(tls)]
             WrappedHttpStream::Embedded(s) => s.is_write_vectored(),
+            WrappedHttpStream::Custom(s) => s.is_write_vectored(),
         }
     }
 
@@ -330,6 +456,7 @@ impl hyper::rt::Write for WrappedHttpStream {
             WrappedHttpStream::Plain(s) => Pin::new(s).poll_flush(cx),
             #[cfg(tls)]
             WrappedHttpStream::Embedded(s) => Pin::new(s).poll_flush(cx),
+            WrappedHttpStream::Custom(s) => Pin::new(s).poll_flush(cx),
         }
     }
 
@@ -338,6 +465,7 @@ impl hyper::rt::Write for WrappedHttpStream {
             WrappedHttpStream::Plain(s) => Pin::new(s).poll_shutdown(cx),
             #[cfg(tls)]
             WrappedHttpStream::Embedded(s) => Pin::new(s).poll_shutdown(cx),
+            WrappedHttpStream::Custom(s) => Pin::new(s).poll_shutdown(cx),
         }
     }
 }
@@ -348,6 +476,7 @@ impl Connection for WrappedHttpStream {
             WrappedHttpStream::Plain(s) => s.connected(),
             #[cfg(tls)]
             WrappedHttpStream::Embedded(s) => s.connected(),
+            WrappedHttpStream::Custom(s) => s.connected(),
         }
     }
 }
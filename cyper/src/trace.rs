@@ -0,0 +1,306 @@
+//! Tracing spans and W3C trace context propagation.
+//!
+//! With the `tracing` feature, each [`Client::execute`](crate::Client::execute)
+//! runs in an `INFO` span named `HTTP`, with the fields of the OpenTelemetry
+//! semantic conventions for HTTP clients:
+//!
+//! - `otel.name`, `otel.kind` and `otel.status_code`
+//! - `http.request.method`, `url.full`, `server.address` and `server.port`
+//! - `http.response.status_code`, `network.protocol.name` and
+//!   `network.protocol.version`
+//! - `network.peer.address` and `network.peer.port`
+//! - `http.request.resend_count`, `cyper.redirect_count` and
+//!   `cyper.retry_count`
+//! - `error.type`
+//!
+//! The `traceparent` and `tracestate` headers are injected from the
+//! [`TraceContext`] returned by the function set with
+//! [`ClientBuilder::trace_context`](crate::ClientBuilder::trace_context),
+//! which is called in the span. It could be bridged to OpenTelemetry with
+//! `tracing-opentelemetry`, from the context of `Span::current()`.
+
+use std::{fmt::Debug, sync::Arc};
+
+use http::{HeaderMap, HeaderName, HeaderValue};
+use tracing::{Span, field::Empty};
+use url::Url;
+
+use crate::{Request, Response, Result};
+
+const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+const TRACESTATE: HeaderName = HeaderName::from_static("tracestate");
+
+/// A W3C trace context, sent in the `traceparent` and `tracestate` headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: u128,
+    parent_id: u64,
+    flags: u8,
+    state: Option<HeaderValue>,
+}
+
+impl TraceContext {
+    /// Creates a trace context with the trace ID, and the span ID of the
+    /// parent. Returns `None` if any of them is zero, which is invalid.
+    pub fn new(trace_id: u128, parent_id: u64, sampled: bool) -> Option<Self> {
+        (trace_id != 0 && parent_id != 0).then_some(Self {
+            trace_id,
+            parent_id,
+            flags: sampled as u8,
+            state: None,
+        })
+    }
+
+    /// Set the vendor-specific `tracestate`.
+    pub fn state(mut self, state: HeaderValue) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Parses the `traceparent` and `tracestate` headers, e.g. of an incoming
+    /// request to propagate.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let mut context = Self::parse(headers.get(TRACEPARENT)?.to_str().ok()?)?;
+        context.state = headers.get(TRACESTATE).cloned();
+        Some(context)
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        fn hex<const N: usize>(s: &str) -> Option<&str> {
+            (s.len() == N && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))).then_some(s)
+        }
+
+        let mut parts = value.trim().split('-');
+        let version = hex::<2>(parts.next()?)?;
+        let trace_id = u128::from_str_radix(hex::<32>(parts.next()?)?, 16).ok()?;
+        let parent_id = u64::from_str_radix(hex::<16>(parts.next()?)?, 16).ok()?;
+        let flags = u8::from_str_radix(hex::<2>(parts.next()?)?, 16).ok()?;
+        // Future versions may append fields.
+        if version == "ff" || (version == "00" && parts.next().is_some()) {
+            return None;
+        }
+        let mut context = Self::new(trace_id, parent_id, false)?;
+        context.flags = flags;
+        Some(context)
+    }
+
+    /// The trace ID.
+    pub fn trace_id(&self) -> u128 {
+        self.trace_id
+    }
+
+    /// The span ID of the parent.
+    pub fn parent_id(&self) -> u64 {
+        self.parent_id
+    }
+
+    /// Whether the trace is sampled.
+    pub fn sampled(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    /// The `traceparent` header value.
+    pub fn traceparent(&self) -> HeaderValue {
+        HeaderValue::try_from(format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id, self.parent_id, self.flags
+        ))
+        .expect("the value should be valid")
+    }
+
+    /// Inserts the headers, unless the request already carries a trace
+    /// context.
+    fn inject(self, headers: &mut HeaderMap) {
+        if headers.contains_key(TRACEPARENT) {
+            return;
+        }
+        headers.insert(TRACEPARENT, self.traceparent());
+        match self.state {
+            Some(state) => headers.insert(TRACESTATE, state),
+            None => headers.remove(TRACESTATE),
+        };
+    }
+}
+
+/// The function providing the trace context of the current span.
+#[derive(Clone)]
+pub(crate) struct Propagator(Arc<dyn Fn() -> Option<TraceContext> + Send + Sync>);
+
+impl Propagator {
+    pub(crate) fn new(f: impl Fn() -> Option<TraceContext> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn inject(&self, headers: &mut HeaderMap) {
+        if let Some(context) = (self.0)() {
+            context.inject(headers);
+        }
+    }
+}
+
+impl Debug for Propagator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Propagator").finish_non_exhaustive()
+    }
+}
+
+/// The span of [`Client::execute`](crate::Client::execute).
+pub(crate) fn span(request: &Request) -> Span {
+    let method = request.method().as_str();
+    let url = request.url();
+    tracing::info_span!(
+        "HTTP",
+        otel.name = method,
+        otel.kind = "client",
+        otel.status_code = Empty,
+        http.request.method = method,
+        url.full = %redact(url),
+        server.address = url.host_str(),
+        server.port = url.port_or_known_default(),
+        http.response.status_code = Empty,
+        network.protocol.name = "http",
+        network.protocol.version = Empty,
+        network.peer.address = Empty,
+        network.peer.port = Empty,
+        http.request.resend_count = Empty,
+        cyper.redirect_count = Empty,
+        cyper.retry_count = Empty,
+        error.type = Empty,
+    )
+}
+
+/// Records the result of [`Client::execute`](crate::Client::execute).
+pub(crate) fn record(span: &Span, res: &Result<Response>) {
+    match res {
+        Ok(res) => {
+            let status = res.status();
+            span.record("http.response.status_code", status.as_u16());
+            let version = match res.version() {
+                http::Version::HTTP_09 => "0.9",
+                http::Version::HTTP_10 => "1.0",
+                http::Version::HTTP_11 => "1.1",
+                http::Version::HTTP_2 => "2",
+                http::Version::HTTP_3 => "3",
+                _ => "",
+            };
+            span.record("network.protocol.version", version);
+            if let Some(addr) = res.remote_addr() {
+                span.record("network.peer.address", addr.ip().to_string());
+                span.record("network.peer.port", addr.port());
+            }
+            let redirects = res.redirect_history().len() as u32;
+            let resends = redirects + res.retries;
+            if resends > 0 {
+                span.record("http.request.resend_count", resends);
+                span.record("cyper.redirect_count", redirects);
+                span.record("cyper.retry_count", res.retries);
+            }
+            if status.is_client_error() || status.is_server_error() {
+                span.record("otel.status_code", "ERROR");
+                span.record("error.type", status.as_str());
+            }
+        }
+        Err(e) => {
+            span.record("otel.status_code", "ERROR");
+            span.record("error.type", error_type(e));
+        }
+    }
+}
+
+/// The URL without the credentials.
+fn redact(url: &Url) -> Url {
+    let mut url = url.clone();
+    if !url.username().is_empty() || url.password().is_some() {
+        let _ = url.set_username("REDACTED");
+        let _ = url.set_password(Some("REDACTED"));
+    }
+    url
+}
+
+/// The name of the error variant.
+fn error_type(e: &crate::Error) -> &'static str {
+    use crate::Error;
+
+    match e {
+        Error::Timeout => "Timeout",
+        Error::NoTlsBackend => "NoTlsBackend",
+        Error::InvalidUrl(_) => "InvalidUrl",
+        Error::BadScheme(_) => "BadScheme",
+        Error::System(_) => "System",
+        Error::Http(_) => "Http",
+        Error::Hyper(_) => "Hyper",
+        Error::HyperClient(_) => "HyperClient",
+        Error::UrlParse(_) => "UrlParse",
+        Error::UrlEncoded(_) => "UrlEncoded",
+        #[cfg(feature = "json")]
+        Error::Json(_) => "Json",
+        #[cfg(feature = "native-tls")]
+        Error::NativeTls(_) => "NativeTls",
+        #[cfg(any(feature = "http3", feature = "rustls"))]
+        Error::Rustls(_) => "Rustls",
+        #[cfg(feature = "http3")]
+        Error::H3Connection(_) => "H3Connection",
+        #[cfg(feature = "http3")]
+        Error::H3Stream(_) => "H3Stream",
+        #[cfg(feature = "http3")]
+        Error::H3Client(_) => "H3Client",
+        #[cfg(feature = "http3")]
+        Error::H3Shared(e) => error_type(e),
+        #[cfg(feature = "http3")]
+        Error::QuicConnect(_) => "QuicConnect",
+        #[cfg(feature = "http3")]
+        Error::QuicConnection(_) => "QuicConnection",
+        Error::Redirect(_) => "Redirect",
+        Error::Proxy(_) => "Proxy",
+        Error::Auth(_) => "Auth",
+        #[cfg(feature = "cookies")]
+        Error::Cookie(_) => "Cookie",
+        #[cfg(feature = "ws")]
+        Error::WebSocket(_) => "WebSocket",
+        #[cfg(feature = "sse")]
+        Error::EventSource(_) => "EventSource",
+        #[cfg(feature = "signatures")]
+        Error::Signature(_) => "Signature",
+        #[cfg(feature = "vcr")]
+        Error::Cassette(_) => "Cassette",
+        #[cfg(feature = "hickory-dns")]
+        Error::Hickory(_) => "Hickory",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traceparent() {
+        let value = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let context = TraceContext::parse(value).unwrap();
+        assert_eq!(context.trace_id(), 0x4bf92f3577b34da6a3ce929d0e0e4736);
+        assert_eq!(context.parent_id(), 0x00f067aa0ba902b7);
+        assert!(context.sampled());
+        assert_eq!(context.traceparent(), value);
+
+        // Future versions may append fields.
+        assert!(TraceContext::parse(&format!("cc{}-extra", &value[2..])).is_some());
+        for invalid in [
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+        ] {
+            assert!(TraceContext::parse(invalid).is_none(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_error_type() {
+        assert_eq!(error_type(&crate::Error::Timeout), "Timeout");
+        assert_eq!(
+            error_type(&crate::Error::BadScheme("ftp".into())),
+            "BadScheme"
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use compio::{
+    BufResult,
+    buf::{IoBuf, IoBufMut, IoVectoredBuf},
+    io::{AsyncRead, AsyncWrite},
+};
+
+/// An upgraded connection, taken over from a `101 Switching Protocols`
+/// response, or a successful response to a `CONNECT` request.
+///
+/// It implements the compio [`AsyncRead`] and [`AsyncWrite`], the
+/// [`futures_util`] IO traits, and the [`hyper::rt`] IO traits. Writes may
+/// be buffered, so remember to flush the stream.
+pub struct Upgraded {
+    inner: hyper::upgrade::Upgraded,
+}
+
+impl Upgraded {
+    pub(crate) fn new(inner: hyper::upgrade::Upgraded) -> Self {
+        Self { inner }
+    }
+
+    /// Get the inner [`hyper::upgrade::Upgraded`].
+    pub fn into_inner(self) -> hyper::upgrade::Upgraded {
+        self.inner
+    }
+}
+
+impl std::fmt::Debug for Upgraded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Upgraded").finish_non_exhaustive()
+    }
+}
+
+impl hyper::rt::Read for Upgraded {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: hyper::rt::ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl hyper::rt::Write for Upgraded {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+}
+
+impl futures_util::AsyncRead for Upgraded {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut read_buf = hyper::rt::ReadBuf::new(buf);
+        ready!(hyper::rt::Read::poll_read(
+            Pin::new(&mut self.inner),
+            cx,
+            read_buf.unfilled()
+        ))?;
+        Poll::Ready(Ok(read_buf.filled().len()))
+    }
+}
+
+impl futures_util::AsyncWrite for Upgraded {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        hyper::rt::Write::poll_write(Pin::new(&mut self.inner), cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        hyper::rt::Write::poll_write_vectored(Pin::new(&mut self.inner), cx, bufs)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        hyper::rt::Write::poll_flush(Pin::new(&mut self.inner), cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        hyper::rt::Write::poll_shutdown(Pin::new(&mut self.inner), cx)
+    }
+}
+
+impl AsyncRead for Upgraded {
+    async fn read<B: IoBufMut>(&mut self, mut buf: B) -> BufResult<usize, B> {
+        let res = futures_util::AsyncReadExt::read(self, buf.ensure_init()).await;
+        if let Ok(len) = &res {
+            unsafe { buf.set_len(*len) };
+        }
+        BufResult(res, buf)
+    }
+}
+
+impl AsyncWrite for Upgraded {
+    async fn write<T: IoBuf>(&mut self, buf: T) -> BufResult<usize, T> {
+        let res = futures_util::AsyncWriteExt::write(self, buf.as_init()).await;
+        BufResult(res, buf)
+    }
+
+    async fn write_vectored<T: IoVectoredBuf>(&mut self, buf: T) -> BufResult<usize, T> {
+        let slices = buf.iter_slice().map(io::IoSlice::new).collect::<Vec<_>>();
+        let res = futures_util::AsyncWriteExt::write_vectored(self, &slices).await;
+        BufResult(res, buf)
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        futures_util::AsyncWriteExt::flush(self).await
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        futures_util::AsyncWriteExt::close(self).await
+    }
+}
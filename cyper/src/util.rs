@@ -1,5 +1,7 @@
 //! Code from cyper_core.
 
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use hyper::{
     HeaderMap,
     header::{Entry, HeaderValue, OccupiedEntry},
@@ -27,6 +29,27 @@ where
     header
 }
 
+/// Clone the request if the body is clonable. Extensions are not cloned.
+#[cfg(feature = "http3")]
+pub(crate) fn try_clone_request(
+    req: &http::Request<crate::Body>,
+) -> Option<http::Request<crate::Body>> {
+    let body = req.body().try_clone()?;
+    let mut clone = http::Request::new(body);
+    *clone.method_mut() = req.method().clone();
+    *clone.uri_mut() = req.uri().clone();
+    *clone.version_mut() = req.version();
+    *clone.headers_mut() = req.headers().clone();
+    Some(clone)
+}
+
+/// Check if the request takes over the connection, which isn't supported
+/// over HTTP/3.
+#[cfg(any(feature = "http3", feature = "vcr"))]
+pub(crate) fn is_upgrade<B>(req: &http::Request<B>) -> bool {
+    req.method() == http::Method::CONNECT || req.headers().contains_key(http::header::UPGRADE)
+}
+
 pub(crate) fn replace_headers(dst: &mut HeaderMap, src: HeaderMap) {
     // IntoIter of HeaderMap yields (Option<HeaderName>, HeaderValue).
     // The first time a name is yielded, it will be Some(name), and if
@@ -80,3 +103,98 @@ pub(crate) fn fast_random() -> u64 {
         })
     })
 }
+
+/// Formats the time as `YYYYMMDD HH:MM:SS` in UTC.
+pub(crate) fn format_expiry(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (days, secs) = (secs / 86400, secs % 86400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{year:04}{month:02}{day:02} {:02}:{:02}:{:02}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// Formats the time as `YYYY-MM-DDTHH:MM:SS.sssZ` in UTC.
+#[cfg(feature = "har")]
+pub(crate) fn format_rfc3339(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs();
+    let (days, secs) = (secs / 86400, secs % 86400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+        since.subsec_millis()
+    )
+}
+
+pub(crate) fn parse_expiry(s: &str) -> Option<SystemTime> {
+    let (date, time) = s.split_once(' ')?;
+    if date.len() != 8 || !date.is_ascii() {
+        return None;
+    }
+    let year = date[..4].parse().ok()?;
+    let month = date[4..6].parse().ok()?;
+    let day = date[6..].parse().ok()?;
+    let mut hms = time.splitn(3, ':').map(|s| s.parse::<u64>().ok());
+    let (h, m, s) = (hms.next()??, hms.next()??, hms.next()??);
+    if h > 23 || m > 59 || s > 60 {
+        return None;
+    }
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    let secs = days
+        .checked_mul(86400)?
+        .checked_add(h * 3600 + m * 60 + s)?;
+    UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
+
+// Conversion between days since the Unix epoch and the proleptic Gregorian
+// calendar, from http://howardhinnant.github.io/date_algorithms.html
+
+fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from((month + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[test]
+fn test_expiry_format() {
+    let time = SystemTime::UNIX_EPOCH + Duration::from_secs(951_782_400 + 3723);
+    assert_eq!(format_expiry(time), "20000229 01:02:03");
+    assert_eq!(parse_expiry("20000229 01:02:03"), Some(time));
+    assert_eq!(
+        parse_expiry("19700101 00:00:00"),
+        Some(SystemTime::UNIX_EPOCH)
+    );
+    // Malformed times are rejected instead of overflowing.
+    assert_eq!(parse_expiry("20000101 99999999999999999:0:0"), None);
+    assert_eq!(parse_expiry("20000101 24:00:00"), None);
+    assert_eq!(parse_expiry("20000101 00:60:00"), None);
+    assert_eq!(parse_expiry("20000101 00:00:61"), None);
+    assert_eq!(parse_expiry("20000101 00:00"), None);
+}
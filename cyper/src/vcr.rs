@@ -0,0 +1,368 @@
+//! Record and replay the HTTP exchanges, for deterministic tests.
+//!
+//! A [`Cassette`] set with
+//! [`ClientBuilder::cassette`](crate::ClientBuilder::cassette) records the
+//! exchanges with the servers, which could be saved to a JSON file, and
+//! replays them later without touching the network. The requests are matched
+//! by the method, the URL and the body.
+//!
+//! The recorded bodies are read as a whole, and the decompressed response
+//! bodies are recorded. The upgrades, e.g. WebSocket, are not recorded.
+
+use std::{future::Future, path::Path};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use compio::bytes::Bytes;
+use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Version};
+use http_body_util::BodyExt;
+use serde_json::{Value, json};
+use url::Url;
+
+use crate::{
+    Body, Error, Response, ResponseBody, Result,
+    sync::{mutex_blocking::Mutex, shared::Shared},
+};
+
+/// How a [`Cassette`] handles the requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Replays the recorded exchanges, and fails the requests not recorded.
+    /// The network is never touched.
+    #[default]
+    Replay,
+    /// Sends all requests to the network, and records the exchanges.
+    Record,
+    /// Replays the recorded exchanges, and sends the requests not recorded
+    /// to the network, recording them.
+    ReplayOrRecord,
+}
+
+#[derive(Debug)]
+struct Interaction {
+    method: Method,
+    url: String,
+    request_body: Bytes,
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+    body: Bytes,
+    /// Whether the exchange is replayed.
+    played: bool,
+}
+
+/// A recording of HTTP exchanges.
+///
+/// The exchanges are shared between the clones. When the same request is
+/// recorded multiple times, the exchanges are replayed in order, and the
+/// last one is repeated.
+///
+/// ```no_run
+/// use cyper::{
+///     Client,
+///     vcr::{Cassette, Mode},
+/// };
+///
+/// # async fn run() -> cyper::Result<()> {
+/// let mode = if std::env::var_os("CI").is_some() {
+///     Mode::Replay
+/// } else {
+///     Mode::ReplayOrRecord
+/// };
+/// let cassette = Cassette::load("tests/cassettes/example.json")
+///     .await?
+///     .mode(mode);
+/// let client = Client::builder().cassette(cassette.clone()).build()?;
+/// client
+///     .get("https://example.com")?
+///     .send()
+///     .await?
+///     .text()
+///     .await?;
+/// cassette.save("tests/cassettes/example.json").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cassette {
+    interactions: Shared<Mutex<Vec<Interaction>>>,
+    mode: Mode,
+}
+
+impl Default for Cassette {
+    fn default() -> Self {
+        Self {
+            interactions: Shared::new(Mutex::new(Vec::new())),
+            mode: Mode::default(),
+        }
+    }
+}
+
+impl Cassette {
+    /// Creates an empty cassette.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how the requests are handled.
+    ///
+    /// Default is [`Mode::Replay`].
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Loads the cassette from a file. A missing file results in an empty
+    /// cassette.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        match compio::fs::read(path).await {
+            Ok(content) => Self::from_json(&String::from_utf8_lossy(&content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Saves the cassette to a file.
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        compio::fs::write(path, self.to_json()).await.0?;
+        Ok(())
+    }
+
+    /// Parses the cassette from JSON.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: Value =
+            serde_json::from_str(json).map_err(|e| Error::Cassette(e.to_string()))?;
+        let interactions = value["interactions"]
+            .as_array()
+            .ok_or_else(|| Error::Cassette("missing interactions".into()))?
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                Interaction::parse(value)
+                    .ok_or_else(|| Error::Cassette(format!("malformed interaction {i}")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            interactions: Shared::new(Mutex::new(interactions)),
+            mode: Mode::default(),
+        })
+    }
+
+    /// Exports the cassette as JSON.
+    pub fn to_json(&self) -> String {
+        let interactions = self
+            .interactions
+            .lock()
+            .iter()
+            .map(Interaction::export)
+            .collect::<Vec<_>>();
+        serde_json::to_string_pretty(&json!({ "interactions": interactions }))
+            .expect("the cassette should be serializable")
+    }
+
+    /// The count of the recorded exchanges.
+    pub fn len(&self) -> usize {
+        self.interactions.lock().len()
+    }
+
+    /// Whether no exchange is recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all exchanges.
+    pub fn clear(&self) {
+        self.interactions.lock().clear();
+    }
+
+    /// Replays the response of the request, or sends it with `send` and
+    /// records the exchange.
+    pub(crate) async fn play<F: Future<Output = Result<Response>>>(
+        &self,
+        mut request: http::Request<Body>,
+        url: &Url,
+        send: impl FnOnce(http::Request<Body>) -> F,
+    ) -> Result<Response> {
+        let collected = std::mem::take(request.body_mut()).collect().await?;
+        let trailers = collected.trailers().cloned();
+        let request_body = collected.to_bytes();
+
+        if self.mode != Mode::Record
+            && let Some(res) = self.replay(request.method(), url, &request_body)
+        {
+            return Ok(res);
+        }
+        if self.mode == Mode::Replay {
+            return Err(Error::Cassette(format!(
+                "no recorded response for {} {url}",
+                request.method()
+            )));
+        }
+
+        let method = request.method().clone();
+        let mut body = Body::from(request_body.clone());
+        if let Some(trailers) = trailers {
+            body = body.with_trailers(trailers);
+        }
+        *request.body_mut() = body;
+        let mut res = send(request).await?;
+
+        let collected = std::mem::replace(&mut res.body, ResponseBody::Blob(None, None))
+            .collect()
+            .await?;
+        let trailers = collected.trailers().cloned();
+        let body = collected.to_bytes();
+        res.body = ResponseBody::Blob(Some(Ok(body.clone())), trailers);
+        self.interactions.lock().push(Interaction {
+            method,
+            url: url.to_string(),
+            request_body,
+            status: res.status(),
+            version: res.version(),
+            headers: res.headers().clone(),
+            body,
+            played: false,
+        });
+        Ok(res)
+    }
+
+    fn replay(&self, method: &Method, url: &Url, body: &Bytes) -> Option<Response> {
+        let mut interactions = self.interactions.lock();
+        let matches = |interaction: &Interaction| {
+            interaction.method == method
+                && interaction.url == url.as_str()
+                && interaction.request_body == body
+        };
+        // Repeat the last one when all are played.
+        let index = interactions
+            .iter()
+            .position(|interaction| !interaction.played && matches(interaction))
+            .or_else(|| interactions.iter().rposition(matches))?;
+        let interaction = &mut interactions[index];
+        interaction.played = true;
+
+        let mut res = http::Response::new(());
+        *res.status_mut() = interaction.status;
+        *res.version_mut() = interaction.version;
+        *res.headers_mut() = interaction.headers.clone();
+        Some(Response::with_body(
+            res,
+            interaction.body.clone(),
+            None,
+            url.clone(),
+        ))
+    }
+}
+
+impl Interaction {
+    fn parse(value: &Value) -> Option<Self> {
+        let request = &value["request"];
+        let response = &value["response"];
+        let headers = response["headers"]
+            .as_array()?
+            .iter()
+            .map(|header| {
+                let name = HeaderName::try_from(header[0].as_str()?).ok()?;
+                let value = HeaderValue::try_from(header[1].as_str()?).ok()?;
+                Some((name, value))
+            })
+            .collect::<Option<HeaderMap>>()?;
+        Some(Self {
+            method: Method::try_from(request["method"].as_str()?).ok()?,
+            url: request["url"].as_str()?.to_string(),
+            request_body: parse_body(request)?,
+            status: StatusCode::from_u16(u16::try_from(response["status"].as_u64()?).ok()?).ok()?,
+            version: match response["version"].as_str()? {
+                "HTTP/0.9" => Version::HTTP_09,
+                "HTTP/1.0" => Version::HTTP_10,
+                "HTTP/1.1" => Version::HTTP_11,
+                "HTTP/2.0" => Version::HTTP_2,
+                "HTTP/3.0" => Version::HTTP_3,
+                _ => return None,
+            },
+            headers,
+            body: parse_body(response)?,
+            played: false,
+        })
+    }
+
+    fn export(&self) -> Value {
+        let mut request = json!({ "method": self.method.as_str(), "url": self.url });
+        export_body(&mut request, &self.request_body);
+        let mut response = json!({
+            "status": self.status.as_u16(),
+            "version": format!("{:?}", self.version),
+            "headers": self
+                .headers
+                .iter()
+                .map(|(name, value)| {
+                    json!([name.as_str(), String::from_utf8_lossy(value.as_bytes())])
+                })
+                .collect::<Vec<_>>(),
+        });
+        export_body(&mut response, &self.body);
+        json!({ "request": request, "response": response })
+    }
+}
+
+/// The body is written as text if it is UTF-8, otherwise base64.
+fn export_body(value: &mut Value, body: &Bytes) {
+    match std::str::from_utf8(body) {
+        Ok(text) => value["body"] = text.into(),
+        Err(_) => {
+            value["body"] = BASE64_STANDARD.encode(body).into();
+            value["encoding"] = "base64".into();
+        }
+    }
+}
+
+fn parse_body(value: &Value) -> Option<Bytes> {
+    let body = match &value["body"] {
+        Value::Null => return Some(Bytes::new()),
+        body => body.as_str()?,
+    };
+    match value["encoding"].as_str() {
+        None => Some(Bytes::copy_from_slice(body.as_bytes())),
+        Some("base64") => BASE64_STANDARD.decode(body).ok().map(Bytes::from),
+        Some(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let json = r#"{
+            "interactions": [
+                {
+                    "request": { "method": "POST", "url": "http://example.com/", "body": "hello" },
+                    "response": {
+                        "status": 201,
+                        "version": "HTTP/1.1",
+                        "headers": [["set-cookie", "a=1"], ["set-cookie", "b=2"]],
+                        "body": "/wA=",
+                        "encoding": "base64"
+                    }
+                }
+            ]
+        }"#;
+        let cassette = Cassette::from_json(json).unwrap();
+        assert_eq!(cassette.len(), 1);
+        {
+            let interactions = cassette.interactions.lock();
+            let interaction = &interactions[0];
+            assert_eq!(interaction.method, Method::POST);
+            assert_eq!(interaction.request_body, "hello");
+            assert_eq!(interaction.status, StatusCode::CREATED);
+            assert_eq!(interaction.headers.get_all("set-cookie").iter().count(), 2);
+            assert_eq!(interaction.body, [0xff, 0].as_slice());
+        }
+
+        let exported = Cassette::from_json(&cassette.to_json()).unwrap();
+        assert_eq!(exported.to_json(), cassette.to_json());
+
+        assert!(Cassette::from_json("{}").is_err());
+        assert!(Cassette::from_json(r#"{ "interactions": [{}] }"#).is_err());
+    }
+}
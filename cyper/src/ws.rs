@@ -0,0 +1,276 @@
+//! WebSocket client.
+//!
+//! The handshake is sent through the [`Client`], so the proxies, TLS
+//! settings, cookies, resolver, authentication, metrics and tracing of the
+//! client all apply. WebSockets are opened with an HTTP/1.1 upgrade, or with an
+//! extended CONNECT (RFC 8441) when the connection is HTTP/2.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use cyper::ws::Message;
+//!
+//! # async fn run() -> cyper::Result<()> {
+//! let client = cyper::Client::new()?;
+//! let mut ws = client.get("wss://echo.websocket.org")?.websocket().await?;
+//! ws.send(Message::text("hello")).await?;
+//! while let Some(msg) = ws.recv().await {
+//!     println!("{:?}", msg?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_tungstenite::{
+    WebSocketStream,
+    tungstenite::{
+        self as ts,
+        error::{ProtocolError, SubProtocolError},
+        handshake::{client::generate_key, derive_accept_key},
+        protocol::Role,
+    },
+};
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use http::{HeaderMap, HeaderValue, Method, StatusCode, Version, header};
+// Re-export tungstenite types for convenience.
+pub use ts::protocol::frame::coding::CloseCode;
+pub use ts::{Bytes, Message, Utf8Bytes, protocol::CloseFrame};
+
+use crate::{Body, Client, Request, Response, Result, Upgraded};
+
+/// A WebSocket stream.
+///
+/// Use [`recv`](Self::recv) and [`send`](Self::send) to communicate.
+pub struct WebSocket {
+    inner: WebSocketStream<Upgraded>,
+    protocol: Option<HeaderValue>,
+}
+
+impl std::fmt::Debug for WebSocket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebSocket")
+            .field("protocol", &self.protocol)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WebSocket {
+    /// Receive another message.
+    ///
+    /// Returns `None` if the stream has closed.
+    pub async fn recv(&mut self) -> Option<Result<Message>> {
+        self.inner.next().await.map(|res| res.map_err(Into::into))
+    }
+
+    /// Send a message.
+    pub async fn send(&mut self, msg: Message) -> Result<()> {
+        Ok(self.inner.send(msg).await?)
+    }
+
+    /// Close the WebSocket connection.
+    pub async fn close(mut self, close_frame: Option<CloseFrame>) -> Result<()> {
+        Ok(self.inner.close(close_frame).await?)
+    }
+
+    /// Return the subprotocol selected by the server, if any.
+    pub fn protocol(&self) -> Option<&HeaderValue> {
+        self.protocol.as_ref()
+    }
+}
+
+impl Stream for WebSocket {
+    type Item = Result<Message>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner
+            .poll_next_unpin(cx)
+            .map(|msg| msg.map(|res| res.map_err(Into::into)))
+    }
+}
+
+impl Sink<Message> for WebSocket {
+    type Error = crate::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.inner.poll_ready_unpin(cx).map_err(Into::into)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Message) -> Result<()> {
+        Ok(self.inner.start_send_unpin(item)?)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.inner.poll_flush_unpin(cx).map_err(Into::into)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.inner.poll_close_unpin(cx).map_err(Into::into)
+    }
+}
+
+/// Open a WebSocket with the method and body of the request ignored.
+pub(crate) async fn connect(client: Client, mut request: Request) -> Result<WebSocket> {
+    let scheme = match request.url().scheme() {
+        "ws" => Some("http"),
+        "wss" => Some("https"),
+        _ => None,
+    };
+    if let Some(scheme) = scheme {
+        request
+            .url_mut()
+            .set_scheme(scheme)
+            .expect("ws and http are both special schemes");
+    }
+    *request.body_mut() = Body::empty();
+    let headers = request.headers().clone();
+
+    // The handshake is authenticated and reported like any other request.
+    let mut key = None;
+    let res = client
+        .execute_with(request, async |request| {
+            handshake(&client, request, &mut key).await
+        })
+        .await?;
+
+    let Some(key) = key else {
+        if !res.status().is_success() {
+            return Err(http_error(res).await);
+        }
+        return finish(res, &headers).await;
+    };
+    if res.status() != StatusCode::SWITCHING_PROTOCOLS {
+        return Err(http_error(res).await);
+    }
+    if !header_contains(res.headers(), header::CONNECTION, "upgrade") {
+        return Err(protocol_error(
+            ProtocolError::MissingConnectionUpgradeHeader,
+        ));
+    }
+    if !header_eq(res.headers(), header::UPGRADE, "websocket") {
+        return Err(protocol_error(ProtocolError::MissingUpgradeWebSocketHeader));
+    }
+    if res
+        .headers()
+        .get(header::SEC_WEBSOCKET_ACCEPT)
+        .is_none_or(|accept| accept.as_bytes() != derive_accept_key(key.as_bytes()).as_bytes())
+    {
+        return Err(protocol_error(ProtocolError::SecWebSocketAcceptKeyMismatch));
+    }
+    finish(res, &headers).await
+}
+
+/// Send the handshake request, setting `key` if it is an HTTP/1.1 upgrade
+/// rather than an extended CONNECT.
+async fn handshake(
+    client: &Client,
+    mut request: Request,
+    key: &mut Option<String>,
+) -> Result<Response> {
+    request.headers_mut().insert(
+        header::SEC_WEBSOCKET_VERSION,
+        HeaderValue::from_static("13"),
+    );
+
+    #[cfg(feature = "http2")]
+    {
+        // Try the extended CONNECT first. It is rejected before anything is
+        // sent if the connection turns out to be HTTP/1, unless the version is
+        // HTTP/2 explicitly.
+        let mut connect = request.try_clone().expect("the body is empty");
+        *connect.method_mut() = Method::CONNECT;
+        *connect.version_mut() = Version::HTTP_2;
+        *connect.protocol_mut() = Some(hyper::ext::Protocol::from_static("websocket"));
+        match client.execute_auth(connect).await {
+            Err(crate::Error::HyperClient(e))
+                if request.version() != Version::HTTP_2 && is_http1_connection(&e) => {}
+            res => return res,
+        }
+    }
+
+    let new_key = generate_key();
+    *request.method_mut() = Method::GET;
+    *request.version_mut() = Version::HTTP_11;
+    let headers = request.headers_mut();
+    headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+    headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+    headers.insert(
+        header::SEC_WEBSOCKET_KEY,
+        HeaderValue::from_str(&new_key).expect("base64 is a valid value"),
+    );
+    *key = Some(new_key);
+    client.execute_auth(request).await
+}
+
+/// Whether the HTTP/2 request is rejected because the connection is HTTP/1.
+#[cfg(feature = "http2")]
+fn is_http1_connection(e: &hyper_util::client::legacy::Error) -> bool {
+    use std::error::Error;
+
+    !e.is_connect()
+        && e.source().is_none()
+        && e.connect_info()
+            .is_some_and(|info| !info.is_negotiated_h2())
+}
+
+async fn finish(res: Response, headers: &HeaderMap) -> Result<WebSocket> {
+    let protocol = res.headers().get(header::SEC_WEBSOCKET_PROTOCOL).cloned();
+    if let Some(protocol) = &protocol {
+        let requested = headers
+            .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .flat_map(|val| val.as_bytes().split(|&b| b == b','))
+            .any(|proto| proto.trim_ascii() == protocol.as_bytes());
+        if !requested {
+            let err = if headers.contains_key(header::SEC_WEBSOCKET_PROTOCOL) {
+                SubProtocolError::InvalidSubProtocol
+            } else {
+                SubProtocolError::ServerSentSubProtocolNoneRequested
+            };
+            return Err(protocol_error(ProtocolError::SecWebSocketSubProtocolError(
+                err,
+            )));
+        }
+    } else if headers.contains_key(header::SEC_WEBSOCKET_PROTOCOL) {
+        return Err(protocol_error(ProtocolError::SecWebSocketSubProtocolError(
+            SubProtocolError::NoSubProtocol,
+        )));
+    }
+
+    let upgraded = res.upgrade().await?;
+    let inner = WebSocketStream::from_raw_socket(upgraded, Role::Client, None).await;
+    Ok(WebSocket { inner, protocol })
+}
+
+/// The handshake is rejected, and the response is returned as an error.
+async fn http_error(res: Response) -> crate::Error {
+    let mut builder = http::Response::builder().status(res.status());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = res.headers().clone();
+    }
+    let body = res.bytes().await.ok().map(|body| body.to_vec());
+    let res = builder.body(body).expect("status and headers are valid");
+    ts::Error::Http(Box::new(res)).into()
+}
+
+fn protocol_error(err: ProtocolError) -> crate::Error {
+    ts::Error::Protocol(err).into()
+}
+
+fn header_eq(headers: &HeaderMap, key: header::HeaderName, value: &'static str) -> bool {
+    headers
+        .get(&key)
+        .is_some_and(|header| header.as_bytes().eq_ignore_ascii_case(value.as_bytes()))
+}
+
+fn header_contains(headers: &HeaderMap, key: header::HeaderName, value: &'static str) -> bool {
+    headers
+        .get_all(&key)
+        .iter()
+        .flat_map(|header| header.as_bytes().split(|&b| b == b','))
+        .any(|token| token.trim_ascii().eq_ignore_ascii_case(value.as_bytes()))
+}
@@ -0,0 +1,212 @@
+mod server;
+
+#[cfg(feature = "json")]
+use std::collections::HashMap;
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+#[cfg(feature = "json")]
+use axum::response::IntoResponse;
+use cyper::{Client, Request, auth::Authenticator};
+use http::{HeaderValue, StatusCode, header};
+
+/// A token endpoint issuing `token1`, `token2`, ..., and an API accepting
+/// only the latest token.
+#[cfg(feature = "json")]
+#[derive(Clone, Default)]
+struct OAuthServer {
+    tokens: Arc<AtomicUsize>,
+}
+
+#[cfg(feature = "json")]
+impl OAuthServer {
+    async fn handle(self, req: axum::extract::Request) -> axum::response::Response {
+        let auth = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .map(|v| v.to_str().unwrap().to_string());
+        match req.uri().path() {
+            "/token" => {
+                assert_eq!(auth.as_deref(), Some("Basic aWQ6c2VjcmV0"));
+                let body = axum::body::to_bytes(req.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let form: HashMap<String, String> = serde_urlencoded::from_bytes(&body).unwrap();
+                let n = self.tokens.fetch_add(1, Ordering::SeqCst) + 1;
+                let refresh = match form["grant_type"].as_str() {
+                    "client_credentials" => {
+                        assert_eq!(form["scope"], "read write");
+                        "refresh1".to_string()
+                    }
+                    "refresh_token" => {
+                        assert_eq!(form["refresh_token"], format!("refresh{}", n - 1));
+                        format!("refresh{n}")
+                    }
+                    grant => panic!("unexpected grant: {grant}"),
+                };
+                (
+                    [(header::CONTENT_TYPE, "application/json")],
+                    format!(
+                        r#"{{"access_token":"token{n}","token_type":"Bearer","expires_in":3600,"refresh_token":"{refresh}"}}"#
+                    ),
+                )
+                    .into_response()
+            }
+            _ => {
+                let expected = format!("Bearer token{}", self.tokens.load(Ordering::SeqCst));
+                // The first token is revoked.
+                if auth.as_deref() == Some(expected.as_str()) && expected != "Bearer token1" {
+                    "ok".into_response()
+                } else {
+                    (
+                        StatusCode::UNAUTHORIZED,
+                        [(header::WWW_AUTHENTICATE, "Bearer error=\"invalid_token\"")],
+                    )
+                        .into_response()
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+#[compio::test]
+async fn oauth2_client_credentials() {
+    use cyper::auth::OAuth2;
+
+    let oauth = OAuthServer::default();
+    let handler = oauth.clone();
+    let server = server::http(move |req| handler.clone().handle(req)).await;
+
+    let client = Client::builder()
+        .authenticator(
+            OAuth2::client_credentials(format!("http://{}/token", server.addr()), "id", "secret")
+                .unwrap()
+                .origin(format!("http://{}/api", server.addr()))
+                .unwrap()
+                .scopes(["read", "write"]),
+        )
+        .build()
+        .unwrap();
+    for _ in 0..2 {
+        let res = client
+            .post(format!("http://{}/api", server.addr()))
+            .unwrap()
+            .body("body")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+    // The rejected token is refreshed once, and then cached.
+    assert_eq!(oauth.tokens.load(Ordering::SeqCst), 2);
+
+    // An explicit header bypasses the authenticator.
+    let res = client
+        .get(format!("http://{}/api", server.addr()))
+        .unwrap()
+        .bearer_auth("token1")
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(oauth.tokens.load(Ordering::SeqCst), 2);
+
+    // The token is not sent to other origins, which are not retried either.
+    let other = server::http(|req: axum::extract::Request| async move {
+        match req.headers().get(header::AUTHORIZATION) {
+            Some(_) => StatusCode::BAD_REQUEST,
+            None => StatusCode::UNAUTHORIZED,
+        }
+    })
+    .await;
+    let res = client
+        .get(format!("http://{}/api", other.addr()))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(oauth.tokens.load(Ordering::SeqCst), 2);
+}
+
+#[cfg(feature = "json")]
+#[compio::test]
+async fn oauth2_refresh_token() {
+    use cyper::auth::OAuth2;
+
+    let oauth = OAuthServer::default();
+    // Pretend that `token1` has been issued with `refresh1`.
+    oauth.tokens.store(1, Ordering::SeqCst);
+    let handler = oauth.clone();
+    let server = server::http(move |req| handler.clone().handle(req)).await;
+
+    let client = Client::builder()
+        .authenticator(
+            OAuth2::refresh_token(format!("http://{}/token", server.addr()), "id", "refresh1")
+                .unwrap()
+                .origin(format!("http://{}", server.addr()))
+                .unwrap()
+                .client_secret("secret"),
+        )
+        .build()
+        .unwrap();
+    let res = client
+        .get(format!("http://{}/api", server.addr()))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(oauth.tokens.load(Ordering::SeqCst), 2);
+}
+
+struct Static(Arc<AtomicUsize>);
+
+impl Authenticator for Static {
+    type Err = cyper::Error;
+
+    async fn authorize(
+        &self,
+        _client: &Client,
+        request: &Request,
+    ) -> cyper::Result<Option<HeaderValue>> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Ok((request.url().path() != "/public").then(|| HeaderValue::from_static("Static key")))
+    }
+}
+
+#[compio::test]
+async fn custom_authenticator() {
+    let server = server::http(|req: axum::extract::Request| async move {
+        match req.headers().get(header::AUTHORIZATION) {
+            Some(auth) if auth == "Static key" => StatusCode::OK,
+            Some(_) => StatusCode::BAD_REQUEST,
+            None if req.uri().path() == "/public" => StatusCode::NO_CONTENT,
+            None => StatusCode::UNAUTHORIZED,
+        }
+    })
+    .await;
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let client = Client::builder()
+        .authenticator(Static(calls.clone()))
+        .build()
+        .unwrap();
+    for (path, status) in [
+        ("/private", StatusCode::OK),
+        ("/public", StatusCode::NO_CONTENT),
+    ] {
+        let res = client
+            .get(format!("http://{}{path}", server.addr()))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), status);
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,114 @@
+mod server;
+
+use std::{convert::Infallible, net::Ipv4Addr, sync::Arc, time::Duration};
+
+use axum::response::IntoResponse;
+use compio::{
+    bytes::Bytes,
+    rustls::{ClientConfig, RootCertStore},
+};
+use cyper::{Client, Timings};
+use http::Response;
+use http_body_util::Full;
+use hyper::service::service_fn;
+
+#[compio::test]
+async fn connection_info_plain() {
+    let server = server::http(|_req: axum::extract::Request| async { "ok" }).await;
+
+    let client = Client::new().unwrap();
+    let res = client
+        .get(format!("http://{}/", server.addr()))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.remote_addr(), Some(server.addr()));
+    let local = res.local_addr().unwrap();
+    assert_eq!(local.ip(), Ipv4Addr::LOCALHOST);
+    assert_ne!(local.port(), 0);
+    assert!(res.tls_info().is_none());
+    assert!(res.negotiated_protocol().is_none());
+}
+
+#[compio::test]
+async fn timings() {
+    let server = server::http(|req: axum::extract::Request| async move {
+        match req.uri().path() {
+            "/redirect" => {
+                (http::StatusCode::FOUND, [(http::header::LOCATION, "/slow")]).into_response()
+            }
+            _ => {
+                send_wrapper::SendWrapper::new(compio::time::sleep(Duration::from_millis(50)))
+                    .await;
+                "ok".into_response()
+            }
+        }
+    })
+    .await;
+
+    let client = Client::new().unwrap();
+    let url = format!("http://{}/", server.addr());
+    let res = client.get(&url).unwrap().send().await.unwrap();
+    let timings = *res.extensions().get::<Timings>().unwrap();
+    assert!(!timings.reused());
+    assert!(timings.tls_handshake().is_none());
+    let name_lookup = timings.name_lookup().unwrap();
+    let connect = timings.connect().unwrap();
+    let request_sent = timings.request_sent().unwrap();
+    assert!(name_lookup <= connect);
+    assert!(connect <= request_sent);
+    assert!(request_sent <= timings.first_byte());
+    assert!(timings.first_byte() >= Duration::from_millis(50));
+    assert_eq!(timings.first_byte(), timings.total());
+    res.text().await.unwrap();
+
+    // The connection is reused, and the total includes the redirect.
+    let res = client
+        .get(format!("{url}redirect"))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    let timings = res.extensions().get::<Timings>().unwrap();
+    assert!(timings.reused());
+    assert!(timings.name_lookup().is_none());
+    assert!(timings.connect().is_none());
+    assert!(timings.request_sent().is_none());
+    assert!(timings.first_byte() >= Duration::from_millis(50));
+    assert!(timings.total() > timings.first_byte());
+}
+
+#[compio::test]
+async fn connection_info_tls() {
+    let service = service_fn(|_| async {
+        Ok::<_, Infallible>(Response::new(Full::new(Bytes::from_static(b"ok"))))
+    });
+    let server::HttpsServer { addr, cert, .. } = server::https(&["127.0.0.1"], service).await;
+
+    let mut roots = RootCertStore::empty();
+    roots.add(cert.clone()).unwrap();
+    let mut config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"http/1.1".into()];
+    let client = Client::builder()
+        .use_rustls(Arc::new(config))
+        .build()
+        .unwrap();
+    let res = client
+        .get(format!("https://{addr}/"))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.remote_addr(), Some(addr));
+    assert_eq!(res.negotiated_protocol(), Some(&b"http/1.1"[..]));
+    let tls = res.tls_info().unwrap();
+    assert_eq!(tls.peer_certificates(), [cert.to_vec()]);
+    assert_eq!(tls.protocol_version(), Some("TLSv1.3"));
+    assert!(tls.cipher_suite().unwrap().starts_with("TLS13_"));
+    let timings = res.extensions().get::<Timings>().unwrap();
+    assert!(timings.connect().unwrap() <= timings.tls_handshake().unwrap());
+    assert_eq!(res.text().await.unwrap(), "ok");
+}
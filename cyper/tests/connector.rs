@@ -0,0 +1,127 @@
+use std::{
+    cell::Cell,
+    io,
+    rc::Rc,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use axum::{
+    Router,
+    extract::Request,
+    routing::{get, post},
+};
+use cyper::{Client, DuplexStream, duplex};
+use futures_channel::mpsc;
+use futures_util::StreamExt;
+use http::{Uri, header};
+
+/// A listener accepting the in-memory streams sent by the connector.
+struct Memory(mpsc::UnboundedReceiver<DuplexStream>);
+
+impl cyper_axum::Listener for Memory {
+    type Addr = ();
+    type Io = DuplexStream;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.0.next().await {
+            Some(stream) => (stream, ()),
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(())
+    }
+}
+
+/// Serve the router in memory, returning the client connected to it and the
+/// count of the connections.
+fn client(router: Router) -> (Client, Arc<AtomicUsize>) {
+    let (tx, rx) = mpsc::unbounded();
+    compio::runtime::spawn(cyper_axum::serve(Memory(rx), router).into_future()).detach();
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    let client = Client::builder()
+        .connector(tower::service_fn({
+            let connections = connections.clone();
+            move |_uri: Uri| {
+                let tx = tx.clone();
+                connections.fetch_add(1, Ordering::Relaxed);
+                async move {
+                    let (client, server) = duplex(1024);
+                    tx.unbounded_send(server).map_err(io::Error::other)?;
+                    Ok::<_, io::Error>(client)
+                }
+            }
+        }))
+        .build()
+        .unwrap();
+    (client, connections)
+}
+
+#[compio::test]
+async fn in_memory() {
+    let router =
+        Router::new()
+            .route(
+                "/host",
+                get(|req: Request| async move {
+                    req.headers()[header::HOST].to_str().unwrap().to_string()
+                }),
+            )
+            .route("/echo", post(|body: String| async move { body }));
+    let (client, connections) = client(router);
+
+    let res = client
+        .get("http://in-memory.test/host")
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert!(res.remote_addr().is_none());
+    assert_eq!(res.text().await.unwrap(), "in-memory.test");
+
+    // The body is larger than the buffer of the streams.
+    let body = "cyper".repeat(64 * 1024);
+    let res = client
+        .post("http://in-memory.test/echo")
+        .unwrap()
+        .body(body.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), body);
+
+    // The connection is pooled.
+    assert_eq!(connections.load(Ordering::Relaxed), 1);
+}
+
+#[compio::test]
+async fn connect_error() {
+    let called = Rc::new(Cell::new(false));
+    let client = Client::builder()
+        .connector(tower::service_fn({
+            let called = called.clone();
+            move |_uri: Uri| {
+                called.set(true);
+                async move {
+                    Err::<DuplexStream, _>(io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        "refused",
+                    ))
+                }
+            }
+        }))
+        .build()
+        .unwrap();
+    client
+        .get("http://in-memory.test/")
+        .unwrap()
+        .send()
+        .await
+        .unwrap_err();
+    assert!(called.get());
+}
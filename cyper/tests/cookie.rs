@@ -1,4 +1,7 @@
-use axum::{extract::Request, response::IntoResponse};
+use axum::{
+    extract::Request,
+    response::{AppendHeaders, IntoResponse},
+};
 use http::header::SET_COOKIE;
 
 mod server;
@@ -175,3 +178,121 @@ async fn cookie_store_path() {
     let url = format!("http://{}/subpath", server.addr());
     client.get(&url).unwrap().send().await.unwrap();
 }
+
+#[compio::test]
+async fn cookie_provider_persist() {
+    use std::sync::Arc;
+
+    use cyper::cookie::{CookieStore, Jar};
+
+    let server = server::http(move |req: Request| async move {
+        if req.uri() == "/login" {
+            AppendHeaders([
+                (SET_COOKIE, "session=1; HttpOnly"),
+                (SET_COOKIE, "persistent=1; Max-Age=3600"),
+            ])
+            .into_response()
+        } else {
+            req.headers()
+                .get("cookie")
+                .map(|v| v.to_str().unwrap().to_string())
+                .unwrap_or_default()
+                .into_response()
+        }
+    })
+    .await;
+
+    // The jar is seeded before the client is built.
+    let jar = Arc::new(Jar::new());
+    let base = format!("http://{}", server.addr());
+    let url = base.parse().unwrap();
+    jar.add_cookie_str("seed=1", &url);
+    let client = cyper::Client::builder()
+        .cookie_provider(jar.clone())
+        .build()
+        .unwrap();
+    client
+        .get(format!("{base}/login"))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    let cookies = jar.cookies(&url).unwrap();
+    assert!(cookies.to_str().unwrap().contains("session=1"));
+
+    let dir = std::env::temp_dir();
+    let json = dir.join(format!("cyper-cookies-{}.json", std::process::id()));
+    let txt = dir.join(format!("cyper-cookies-{}.txt", std::process::id()));
+    jar.save_json(&json).await.unwrap();
+    jar.save_netscape(&txt).await.unwrap();
+
+    for jar in [
+        Jar::load_json(&json).await.unwrap(),
+        Jar::load_netscape(&txt).await.unwrap(),
+    ] {
+        let client = cyper::Client::builder()
+            .cookie_provider(Arc::new(jar))
+            .build()
+            .unwrap();
+        let res = client.get(&base).unwrap().send().await.unwrap();
+        let mut cookies = res
+            .text()
+            .await
+            .unwrap()
+            .split("; ")
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        cookies.sort();
+        assert_eq!(cookies, ["persistent=1", "seed=1", "session=1"]);
+    }
+    std::fs::remove_file(json).unwrap();
+    std::fs::remove_file(txt).unwrap();
+
+    // A missing file results in an empty jar.
+    let jar = Jar::load_json(dir.join("cyper-missing-cookies.json"))
+        .await
+        .unwrap();
+    assert!(jar.cookies(&url).is_none());
+}
+
+#[compio::test]
+async fn cookie_provider_custom() {
+    use std::sync::{Arc, Mutex};
+
+    use cyper::cookie::CookieStore;
+    use http::HeaderValue;
+    use url::Url;
+
+    /// Records the `Set-Cookie` headers, and sends a fixed cookie.
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl CookieStore for Recorder {
+        fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+            let mut recorded = self.0.lock().unwrap();
+            for value in cookie_headers {
+                recorded.push(format!("{} {}", url.path(), value.to_str().unwrap()));
+            }
+        }
+
+        fn cookies(&self, _url: &Url) -> Option<HeaderValue> {
+            Some(HeaderValue::from_static("fixed=1"))
+        }
+    }
+
+    let server = server::http(move |req: Request| async move {
+        assert_eq!(req.headers()["cookie"], "fixed=1");
+        AppendHeaders([(SET_COOKIE, "a=1"), (SET_COOKIE, "b=2")])
+    })
+    .await;
+
+    let store = Arc::new(Recorder::default());
+    let client = cyper::Client::builder()
+        .cookie_provider(store.clone())
+        .build()
+        .unwrap();
+    let url = format!("http://{}/path", server.addr());
+    client.get(&url).unwrap().send().await.unwrap();
+    assert_eq!(*store.0.lock().unwrap(), ["/path a=1", "/path b=2"]);
+    assert_eq!(client.cookie_value(&url).unwrap().unwrap(), "fixed=1");
+}
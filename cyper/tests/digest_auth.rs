@@ -0,0 +1,148 @@
+mod server;
+
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use axum::response::IntoResponse;
+use cyper::Client;
+use http::{StatusCode, header};
+use md5::{Digest, Md5};
+
+fn md5_hex(data: String) -> String {
+    Md5::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn param<'a>(value: &'a str, name: &str) -> Option<&'a str> {
+    value.split(", ").find_map(|param| {
+        let value = param
+            .trim_start_matches("Digest ")
+            .strip_prefix(name)?
+            .strip_prefix('=')?;
+        Some(value.trim_matches('"'))
+    })
+}
+
+/// A server protected by Digest authentication, which counts the challenges
+/// and records the `nc` of authorized requests.
+#[derive(Clone, Default)]
+struct Protected {
+    challenges: Arc<AtomicUsize>,
+    nc: Arc<Mutex<Vec<String>>>,
+}
+
+impl Protected {
+    fn verify(&self, method: &str, value: &str) -> bool {
+        let (Some(uri), Some(nc), Some(cnonce), Some(response)) = (
+            param(value, "uri"),
+            param(value, "nc"),
+            param(value, "cnonce"),
+            param(value, "response"),
+        ) else {
+            return false;
+        };
+        let ha1 = md5_hex("user:test:pass".to_string());
+        let ha2 = md5_hex(format!("{method}:{uri}"));
+        let expected = md5_hex(format!("{ha1}:nonce:{nc}:{cnonce}:auth:{ha2}"));
+        self.nc.lock().unwrap().push(nc.to_string());
+        param(value, "opaque") == Some("opaque") && response == expected
+    }
+
+    async fn handle(self, req: axum::extract::Request) -> axum::response::Response {
+        let authorized = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| self.verify(req.method().as_str(), value));
+        if authorized {
+            return "secret".into_response();
+        }
+        self.challenges.fetch_add(1, Ordering::SeqCst);
+        (
+            StatusCode::UNAUTHORIZED,
+            [
+                (header::WWW_AUTHENTICATE, "Basic realm=\"test\""),
+                (
+                    header::WWW_AUTHENTICATE,
+                    "Digest realm=\"test\", qop=\"auth,auth-int\", nonce=\"nonce\", \
+                     opaque=\"opaque\", algorithm=MD5",
+                ),
+            ],
+        )
+            .into_response()
+    }
+}
+
+#[compio::test]
+async fn digest_auth_challenge() {
+    let protected = Protected::default();
+    let handler = protected.clone();
+    let server = server::http(move |req| handler.clone().handle(req)).await;
+
+    let client = Client::new().unwrap();
+    for _ in 0..2 {
+        let res = client
+            .post(format!("http://{}/protected?a=1", server.addr()))
+            .unwrap()
+            .digest_auth("user", "pass")
+            .body("body")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.text().await.unwrap(), "secret");
+    }
+
+    // The nonce is reused for the second request without a challenge.
+    assert_eq!(protected.challenges.load(Ordering::SeqCst), 1);
+    assert_eq!(*protected.nc.lock().unwrap(), ["00000001", "00000002"]);
+}
+
+#[compio::test]
+async fn digest_auth_wrong_password() {
+    let protected = Protected::default();
+    let handler = protected.clone();
+    let server = server::http(move |req| handler.clone().handle(req)).await;
+
+    let client = Client::new().unwrap();
+    let res = client
+        .get(format!("http://{}/protected", server.addr()))
+        .unwrap()
+        .digest_auth("user", "wrong")
+        .send()
+        .await
+        .unwrap();
+    // The request is replayed only once.
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(protected.challenges.load(Ordering::SeqCst), 2);
+}
+
+#[compio::test]
+async fn digest_auth_redirect_other_origin() {
+    let protected = Protected::default();
+    let handler = protected.clone();
+    let other = server::http(move |req| handler.clone().handle(req)).await;
+    let location = format!("http://{}/protected", other.addr());
+    let server = server::http(move |_: axum::extract::Request| {
+        let location = location.clone();
+        async move { (StatusCode::FOUND, [(header::LOCATION, location)]) }
+    })
+    .await;
+
+    let client = Client::new().unwrap();
+    let res = client
+        .get(format!("http://{}/redirect", server.addr()))
+        .unwrap()
+        .digest_auth("user", "pass")
+        .send()
+        .await
+        .unwrap();
+    // The challenge of the other origin is not answered.
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(protected.challenges.load(Ordering::SeqCst), 1);
+    assert!(protected.nc.lock().unwrap().is_empty());
+}
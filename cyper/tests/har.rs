@@ -0,0 +1,144 @@
+mod server;
+
+use axum::response::IntoResponse;
+use cyper::{Client, har::HarRecorder};
+use http::{StatusCode, header};
+use serde_json::Value;
+
+#[compio::test]
+async fn record() {
+    let server = server::http(|req: axum::extract::Request| async move {
+        match req.uri().path() {
+            "/redirect" => (
+                StatusCode::FOUND,
+                [
+                    (header::LOCATION, "/echo?a=1"),
+                    (header::SET_COOKIE, "session=secret"),
+                ],
+            )
+                .into_response(),
+            _ => {
+                let body = axum::body::to_bytes(req.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                ([(header::CONTENT_TYPE, "text/plain")], body).into_response()
+            }
+        }
+    })
+    .await;
+
+    let recorder = HarRecorder::new();
+    let client = Client::builder()
+        .recorder(recorder.clone())
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/", server.addr());
+    let res = client
+        .get(format!("{url}redirect"))
+        .unwrap()
+        .header(header::AUTHORIZATION, "Bearer secret")
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "");
+    assert_eq!(recorder.len(), 2);
+
+    let res = client
+        .post(format!("{url}echo"))
+        .unwrap()
+        .header(header::CONTENT_TYPE, "text/plain")
+        .unwrap()
+        .body("hello")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "hello");
+
+    let har: Value = serde_json::from_str(&recorder.to_json()).unwrap();
+    assert_eq!(har["log"]["version"], "1.2");
+    assert_eq!(har["log"]["creator"]["name"], "cyper");
+    let entries = har["log"]["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 3);
+
+    let redirect = &entries[0];
+    assert_eq!(redirect["request"]["method"], "GET");
+    assert_eq!(redirect["request"]["url"], format!("{url}redirect"));
+    assert_eq!(redirect["request"]["bodySize"], 0);
+    assert_eq!(redirect["response"]["status"], 302);
+    assert_eq!(redirect["response"]["redirectURL"], "/echo?a=1");
+    assert_eq!(redirect["serverIPAddress"], "127.0.0.1");
+    let header = |headers: &Value, name: &str| {
+        headers
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|h| h["name"] == name)
+            .map(|h| h["value"].clone())
+    };
+    assert_eq!(
+        header(&redirect["request"]["headers"], "authorization").unwrap(),
+        "REDACTED"
+    );
+    assert_eq!(
+        header(&redirect["response"]["headers"], "set-cookie").unwrap(),
+        "REDACTED"
+    );
+
+    let echo = &entries[1];
+    assert_eq!(echo["request"]["url"], format!("{url}echo?a=1"));
+    assert_eq!(
+        echo["request"]["queryString"],
+        serde_json::json!([{ "name": "a", "value": "1" }])
+    );
+    assert_eq!(echo["response"]["status"], 200);
+    assert_eq!(echo["response"]["content"]["text"], "");
+    assert!(echo["time"].as_f64().unwrap() >= 0.0);
+
+    let post = &entries[2];
+    assert_eq!(post["request"]["method"], "POST");
+    assert_eq!(post["request"]["bodySize"], 5);
+    assert_eq!(post["request"]["postData"]["text"], "hello");
+    assert_eq!(post["request"]["postData"]["mimeType"], "text/plain");
+    assert_eq!(post["response"]["content"]["text"], "hello");
+    assert_eq!(post["response"]["content"]["mimeType"], "text/plain");
+    assert_eq!(post["response"]["bodySize"], 5);
+
+    recorder.clear();
+    assert!(recorder.is_empty());
+}
+
+#[compio::test]
+async fn truncate_and_error() {
+    let server = server::http(|_req: axum::extract::Request| async move { "0123456789" }).await;
+
+    let recorder = HarRecorder::new().body_limit(4);
+    let client = Client::builder()
+        .recorder(recorder.clone())
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/", server.addr());
+    let res = client.get(&url).unwrap().send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "0123456789");
+
+    client
+        .get("http://127.0.0.1:1/")
+        .unwrap()
+        .send()
+        .await
+        .unwrap_err();
+
+    let har: Value = serde_json::from_str(&recorder.to_json()).unwrap();
+    let entries = har["log"]["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+
+    let content = &entries[0]["response"]["content"];
+    assert_eq!(content["size"], 10);
+    assert_eq!(content["text"], "0123");
+    assert_eq!(content["comment"], "truncated to 4 bytes");
+
+    assert_eq!(entries[1]["response"]["status"], 0);
+    assert!(entries[1]["_error"].is_string());
+}
@@ -0,0 +1,134 @@
+mod server;
+
+use std::{
+    convert::Infallible,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::response::IntoResponse;
+use compio::{
+    bytes::Bytes,
+    rustls::{ClientConfig, RootCertStore, pki_types::CertificateDer},
+};
+use cyper::{Client, HstsStore, resolve::Resolve};
+use futures_util::{Stream, stream};
+use http::{Response, StatusCode, Uri, header};
+use http_body_util::Full;
+use hyper::service::service_fn;
+
+/// Resolves every host to the loopback address.
+struct Loopback;
+
+impl Resolve for Loopback {
+    type Err = cyper::Error;
+
+    async fn resolve(&self, _uri: &Uri) -> Result<impl Stream<Item = IpAddr> + '_, Self::Err> {
+        Ok(stream::iter([IpAddr::V4(Ipv4Addr::LOCALHOST)]))
+    }
+}
+
+/// An HTTPS server for `hsts.test` and its subdomains, sending the
+/// `Strict-Transport-Security` header.
+async fn https_server() -> (SocketAddr, CertificateDer<'static>) {
+    let service = service_fn(|_| async {
+        let res = Response::builder()
+            .header(
+                header::STRICT_TRANSPORT_SECURITY,
+                "max-age=3600; includeSubDomains",
+            )
+            .body(Full::new(Bytes::from_static(b"https")))
+            .unwrap();
+        Ok::<_, Infallible>(res)
+    });
+    let server = server::https(&["hsts.test", "*.hsts.test"], service).await;
+    (server.addr, server.cert)
+}
+
+fn client(cert: CertificateDer<'static>, store: HstsStore) -> Client {
+    let mut roots = RootCertStore::empty();
+    roots.add(cert).unwrap();
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Client::builder()
+        .use_rustls(Arc::new(config))
+        .custom_resolver(Loopback)
+        .hsts_store(store)
+        .build()
+        .unwrap()
+}
+
+#[compio::test]
+async fn hsts_upgrade() {
+    let (addr, cert) = https_server().await;
+    let port = addr.port();
+    // Redirects to the HTTPS server with a `http` URL.
+    let redirect = server::http(move |_req| async move {
+        (
+            StatusCode::FOUND,
+            [(header::LOCATION, format!("http://hsts.test:{port}/"))],
+        )
+            .into_response()
+    })
+    .await;
+
+    let client = client(cert, HstsStore::new());
+    // Plain HTTP to the HTTPS server fails before the host is known.
+    client
+        .get(format!("http://hsts.test:{port}/"))
+        .unwrap()
+        .send()
+        .await
+        .unwrap_err();
+
+    let res = client
+        .get(format!("https://hsts.test:{port}/"))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "https");
+    assert!(client.hsts_store().unwrap().is_secure("hsts.test"));
+
+    for url in [
+        format!("http://hsts.test:{port}/"),
+        format!("http://sub.hsts.test:{port}/"),
+        format!("http://{}/", redirect.addr()),
+    ] {
+        let res = client.get(url).unwrap().send().await.unwrap();
+        assert_eq!(res.url().scheme(), "https");
+        assert_eq!(res.text().await.unwrap(), "https");
+    }
+}
+
+#[compio::test]
+async fn hsts_preload() {
+    let (addr, cert) = https_server().await;
+
+    let store = HstsStore::new();
+    store.preload("hsts.test", false);
+    let client = client(cert, store.clone());
+    let res = client
+        .get(format!("http://hsts.test:{}/", addr.port()))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "https");
+
+    let path = std::env::temp_dir().join(format!("cyper-hsts-{}", std::process::id()));
+    store.save(&path).await.unwrap();
+    let loaded = HstsStore::load(&path).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(loaded.is_secure("hsts.test"));
+    assert!(!loaded.is_secure("sub.hsts.test"));
+}
+
+#[compio::test]
+async fn hsts_disabled() {
+    let client = Client::new().unwrap();
+    assert!(client.hsts_store().is_none());
+    let client = Client::builder().hsts(true).build().unwrap();
+    assert!(client.hsts_store().is_some());
+}
@@ -0,0 +1,683 @@
+#[allow(dead_code)] // Only the HTTPS server is used.
+mod server;
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use compio::{
+    bytes::Bytes,
+    quic::ServerBuilder,
+    rustls::{ClientConfig, RootCertStore, pki_types::CertificateDer},
+};
+#[cfg(feature = "http3-altsvc")]
+use cyper::AltSvcCache;
+use cyper::{Client, Http3Options};
+use futures_util::future::join_all;
+use http::{Response, Version};
+
+struct Server {
+    addr: SocketAddr,
+    cert: CertificateDer<'static>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl Server {
+    fn url(&self, path: &str) -> String {
+        format!("https://{}{path}", self.addr)
+    }
+
+    fn tls_config(&self) -> Arc<ClientConfig> {
+        tls_config([self])
+    }
+}
+
+fn tls_config<'a>(servers: impl IntoIterator<Item = &'a Server>) -> Arc<ClientConfig> {
+    let mut store = RootCertStore::empty();
+    for server in servers {
+        store.add(server.cert.clone()).unwrap();
+    }
+    Arc::new(
+        ClientConfig::builder()
+            .with_root_certificates(store)
+            .with_no_client_auth(),
+    )
+}
+
+async fn server() -> Server {
+    let rcgen::CertifiedKey { cert, signing_key } =
+        rcgen::generate_simple_self_signed(vec!["127.0.0.1".into(), "svcb.test".into()]).unwrap();
+    let cert = cert.der().clone();
+    let key = signing_key.serialize_der().try_into().unwrap();
+
+    let endpoint = ServerBuilder::new_with_single_cert(vec![cert.clone()], key)
+        .unwrap()
+        .with_alpn_protocols(&["h3"])
+        .bind((Ipv4Addr::LOCALHOST, 0))
+        .await
+        .unwrap();
+    let addr = endpoint.local_addr().unwrap();
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    let counter = connections.clone();
+    compio::runtime::spawn(async move {
+        while let Some(incoming) = endpoint.wait_incoming().await {
+            counter.fetch_add(1, Ordering::SeqCst);
+            compio::runtime::spawn(async move {
+                let Ok(conn) = incoming.await else {
+                    return;
+                };
+                let mut conn = compio::quic::h3::server::builder()
+                    .build::<_, Bytes>(conn)
+                    .await
+                    .unwrap();
+                while let Ok(Some(resolver)) = conn.accept().await {
+                    compio::runtime::spawn(async move {
+                        let (req, mut stream) = resolver.resolve_request().await.unwrap();
+                        if req.uri().path().starts_with("/slow") {
+                            compio::time::sleep(Duration::from_millis(200)).await;
+                        }
+                        // Advertise the alternative service requested by the client.
+                        let mut res = Response::builder();
+                        if let Some(alt_svc) = req.headers().get("x-alt-svc") {
+                            res = res.header(http::header::ALT_SVC, alt_svc);
+                        }
+                        let body = match req.headers().get("alt-used") {
+                            Some(alt_used) if req.uri().path() == "/alt-used" => {
+                                Bytes::copy_from_slice(alt_used.as_bytes())
+                            }
+                            _ => Bytes::from(req.uri().path().to_string()),
+                        };
+                        // Echo the request trailers as the response trailers.
+                        let mut trailers = None;
+                        if req.uri().path() == "/trailers" {
+                            while stream.recv_data().await.unwrap().is_some() {}
+                            trailers = stream.recv_trailers().await.unwrap();
+                        }
+                        stream.send_response(res.body(()).unwrap()).await.unwrap();
+                        stream.send_data(body).await.unwrap();
+                        if let Some(trailers) = trailers {
+                            stream.send_trailers(trailers).await.unwrap();
+                        }
+                        stream.finish().await.unwrap();
+                    })
+                    .detach();
+                }
+            })
+            .detach();
+        }
+    })
+    .detach();
+
+    Server {
+        addr,
+        cert,
+        connections,
+    }
+}
+
+/// An HTTPS server over TCP, responding with `tcp` and the `Alt-Svc` header.
+#[cfg(feature = "http3-altsvc")]
+async fn tcp_server(alt_svc: String) -> Server {
+    use std::convert::Infallible;
+
+    use http_body_util::Full;
+    use hyper::service::service_fn;
+
+    let service = service_fn(move |_| {
+        let res = Response::builder()
+            .header(http::header::ALT_SVC, &alt_svc)
+            .body(Full::new(Bytes::from_static(b"tcp")))
+            .unwrap();
+        async move { Ok::<_, Infallible>(res) }
+    });
+    let server = server::https(&["127.0.0.1"], service).await;
+    Server {
+        addr: server.addr,
+        cert: server.cert,
+        connections: server.connections,
+    }
+}
+
+#[compio::test]
+async fn http3_options() {
+    let server = server().await;
+
+    let client = Client::builder()
+        .http3_options(
+            Http3Options::new()
+                .tls_config(server.tls_config())
+                .idle_timeout(Duration::from_secs(5))
+                .keep_alive_interval(Duration::from_secs(1))
+                .max_concurrent_bidi_streams(16)
+                .zero_rtt(true),
+        )
+        .build()
+        .unwrap();
+
+    for path in ["/first", "/second"] {
+        let res = client
+            .get(server.url(path))
+            .unwrap()
+            .version(Version::HTTP_3)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.version(), Version::HTTP_3);
+        assert_eq!(res.text().await.unwrap(), path);
+    }
+    assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+}
+
+#[compio::test]
+async fn http3_connection_info() {
+    let server = server().await;
+
+    let client = Client::builder()
+        .http3_options(Http3Options::new().tls_config(server.tls_config()))
+        .build()
+        .unwrap();
+    let res = client
+        .get(server.url("/"))
+        .unwrap()
+        .version(Version::HTTP_3)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.remote_addr(), Some(server.addr));
+    assert_ne!(res.local_addr().unwrap().port(), 0);
+    assert_eq!(res.negotiated_protocol(), Some(&b"h3"[..]));
+    let tls = res.tls_info().unwrap();
+    assert_eq!(tls.peer_certificate(), Some(server.cert.as_ref()));
+    assert_eq!(tls.protocol_version(), Some("TLSv1.3"));
+    let timings = res.extensions().get::<cyper::Timings>().unwrap();
+    assert!(!timings.reused());
+    assert!(timings.tls_handshake().unwrap() <= timings.request_sent().unwrap());
+    assert!(timings.request_sent().unwrap() <= timings.first_byte());
+}
+
+#[compio::test]
+async fn http3_trailers() {
+    let server = server().await;
+
+    let client = Client::builder()
+        .http3_options(Http3Options::new().tls_config(server.tls_config()))
+        .build()
+        .unwrap();
+    let trailers =
+        http::HeaderMap::from_iter([("x-checksum".parse().unwrap(), "abc".parse().unwrap())]);
+    let mut res = client
+        .post(server.url("/trailers"))
+        .unwrap()
+        .version(Version::HTTP_3)
+        .body(cyper::Body::from("hello").with_trailers(trailers))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.version(), Version::HTTP_3);
+    let trailers = res.trailers().await.unwrap().unwrap();
+    assert_eq!(trailers["x-checksum"], "abc");
+}
+
+#[compio::test]
+#[cfg(feature = "rustls")]
+async fn http3_reuses_rustls_config() {
+    let server = server().await;
+
+    let client = Client::builder()
+        .use_rustls(server.tls_config())
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(server.url("/rustls"))
+        .unwrap()
+        .version(Version::HTTP_3)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "/rustls");
+}
+
+#[compio::test]
+async fn http3_invalid_idle_timeout() {
+    let server = server().await;
+
+    let client = Client::builder()
+        .http3_options(
+            Http3Options::new()
+                .tls_config(server.tls_config())
+                .idle_timeout(Duration::MAX),
+        )
+        .build()
+        .unwrap();
+
+    let err = client
+        .get(server.url("/"))
+        .unwrap()
+        .version(Version::HTTP_3)
+        .send()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, cyper::Error::H3Client(_)), "{err}");
+}
+
+async fn get_all(client: &Client, server: &Server, paths: &[&str]) {
+    let results = join_all(paths.iter().map(|path| async move {
+        client
+            .get(server.url(path))
+            .unwrap()
+            .version(Version::HTTP_3)
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap()
+    }))
+    .await;
+    assert_eq!(results, paths);
+}
+
+#[compio::test]
+async fn http3_coalesce_connects() {
+    let server = server().await;
+
+    let client = Client::builder()
+        .http3_options(Http3Options::new().tls_config(server.tls_config()))
+        .build()
+        .unwrap();
+
+    get_all(&client, &server, &["/a", "/b", "/c", "/d"]).await;
+    assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+}
+
+#[compio::test]
+async fn http3_max_streams_per_connection() {
+    let server = server().await;
+
+    let client = Client::builder()
+        .http3_options(
+            Http3Options::new()
+                .tls_config(server.tls_config())
+                .max_streams_per_connection(1),
+        )
+        .build()
+        .unwrap();
+
+    get_all(&client, &server, &["/slow"]).await;
+    assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+
+    // The first request reuses the idle connection, the second one needs a new
+    // connection because the first one is saturated.
+    let first = async {
+        get_all(&client, &server, &["/slow/1"]).await;
+    };
+    let second = async {
+        compio::time::sleep(Duration::from_millis(50)).await;
+        get_all(&client, &server, &["/slow/2"]).await;
+    };
+    futures_util::join!(first, second);
+    assert_eq!(server.connections.load(Ordering::SeqCst), 2);
+}
+
+#[compio::test]
+async fn http3_max_streams_coalesced() {
+    let server = server().await;
+
+    let client = Client::builder()
+        .http3_options(
+            Http3Options::new()
+                .tls_config(server.tls_config())
+                .max_streams_per_connection(2),
+        )
+        .build()
+        .unwrap();
+
+    // The requests waiting for the handshake share the connection only up to
+    // the limit.
+    get_all(
+        &client,
+        &server,
+        &["/slow/1", "/slow/2", "/slow/3", "/slow/4"],
+    )
+    .await;
+    assert_eq!(server.connections.load(Ordering::SeqCst), 2);
+}
+
+#[compio::test]
+async fn http3_pool_idle_timeout() {
+    let server = server().await;
+
+    let metrics = cyper::metrics::PrometheusMetrics::new();
+    let client = Client::builder()
+        .metrics(metrics.clone())
+        .http3_options(
+            Http3Options::new()
+                .tls_config(server.tls_config())
+                .pool_idle_timeout(Duration::from_millis(100)),
+        )
+        .build()
+        .unwrap();
+
+    get_all(&client, &server, &["/a"]).await;
+    get_all(&client, &server, &["/b"]).await;
+    assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+
+    compio::time::sleep(Duration::from_millis(200)).await;
+    get_all(&client, &server, &["/c"]).await;
+    assert_eq!(server.connections.load(Ordering::SeqCst), 2);
+
+    // The idle connection is closed without another request.
+    compio::time::sleep(Duration::from_millis(300)).await;
+    let closed = r#"cyper_connections_closed_total{host="127.0.0.1"} 2"#;
+    assert!(metrics.render().lines().any(|line| line == closed));
+}
+
+#[compio::test]
+#[cfg(feature = "http3-altsvc")]
+async fn http3_alt_svc_alternative() {
+    let origin = server().await;
+    let alternative = server().await;
+    let options = Http3Options::new().tls_config(tls_config([&origin, &alternative]));
+
+    let client = Client::builder()
+        .http3_options(options.clone())
+        .build()
+        .unwrap();
+
+    let alt_svc = format!(
+        r#"h2=":443"; ma=60, h3="{}"; ma=60; persist=1"#,
+        alternative.addr
+    );
+    client
+        .get(origin.url("/"))
+        .unwrap()
+        .version(Version::HTTP_3)
+        .header("x-alt-svc", alt_svc)
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+
+    // The request to the origin is sent to the alternative service.
+    let res = client
+        .get(origin.url("/alt-used"))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.version(), Version::HTTP_3);
+    assert_eq!(res.text().await.unwrap(), alternative.addr.to_string());
+    assert_eq!(origin.connections.load(Ordering::SeqCst), 1);
+    assert_eq!(alternative.connections.load(Ordering::SeqCst), 1);
+
+    // A new client starts on the alternative service with a saved cache.
+    let path = std::env::temp_dir().join(format!("cyper-alt-svc-{}", alternative.addr.port()));
+    client.alt_svc_cache().save(&path).await.unwrap();
+    let cache = AltSvcCache::load(&path).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let client = Client::builder()
+        .http3_options(options)
+        .alt_svc_cache(cache)
+        .build()
+        .unwrap();
+    let res = client
+        .get(origin.url("/alt-used"))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), alternative.addr.to_string());
+    assert_eq!(alternative.connections.load(Ordering::SeqCst), 2);
+}
+
+#[cfg(feature = "hickory-dns")]
+mod svcb {
+    use std::{net::Ipv4Addr, panic::resume_unwind, sync::atomic::Ordering, time::Duration};
+
+    use async_trait::async_trait;
+    use cyper::{Client, Http3Options, resolve::HickoryResolver};
+    use cyper_hickory::CompioConnectionProvider;
+    use futures_channel::oneshot;
+    use hickory_net::{
+        proto::{
+            op::{MessageType, Metadata, OpCode},
+            rr::{
+                Name, RData, Record, RecordType,
+                rdata::{
+                    A, HTTPS, SVCB,
+                    svcb::{Alpn, IpHint, SvcParamKey, SvcParamValue},
+                },
+            },
+        },
+        runtime::Time,
+    };
+    use hickory_resolver::{
+        Resolver,
+        config::{ResolverConfig, ServerGroup},
+    };
+    use hickory_server::{
+        server::{Request, RequestHandler, ResponseHandler, ResponseInfo},
+        zone_handler::{MessageResponseBuilder, UpdateRequest},
+    };
+
+    use super::server;
+
+    /// Advertises HTTP/3 on `port` of 127.0.0.1 in the HTTPS record, while the
+    /// A record points to an address where nothing listens.
+    struct DnsHandler {
+        port: u16,
+    }
+
+    #[async_trait]
+    impl RequestHandler for DnsHandler {
+        async fn handle_request<R: ResponseHandler, T: Time>(
+            &self,
+            request: &Request,
+            mut response_handle: R,
+        ) -> ResponseInfo {
+            let query = request.request_info().unwrap().query;
+            let name = Name::from_utf8("svcb.test").unwrap();
+            let data = match query.query_type() {
+                RecordType::HTTPS => RData::HTTPS(HTTPS(SVCB::new(
+                    1,
+                    Name::root(),
+                    vec![
+                        (
+                            SvcParamKey::Alpn,
+                            SvcParamValue::Alpn(Alpn(vec!["h3".into(), "h2".into()])),
+                        ),
+                        (SvcParamKey::Port, SvcParamValue::Port(self.port)),
+                        (
+                            SvcParamKey::Ipv4Hint,
+                            SvcParamValue::Ipv4Hint(IpHint(vec![A(Ipv4Addr::LOCALHOST)])),
+                        ),
+                    ],
+                ))),
+                _ => RData::A(A(Ipv4Addr::new(127, 0, 0, 2))),
+            };
+            let record = Record::from_rdata(name, 60, data);
+            let response = MessageResponseBuilder::new(&request.queries, request.edns.as_ref())
+                .build(
+                    Metadata::new(request.id(), MessageType::Response, OpCode::Query),
+                    vec![&record],
+                    vec![],
+                    vec![],
+                    vec![],
+                );
+            response_handle.send_response(response).await.unwrap()
+        }
+    }
+
+    #[compio::test]
+    async fn http3_svcb_discovery() {
+        let h3_server = server().await;
+        let port = h3_server.addr.port();
+
+        let (tx, rx) = oneshot::channel();
+        let handle = std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(async move {
+                    let mut server = hickory_server::Server::new(DnsHandler { port });
+                    let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+                    tx.send((
+                        socket.local_addr().unwrap(),
+                        server.shutdown_token().clone(),
+                    ))
+                    .unwrap();
+                    server.register_socket(socket);
+                    server.block_until_done().await.unwrap();
+                });
+        });
+        let (dns_addr, token) = rx.await.unwrap();
+
+        let group = ServerGroup {
+            ips: &[dns_addr.ip()],
+            server_name: "svcb.test",
+            path: "/dns-query",
+        };
+        let mut config = ResolverConfig::from_parts(None, vec![], group.udp().collect());
+        for srv in &mut config.name_servers {
+            for conn in &mut srv.connections {
+                conn.port = dns_addr.port();
+            }
+        }
+        let resolver = Resolver::builder_with_config(config, CompioConnectionProvider::default())
+            .build()
+            .unwrap();
+
+        let client = Client::builder()
+            .custom_resolver(HickoryResolver::from(resolver))
+            .http3_options(
+                Http3Options::new()
+                    .tls_config(h3_server.tls_config())
+                    .idle_timeout(Duration::from_secs(5)),
+            )
+            .build()
+            .unwrap();
+
+        // Neither TCP nor the A record could reach the server, the HTTPS
+        // record leads the client to the QUIC endpoint.
+        for path in ["/first", "/second"] {
+            let res = client
+                .get(format!("https://svcb.test{path}"))
+                .unwrap()
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(res.version(), http::Version::HTTP_3);
+            assert_eq!(res.text().await.unwrap(), path);
+        }
+        assert_eq!(h3_server.connections.load(Ordering::SeqCst), 1);
+
+        token.cancel();
+        handle.join().unwrap_or_else(|e| resume_unwind(e));
+    }
+}
+
+#[compio::test]
+#[cfg(feature = "http3-altsvc")]
+async fn http3_fallback_to_tcp() {
+    use compio::net::UdpSocket;
+
+    // A UDP endpoint silently dropping all QUIC packets.
+    let blackhole = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let blackhole_addr = blackhole.local_addr().unwrap();
+    let packets = Arc::new(AtomicUsize::new(0));
+    let counter = packets.clone();
+    compio::runtime::spawn(async move {
+        loop {
+            let (res, _) = blackhole.recv(Vec::with_capacity(2048)).await.into();
+            res.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+        }
+    })
+    .detach();
+
+    let server = tcp_server(format!(r#"h3=":{}""#, blackhole_addr.port())).await;
+    let metrics = cyper::metrics::PrometheusMetrics::new();
+    let client = Client::builder()
+        .use_rustls(server.tls_config())
+        .metrics(metrics.clone())
+        .http3_options(
+            Http3Options::new()
+                .idle_timeout(Duration::from_millis(200))
+                .fallback_delay(Duration::from_millis(50)),
+        )
+        .build()
+        .unwrap();
+
+    let get = || async {
+        let res = client.get(server.url("/")).unwrap().send().await.unwrap();
+        assert_eq!(res.version(), Version::HTTP_11);
+        assert_eq!(res.text().await.unwrap(), "tcp");
+    };
+
+    // Learn the alternative service.
+    get().await;
+    assert_eq!(packets.load(Ordering::SeqCst), 0);
+
+    // QUIC is tried first, and TCP wins the race on the pooled connection.
+    get().await;
+    assert!(packets.load(Ordering::SeqCst) > 0);
+    assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+    let fallbacks = r#"cyper_http3_fallbacks_total{host="127.0.0.1"} 1"#;
+    assert!(metrics.render().lines().any(|line| line == fallbacks));
+
+    // Wait for the QUIC handshake to time out.
+    compio::time::sleep(Duration::from_millis(500)).await;
+
+    // The alternative service is marked broken and skipped.
+    let sent = packets.load(Ordering::SeqCst);
+    get().await;
+    compio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(packets.load(Ordering::SeqCst), sent);
+}
+
+#[compio::test]
+#[cfg(feature = "http3-altsvc")]
+async fn http3_fallback_non_idempotent() {
+    use compio::net::UdpSocket;
+
+    // A UDP endpoint silently dropping all QUIC packets.
+    let blackhole = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let blackhole_addr = blackhole.local_addr().unwrap();
+    let server = tcp_server(format!(r#"h3=":{}""#, blackhole_addr.port())).await;
+    let cache = AltSvcCache::new();
+    let client = || {
+        Client::builder()
+            .use_rustls(server.tls_config())
+            .alt_svc_cache(cache.clone())
+            .http3_options(Http3Options::new().fallback_delay(Duration::from_millis(50)))
+            .build()
+            .unwrap()
+    };
+
+    // Learn the alternative service.
+    client().get(server.url("/")).unwrap().send().await.unwrap();
+
+    // The connections race for non-idempotent requests too, without an open
+    // connection in the pool.
+    let res = client()
+        .post(server.url("/"))
+        .unwrap()
+        .body("body")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.version(), Version::HTTP_11);
+    assert_eq!(res.text().await.unwrap(), "tcp");
+    // The request is sent on the connection of the race.
+    assert_eq!(server.connections.load(Ordering::SeqCst), 2);
+    drop(blackhole);
+}
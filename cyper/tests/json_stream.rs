@@ -0,0 +1,102 @@
+mod server;
+
+use axum::{body::Body, extract::Request};
+use cyper::Client;
+use futures_util::{StreamExt, TryStreamExt, stream};
+use serde::Deserialize;
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+struct Record {
+    id: u64,
+    name: String,
+}
+
+fn record(id: u64, name: &str) -> Record {
+    Record {
+        id,
+        name: name.to_string(),
+    }
+}
+
+/// Serve the chunks as a streaming body.
+async fn chunked(chunks: &'static [&'static str]) -> server::Server {
+    server::http(move |_: Request| async move {
+        Body::from_stream(stream::iter(
+            chunks.iter().map(|c| Ok::<_, std::io::Error>(*c)),
+        ))
+    })
+    .await
+}
+
+#[compio::test]
+async fn json_lines() {
+    let server = chunked(&[
+        "{\"id\": 1, \"name\": \"a\"}\n{\"id\": 2,",
+        " \"name\": \"b\"}\r\n\n",
+        "\u{1e}{\"id\": 3, \"name\": \"c\"}\n{\"id\": 4, \"name\": \"d\"}",
+    ])
+    .await;
+
+    let client = Client::new().unwrap();
+    let records = client
+        .get(format!("http://{}/", server.addr()))
+        .unwrap()
+        .send()
+        .await
+        .unwrap()
+        .json_lines::<Record>()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(
+        records,
+        [
+            record(1, "a"),
+            record(2, "b"),
+            record(3, "c"),
+            record(4, "d")
+        ]
+    );
+}
+
+#[compio::test]
+async fn json_lines_invalid() {
+    let server = chunked(&["{\"id\": 1, \"name\": \"a\"}\n{\"id\": }\n"]).await;
+
+    let client = Client::new().unwrap();
+    let records = client
+        .get(format!("http://{}/", server.addr()))
+        .unwrap()
+        .send()
+        .await
+        .unwrap()
+        .json_lines::<Record>()
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(records.len(), 2);
+    assert_eq!(*records[0].as_ref().unwrap(), record(1, "a"));
+    assert!(matches!(records[1], Err(cyper::Error::Json(_))));
+}
+
+#[compio::test]
+async fn json_array() {
+    let server = chunked(&[
+        "[{\"id\": 1, \"name\": \"a]\"}",
+        ", {\"id\": 2, \"name\": \"b\"}, {\"id\"",
+        ": 3, \"name\": \"c\"}]",
+    ])
+    .await;
+
+    let client = Client::new().unwrap();
+    let records = client
+        .get(format!("http://{}/", server.addr()))
+        .unwrap()
+        .send()
+        .await
+        .unwrap()
+        .json_array::<Record>()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(records, [record(1, "a]"), record(2, "b"), record(3, "c")]);
+}
@@ -0,0 +1,136 @@
+mod server;
+
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use cyper::{Client, metrics::Metrics};
+use http::{Method, StatusCode};
+use url::Url;
+
+/// Records the notifications, with the durations omitted.
+#[derive(Default, Clone)]
+struct Recorder(Rc<RefCell<Vec<String>>>);
+
+impl Recorder {
+    fn push(&self, event: String) {
+        self.0.borrow_mut().push(event);
+    }
+
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut self.0.borrow_mut())
+    }
+}
+
+impl Metrics for Recorder {
+    fn request_start(&self, method: &Method, url: &Url) {
+        self.push(format!("start {method} {}", url.path()));
+    }
+
+    fn request_end(
+        &self,
+        method: &Method,
+        url: &Url,
+        result: Result<StatusCode, &cyper::Error>,
+        _duration: Duration,
+    ) {
+        let result = match result {
+            Ok(status) => status.as_str().to_string(),
+            Err(_) => "error".to_string(),
+        };
+        self.push(format!("end {method} {} {result}", url.path()));
+    }
+
+    fn request_cancel(&self, method: &Method, url: &Url, _duration: Duration) {
+        self.push(format!("cancel {method} {}", url.path()));
+    }
+
+    fn connection_open(&self, host: &str) {
+        self.push(format!("open {host}"));
+    }
+
+    fn connection_close(&self, host: &str, _duration: Duration) {
+        self.push(format!("close {host}"));
+    }
+
+    fn pool_hit(&self, host: &str) {
+        self.push(format!("hit {host}"));
+    }
+
+    fn pool_miss(&self, host: &str) {
+        self.push(format!("miss {host}"));
+    }
+}
+
+#[compio::test]
+async fn metrics() {
+    let server = server::http(|req: axum::extract::Request| async move {
+        match req.uri().path() {
+            "/missing" => StatusCode::NOT_FOUND,
+            _ => StatusCode::OK,
+        }
+    })
+    .await;
+
+    let recorder = Recorder::default();
+    let client = Client::builder().metrics(recorder.clone()).build().unwrap();
+
+    let url = format!("http://{}/", server.addr());
+    let res = client.get(&url).unwrap().send().await.unwrap();
+    res.bytes().await.unwrap();
+    assert_eq!(
+        recorder.take(),
+        [
+            "start GET /",
+            "open 127.0.0.1",
+            "miss 127.0.0.1",
+            "end GET / 200"
+        ]
+    );
+
+    let res = client
+        .post(format!("{url}missing"))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    res.bytes().await.unwrap();
+    assert_eq!(
+        recorder.take(),
+        [
+            "start POST /missing",
+            "hit 127.0.0.1",
+            "end POST /missing 404"
+        ]
+    );
+
+    client
+        .get("http://127.0.0.1:1/")
+        .unwrap()
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(recorder.take(), ["start GET /", "end GET / error"]);
+
+    // The pooled connection is closed with the client.
+    drop(client);
+    compio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(recorder.take(), ["close 127.0.0.1"]);
+}
+
+#[compio::test]
+async fn metrics_cancel() {
+    // The server never responds.
+    let server = server::http(|_: axum::extract::Request| std::future::pending::<()>()).await;
+
+    let recorder = Recorder::default();
+    let client = Client::builder().metrics(recorder.clone()).build().unwrap();
+    let request = client
+        .get(format!("http://{}/slow", server.addr()))
+        .unwrap()
+        .send();
+    compio::time::timeout(Duration::from_millis(100), request)
+        .await
+        .unwrap_err();
+    let events = recorder.take();
+    assert_eq!(events.first().unwrap(), "start GET /slow");
+    assert_eq!(events.last().unwrap(), "cancel GET /slow");
+}
@@ -0,0 +1,83 @@
+mod server;
+
+use axum::response::IntoResponse;
+use cyper::Client;
+use http::{StatusCode, header};
+
+async fn handle(req: axum::extract::Request) -> axum::response::Response {
+    let host = req.headers()[header::HOST].to_str().unwrap().to_string();
+    match req.uri().path() {
+        // Redirect from `127.0.0.1` to `localhost`, on the same port.
+        "/cross" => {
+            let port = host.rsplit_once(':').unwrap().1;
+            (
+                StatusCode::FOUND,
+                [(header::LOCATION, format!("http://localhost:{port}/auth"))],
+            )
+                .into_response()
+        }
+        "/same" => (StatusCode::FOUND, [(header::LOCATION, "/auth")]).into_response(),
+        _ => req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .map(|v| v.to_str().unwrap().to_string())
+            .unwrap_or_default()
+            .into_response(),
+    }
+}
+
+async fn authorization(client: &Client, url: String) -> String {
+    client
+        .get(url)
+        .unwrap()
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap()
+}
+
+#[compio::test]
+async fn netrc() {
+    let path = std::env::temp_dir().join(format!("cyper-netrc-{}", std::process::id()));
+    std::fs::write(
+        &path,
+        "machine 127.0.0.1 login user password pass\nmachine example.com login other password \
+         other\n",
+    )
+    .unwrap();
+    // SAFETY: no other test in this binary reads the environment.
+    unsafe { std::env::set_var("NETRC", &path) };
+
+    let server = server::http(handle).await;
+    let client = Client::builder().netrc(true).build().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let base = format!("http://{}", server.addr());
+    assert_eq!(
+        authorization(&client, format!("{base}/auth")).await,
+        "Basic dXNlcjpwYXNz"
+    );
+    assert_eq!(
+        authorization(&client, format!("{base}/same")).await,
+        "Basic dXNlcjpwYXNz"
+    );
+    // `localhost` has no entry, and the credentials are not forwarded.
+    assert_eq!(authorization(&client, format!("{base}/cross")).await, "");
+
+    // An explicit header is kept.
+    let res = client
+        .get(format!("{base}/auth"))
+        .unwrap()
+        .bearer_auth("token")
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "Bearer token");
+
+    // Disabled by default.
+    let client = Client::new().unwrap();
+    assert_eq!(authorization(&client, format!("{base}/auth")).await, "");
+}
@@ -1,8 +1,13 @@
 mod server;
 
+use std::time::Duration;
+
 use axum::response::IntoResponse;
 use cyper::{Client, redirect};
-use http::{StatusCode, header::LOCATION};
+use http::{
+    Method, StatusCode,
+    header::{LOCATION, SERVER, SET_COOKIE},
+};
 
 #[compio::test]
 async fn test_redirect_follow() {
@@ -271,3 +276,209 @@ async fn test_redirect_method_change_on_301() {
     let text = res.text().await.unwrap();
     assert_eq!(text, "GET");
 }
+
+#[compio::test]
+async fn test_redirect_history() {
+    let server = server::http(move |req: axum::extract::Request| async move {
+        match req.uri().path() {
+            "/step/1" => (
+                StatusCode::MOVED_PERMANENTLY,
+                [(LOCATION, "/step/2"), (SERVER, "test")],
+            )
+                .into_response(),
+            "/step/2" => (
+                StatusCode::TEMPORARY_REDIRECT,
+                [(LOCATION, "/step/3"), (SET_COOKIE, "a=1")],
+            )
+                .into_response(),
+            "/step/3" => (StatusCode::FOUND, [(LOCATION, "/done")]).into_response(),
+            _ => (StatusCode::OK, "done").into_response(),
+        }
+    })
+    .await;
+
+    let client = Client::new().unwrap();
+    let res = client
+        .post(format!("http://{}/step/1", server.addr()))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let history = res
+        .redirect_history()
+        .iter()
+        .map(|hop| (hop.method().clone(), hop.url().path(), hop.status()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        history,
+        [
+            (Method::POST, "/step/1", StatusCode::MOVED_PERMANENTLY),
+            (Method::GET, "/step/2", StatusCode::TEMPORARY_REDIRECT),
+            (Method::GET, "/step/3", StatusCode::FOUND),
+        ]
+    );
+    let hop = &res.redirect_history()[0];
+    assert_eq!(hop.headers()[LOCATION], "/step/2");
+    assert_eq!(hop.headers()[SERVER], "test");
+    assert!(hop.elapsed() > Duration::ZERO);
+    // Only the selected headers are kept.
+    assert!(!res.redirect_history()[1].headers().contains_key(SET_COOKIE));
+
+    // The history is kept when the policy stops.
+    let client = Client::builder()
+        .redirect(redirect::Policy::custom(|attempt| {
+            if attempt.url().path() == "/done" {
+                attempt.stop()
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()
+        .unwrap();
+    let res = client
+        .get(format!("http://{}/step/1", server.addr()))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FOUND);
+    assert_eq!(res.url().path(), "/step/3");
+    let paths = res
+        .redirect_history()
+        .iter()
+        .map(|hop| hop.url().path())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, ["/step/1", "/step/2"]);
+
+    // No redirect.
+    let res = client
+        .get(format!("http://{}/done", server.addr()))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert!(res.redirect_history().is_empty());
+}
+
+#[compio::test]
+async fn test_redirect_rewrite() {
+    let server = server::http(move |req: axum::extract::Request| async move {
+        match req.uri().path() {
+            "/redirect" => {
+                (StatusCode::FOUND, [(LOCATION, "/target"), (SERVER, "test")]).into_response()
+            }
+            "/target" => {
+                let (parts, body) = req.into_parts();
+                let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+                let header = |name: &str| {
+                    parts
+                        .headers
+                        .get(name)
+                        .map(|v| v.to_str().unwrap().to_string())
+                        .unwrap_or_default()
+                };
+                format!(
+                    "{} {} {} {}",
+                    parts.method,
+                    String::from_utf8_lossy(&body),
+                    header("x-added"),
+                    header("x-removed"),
+                )
+                .into_response()
+            }
+            _ => StatusCode::NOT_FOUND.into_response(),
+        }
+    })
+    .await;
+    let url = format!("http://{}/redirect", server.addr());
+
+    let client = Client::builder()
+        .redirect(redirect::Policy::custom(|attempt| {
+            assert_eq!(attempt.method(), Method::POST);
+            assert_eq!(attempt.headers()["x-removed"], "1");
+            assert_eq!(attempt.response_headers()[SERVER], "test");
+            attempt
+                .follow()
+                .keep_body()
+                .header(
+                    "x-added".parse().unwrap(),
+                    http::HeaderValue::from_static("2"),
+                )
+                .remove_header("x-removed".parse().unwrap())
+        }))
+        .build()
+        .unwrap();
+    let res = client
+        .post(&url)
+        .unwrap()
+        .header("x-removed", "1")
+        .unwrap()
+        .body("hello")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "POST hello 2 ");
+
+    // The method is changed, and the body is dropped as usual on 302.
+    let client = Client::builder()
+        .redirect(redirect::Policy::custom(|attempt| {
+            attempt.follow().method(Method::PUT)
+        }))
+        .build()
+        .unwrap();
+    let res = client
+        .post(&url)
+        .unwrap()
+        .body("hello")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "PUT   ");
+
+    // The rewrites are ignored when not following.
+    let client = Client::builder()
+        .redirect(redirect::Policy::custom(|attempt| {
+            attempt.stop().method(Method::PUT)
+        }))
+        .build()
+        .unwrap();
+    let res = client.get(&url).unwrap().send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::FOUND);
+}
+
+#[compio::test]
+async fn test_redirect_request_policy() {
+    let server = server::http(move |req: axum::extract::Request| async move {
+        match req.uri().path() {
+            "/redirect" => (StatusCode::FOUND, [(LOCATION, "/target")]).into_response(),
+            "/target" => "target".into_response(),
+            _ => StatusCode::NOT_FOUND.into_response(),
+        }
+    })
+    .await;
+    let url = format!("http://{}/redirect", server.addr());
+
+    let client = Client::new().unwrap();
+    let res = client
+        .get(&url)
+        .unwrap()
+        .redirect(redirect::Policy::none())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FOUND);
+
+    let client = Client::builder()
+        .redirect(redirect::Policy::none())
+        .build()
+        .unwrap();
+    let res = client
+        .get(&url)
+        .unwrap()
+        .redirect(redirect::Policy::default())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "target");
+}
@@ -49,3 +49,82 @@ where
         shutdown_tx: Some(shutdown_tx),
     }
 }
+
+/// An HTTPS server over HTTP/1.1, with a self-signed certificate.
+#[cfg(feature = "rustls")]
+#[allow(dead_code)] // Not every test uses it.
+pub struct HttpsServer {
+    pub addr: SocketAddr,
+    pub cert: compio::rustls::pki_types::CertificateDer<'static>,
+    /// The number of the accepted connections.
+    pub connections: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+
+/// Serve the hyper service over HTTPS, with a certificate for the names.
+#[cfg(feature = "rustls")]
+#[allow(dead_code)] // Not every test uses it.
+pub async fn https<S>(names: &[&str], service: S) -> HttpsServer
+where
+    S: hyper::service::Service<
+            http::Request<hyper::body::Incoming>,
+            Response = http::Response<http_body_util::Full<compio::bytes::Bytes>>,
+            Error = std::convert::Infallible,
+        > + Clone
+        + 'static,
+{
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use compio::{
+        rustls::{ServerConfig, pki_types::PrivateKeyDer},
+        tls::TlsAcceptor,
+    };
+    use cyper_core::HyperStream;
+
+    let names = names
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    let rcgen::CertifiedKey { cert, signing_key } =
+        rcgen::generate_simple_self_signed(names).unwrap();
+    let cert = cert.der().clone();
+    let key = PrivateKeyDer::try_from(signing_key.serialize_der()).unwrap();
+    let mut config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert.clone()], key)
+        .unwrap();
+    config.alpn_protocols = vec![b"http/1.1".into()];
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    let counter = connections.clone();
+    compio::runtime::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let acceptor = acceptor.clone();
+            let service = service.clone();
+            compio::runtime::spawn(async move {
+                let Ok(stream) = acceptor.accept(stream).await else {
+                    return;
+                };
+                hyper::server::conn::http1::Builder::new()
+                    .serve_connection(HyperStream::new_tls(stream), service)
+                    .await
+                    .ok();
+            })
+            .detach();
+        }
+    })
+    .detach();
+
+    HttpsServer {
+        addr,
+        cert,
+        connections,
+    }
+}
@@ -0,0 +1,165 @@
+mod server;
+
+use axum::response::IntoResponse;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use cyper::{
+    Client,
+    signatures::{Signer, SigningKey, Verifier, VerifyingKey},
+};
+use http::{HeaderMap, StatusCode, header};
+use ring::{digest, hmac};
+
+const SECRET: &[u8] = b"test-secret";
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> &'a str {
+    headers
+        .get(name)
+        .map(|v| v.to_str().unwrap())
+        .unwrap_or_default()
+}
+
+/// Verify the `sig1` HMAC signature covering `@method`, `@target-uri`,
+/// `content-digest` and `content-type`.
+fn verify_request(host: &str, req: &http::request::Parts, body: &[u8]) -> bool {
+    let digest = format!(
+        "sha-256=:{}:",
+        BASE64_STANDARD.encode(digest::digest(&digest::SHA256, body))
+    );
+    if header(&req.headers, "content-digest") != digest {
+        return false;
+    }
+    let Some(params) = header(&req.headers, "signature-input").strip_prefix("sig1=") else {
+        return false;
+    };
+    let Some(signature) = header(&req.headers, "signature")
+        .strip_prefix("sig1=:")
+        .and_then(|sig| sig.strip_suffix(':'))
+    else {
+        return false;
+    };
+    let base = format!(
+        "\"@method\": {}\n\"@target-uri\": http://{host}{}\n\"content-digest\": \
+         {digest}\n\"content-type\": {}\n\"@signature-params\": {params}",
+        req.method,
+        req.uri,
+        header(&req.headers, "content-type"),
+    );
+    let key = hmac::Key::new(hmac::HMAC_SHA256, SECRET);
+    hmac::verify(
+        &key,
+        base.as_bytes(),
+        &BASE64_STANDARD.decode(signature).unwrap(),
+    )
+    .is_ok()
+}
+
+async fn handle(req: axum::extract::Request) -> axum::response::Response {
+    let (parts, body) = req.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+    let host = header(&parts.headers, "host").to_string();
+    if !verify_request(&host, &parts, &body) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    match parts.uri.path() {
+        "/redirect" => (
+            StatusCode::TEMPORARY_REDIRECT,
+            [(header::LOCATION, "/target")],
+        )
+            .into_response(),
+        _ => {
+            // Sign the response with `@status` and `content-type`.
+            let params = r#"("@status" "content-type");keyid="server";alg="hmac-sha256""#;
+            let base = format!(
+                "\"@status\": 200\n\"content-type\": text/plain\n\"@signature-params\": {params}"
+            );
+            let key = hmac::Key::new(hmac::HMAC_SHA256, SECRET);
+            let signature = BASE64_STANDARD.encode(hmac::sign(&key, base.as_bytes()));
+            (
+                [
+                    (header::CONTENT_TYPE, "text/plain".to_string()),
+                    ("signature-input".parse().unwrap(), format!("sig1={params}")),
+                    ("signature".parse().unwrap(), format!("sig1=:{signature}:")),
+                ],
+                body,
+            )
+                .into_response()
+        }
+    }
+}
+
+fn client() -> Client {
+    let signer = Signer::new("client", SigningKey::hmac_sha256(SECRET)).components([
+        "@method",
+        "@target-uri",
+        "content-digest",
+        "content-type",
+    ]);
+    Client::builder()
+        .sign_requests("127.0.0.1", signer)
+        .build()
+        .unwrap()
+}
+
+#[compio::test]
+async fn sign_requests() {
+    let server = server::http(handle).await;
+
+    let client = client();
+    // Signed again after the redirect, for the new target URI. The fragment is
+    // not a part of the target URI.
+    let res = client
+        .post(format!("http://{}/redirect?a=1#top", server.addr()))
+        .unwrap()
+        .header(header::CONTENT_TYPE, "text/plain")
+        .unwrap()
+        .body("hello")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let verifier = Verifier::new()
+        .key("server", VerifyingKey::hmac_sha256(SECRET))
+        .require_components(["@status"]);
+    verifier.verify(&res).unwrap();
+    Verifier::new()
+        .key("server", VerifyingKey::hmac_sha256(b"wrong"))
+        .verify(&res)
+        .unwrap_err();
+    assert_eq!(res.text().await.unwrap(), "hello");
+}
+
+#[compio::test]
+async fn sign_requests_other_host() {
+    let server = server::http(handle).await;
+
+    let client = client();
+    let res = client
+        .post(format!("http://localhost:{}/", server.addr().port()))
+        .unwrap()
+        .header(header::CONTENT_TYPE, "text/plain")
+        .unwrap()
+        .body("hello")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[compio::test]
+async fn sign_stream_body() {
+    let server = server::http(handle).await;
+
+    let client = client();
+    let body = futures_util::stream::iter([Ok::<_, cyper::Error>("hello".into())]);
+    let err = client
+        .post(format!("http://{}/", server.addr()))
+        .unwrap()
+        .header(header::CONTENT_TYPE, "text/plain")
+        .unwrap()
+        .body(cyper::Body::stream(body))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, cyper::Error::Signature(_)));
+}
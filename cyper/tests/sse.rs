@@ -0,0 +1,114 @@
+mod server;
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use axum::{extract::Request, response::IntoResponse};
+use cyper::Client;
+use futures_util::StreamExt;
+use http::{StatusCode, header};
+
+#[compio::test]
+async fn sse_response() {
+    let server = server::http(|_: Request| async {
+        (
+            [(header::CONTENT_TYPE, "text/event-stream")],
+            "event: greeting\ndata: hello\ndata: world\n\n: comment\nid: 7\ndata: bye\n\ndata: \
+             lost",
+        )
+    })
+    .await;
+
+    let client = Client::new().unwrap();
+    let mut events = client
+        .get(format!("http://{}/", server.addr()))
+        .unwrap()
+        .send()
+        .await
+        .unwrap()
+        .sse();
+    let event = events.next().await.unwrap().unwrap();
+    assert_eq!(event.event, "greeting");
+    assert_eq!(event.data, "hello\nworld");
+    assert_eq!(event.id, None);
+    let event = events.next().await.unwrap().unwrap();
+    assert_eq!(event.event, "message");
+    assert_eq!(event.data, "bye");
+    assert_eq!(event.id.as_deref(), Some("7"));
+    assert!(events.next().await.is_none());
+    assert_eq!(events.last_event_id(), Some("7"));
+}
+
+#[compio::test]
+async fn sse_reconnect() {
+    let connections = Arc::new(AtomicUsize::new(0));
+    let last_event_ids = Arc::new(Mutex::new(Vec::new()));
+    let server = server::http({
+        let connections = connections.clone();
+        let last_event_ids = last_event_ids.clone();
+        move |req: Request| async move {
+            assert_eq!(req.headers()[header::ACCEPT], "text/event-stream");
+            last_event_ids.lock().unwrap().push(
+                req.headers()
+                    .get("last-event-id")
+                    .map(|id| id.to_str().unwrap().to_string()),
+            );
+            let body = match connections.fetch_add(1, Ordering::SeqCst) {
+                0 => "retry: 10\n\nid: 1\ndata: one\n\n",
+                1 => "data: two\n\n",
+                _ => return StatusCode::NO_CONTENT.into_response(),
+            };
+            ([(header::CONTENT_TYPE, "text/event-stream")], body).into_response()
+        }
+    })
+    .await;
+
+    let client = Client::new().unwrap();
+    let events = client
+        .get(format!("http://{}/", server.addr()))
+        .unwrap()
+        .event_source();
+    let events = compio::time::timeout(Duration::from_secs(1), events.collect::<Vec<_>>())
+        .await
+        .unwrap();
+    let events = events
+        .into_iter()
+        .map(|event| {
+            let event = event.unwrap();
+            (event.id, event.data)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        [
+            (Some("1".to_string()), "one".to_string()),
+            (Some("1".to_string()), "two".to_string()),
+        ]
+    );
+    assert_eq!(connections.load(Ordering::SeqCst), 3);
+    assert_eq!(
+        *last_event_ids.lock().unwrap(),
+        [None, Some("1".to_string()), Some("1".to_string())]
+    );
+}
+
+#[compio::test]
+async fn sse_bad_content_type() {
+    let server = server::http(|_: Request| async { "data: plain\n\n" }).await;
+
+    let client = Client::new().unwrap();
+    let mut events = client
+        .get(format!("http://{}/", server.addr()))
+        .unwrap()
+        .event_source();
+    assert!(matches!(
+        events.next().await.unwrap(),
+        Err(cyper::Error::EventSource(_))
+    ));
+    assert!(events.next().await.is_none());
+}
@@ -0,0 +1,191 @@
+mod server;
+
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use axum::response::IntoResponse;
+use cyper::{Client, trace::TraceContext};
+use http::{HeaderValue, StatusCode, header};
+use tracing::{
+    Event, Metadata, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+};
+
+type Fieldset = HashMap<String, String>;
+
+/// Records the fields of all spans.
+#[derive(Default, Clone)]
+struct Recorder {
+    spans: Arc<Mutex<Vec<(&'static str, Fieldset)>>>,
+}
+
+impl Recorder {
+    fn span(&self, name: &str) -> Fieldset {
+        let spans = self.spans.lock().unwrap();
+        let mut spans = spans.iter().filter(|(n, _)| *n == name);
+        let (_, fields) = spans.next().expect("the span should be recorded");
+        assert!(spans.next().is_none(), "only one span should be recorded");
+        fields.clone()
+    }
+}
+
+struct Fields<'a>(&'a mut Fieldset);
+
+impl Visit for Fields<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}"));
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = HashMap::new();
+        span.record(&mut Fields(&mut fields));
+        let mut spans = self.spans.lock().unwrap();
+        spans.push((span.metadata().name(), fields));
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        values.record(&mut Fields(&mut spans[span.into_u64() as usize - 1].1));
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, _: &Event<'_>) {}
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[compio::test]
+async fn span_fields() {
+    let server = server::http(|req: axum::extract::Request| async move {
+        match req.uri().path() {
+            "/redirect" => (StatusCode::FOUND, [(header::LOCATION, "/missing")]).into_response(),
+            _ => StatusCode::NOT_FOUND.into_response(),
+        }
+    })
+    .await;
+
+    let recorder = Recorder::default();
+    let _guard = tracing::subscriber::set_default(recorder.clone());
+
+    let client = Client::new().unwrap();
+    let url = format!("http://user:secret@{}/redirect", server.addr());
+    let res = client.get(&url).unwrap().send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let fields = recorder.span("HTTP");
+    let field = |name: &str| fields.get(name).map(String::as_str);
+    assert_eq!(field("otel.name"), Some("GET"));
+    assert_eq!(field("otel.kind"), Some("client"));
+    assert_eq!(field("otel.status_code"), Some("ERROR"));
+    assert_eq!(field("http.request.method"), Some("GET"));
+    assert_eq!(
+        field("url.full"),
+        Some(format!("http://REDACTED:REDACTED@{}/redirect", server.addr()).as_str())
+    );
+    assert_eq!(field("server.address"), Some("127.0.0.1"));
+    assert_eq!(
+        field("server.port"),
+        Some(server.addr().port().to_string().as_str())
+    );
+    assert_eq!(field("http.response.status_code"), Some("404"));
+    assert_eq!(field("network.protocol.name"), Some("http"));
+    assert_eq!(field("network.protocol.version"), Some("1.1"));
+    assert_eq!(field("network.peer.address"), Some("127.0.0.1"));
+    assert_eq!(field("http.request.resend_count"), Some("1"));
+    assert_eq!(field("cyper.redirect_count"), Some("1"));
+    assert_eq!(field("cyper.retry_count"), Some("0"));
+    assert_eq!(field("error.type"), Some("404"));
+}
+
+#[compio::test]
+async fn span_error() {
+    let recorder = Recorder::default();
+    let _guard = tracing::subscriber::set_default(recorder.clone());
+
+    let client = Client::new().unwrap();
+    client
+        .get("http://127.0.0.1:1/")
+        .unwrap()
+        .send()
+        .await
+        .unwrap_err();
+
+    let fields = recorder.span("HTTP");
+    assert_eq!(fields["otel.status_code"], "ERROR");
+    assert_eq!(fields["error.type"], "HyperClient");
+    assert!(!fields.contains_key("http.response.status_code"));
+}
+
+#[compio::test]
+async fn inject_trace_context() {
+    let server = server::http(|req: axum::extract::Request| async move {
+        let header = |name| {
+            req.headers()
+                .get(name)
+                .map(|v: &HeaderValue| v.to_str().unwrap().to_string())
+                .unwrap_or_default()
+        };
+        format!("{} {}", header("traceparent"), header("tracestate"))
+    })
+    .await;
+
+    let client = Client::builder()
+        .trace_context(|| {
+            Some(
+                TraceContext::new(0x4bf92f3577b34da6a3ce929d0e0e4736, 0xf067aa0ba902b7, true)
+                    .unwrap()
+                    .state(HeaderValue::from_static("congo=t61rcWkgMzE")),
+            )
+        })
+        .build()
+        .unwrap();
+    let url = format!("http://{}/", server.addr());
+    let res = client.get(&url).unwrap().send().await.unwrap();
+    assert_eq!(
+        res.text().await.unwrap(),
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01 congo=t61rcWkgMzE"
+    );
+
+    // The existing context is propagated as is.
+    let traceparent = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00";
+    let res = client
+        .get(&url)
+        .unwrap()
+        .header("traceparent", traceparent)
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), format!("{traceparent} "));
+
+    let mut headers = http::HeaderMap::new();
+    headers.insert("traceparent", HeaderValue::from_static(traceparent));
+    headers.insert(
+        "tracestate",
+        HeaderValue::from_static("rojo=00f067aa0ba902b7"),
+    );
+    let context = TraceContext::from_headers(&headers).unwrap();
+    assert_eq!(context.trace_id(), 0x0af7651916cd43dd8448eb211c80319c);
+    assert_eq!(context.parent_id(), 0xb7ad6b7169203331);
+    assert!(!context.sampled());
+    assert_eq!(context.traceparent(), traceparent);
+}
@@ -0,0 +1,132 @@
+mod server;
+
+use std::convert::Infallible;
+
+use axum::response::IntoResponse;
+use compio::bytes::Bytes;
+use cyper::{Body, Client};
+use http::{HeaderMap, HeaderValue, header};
+use http_body_util::{BodyExt, StreamBody};
+use hyper::body::Frame;
+
+/// Echo the request body and the `x-checksum` request trailer, and send the
+/// response trailers.
+async fn echo(req: axum::extract::Request) -> axum::response::Response {
+    let collected = req.into_body().collect().await.unwrap();
+    let checksum = collected
+        .trailers()
+        .and_then(|trailers| trailers.get("x-checksum"))
+        .cloned()
+        .unwrap_or(HeaderValue::from_static("none"));
+    let mut trailers = HeaderMap::new();
+    trailers.insert("x-checksum", checksum);
+    trailers.insert("grpc-status", HeaderValue::from_static("0"));
+    let frames = [
+        Ok::<_, Infallible>(Frame::data(collected.to_bytes())),
+        Ok(Frame::trailers(trailers)),
+    ];
+    (
+        [(header::TRAILER, "x-checksum, grpc-status")],
+        axum::body::Body::new(StreamBody::new(futures_util::stream::iter(frames))),
+    )
+        .into_response()
+}
+
+fn request_trailers() -> HeaderMap {
+    HeaderMap::from_iter([("x-checksum".parse().unwrap(), "abc".parse().unwrap())])
+}
+
+async fn assert_trailers(client: &Client, addr: std::net::SocketAddr) {
+    let mut res = client
+        .post(format!("http://{addr}/"))
+        .unwrap()
+        // HTTP/1.1 servers only send trailers if the client accepts them.
+        .header(header::TE, "trailers")
+        .unwrap()
+        .body(Body::from("hello").with_trailers(request_trailers()))
+        .send()
+        .await
+        .unwrap();
+    let trailers = res.trailers().await.unwrap().unwrap();
+    assert_eq!(trailers["x-checksum"], "abc");
+    assert_eq!(trailers["grpc-status"], "0");
+}
+
+#[compio::test]
+async fn trailers_http1() {
+    let server = server::http(echo).await;
+
+    let client = Client::new().unwrap();
+    assert_trailers(&client, server.addr()).await;
+}
+
+#[cfg(feature = "http2")]
+#[compio::test]
+async fn trailers_http2() {
+    let server = server::http(echo).await;
+
+    let client = Client::builder().http2_prior_knowledge().build().unwrap();
+    assert_trailers(&client, server.addr()).await;
+}
+
+#[compio::test]
+async fn trailers_future() {
+    let server = server::http(echo).await;
+
+    let client = Client::new().unwrap();
+    let body = Body::from(Bytes::from_static(b"hello"))
+        .with_trailers_future(async { Ok(request_trailers()) });
+    let mut res = client
+        .post(format!("http://{}/", server.addr()))
+        .unwrap()
+        .header(header::TE, "trailers")
+        .unwrap()
+        .header(header::TRAILER, "x-checksum")
+        .unwrap()
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    let trailers = res.trailers().await.unwrap().unwrap();
+    assert_eq!(trailers["x-checksum"], "abc");
+}
+
+#[cfg(feature = "stream")]
+#[compio::test]
+async fn trailers_after_stream() {
+    use futures_util::StreamExt;
+
+    let server = server::http(echo).await;
+
+    let client = Client::new().unwrap();
+    let mut res = client
+        .post(format!("http://{}/", server.addr()))
+        .unwrap()
+        .header(header::TE, "trailers")
+        .unwrap()
+        .body("hello")
+        .send()
+        .await
+        .unwrap();
+    let mut body = Vec::new();
+    while let Some(chunk) = res.next().await {
+        body.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(body, b"hello");
+    let trailers = res.trailers().await.unwrap().unwrap();
+    assert_eq!(trailers["x-checksum"], "none");
+}
+
+#[compio::test]
+async fn no_trailers() {
+    let server = server::http(|_: axum::extract::Request| async { "hello" }).await;
+
+    let client = Client::new().unwrap();
+    let mut res = client
+        .get(format!("http://{}/", server.addr()))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert!(res.trailers().await.unwrap().is_none());
+}
@@ -0,0 +1,105 @@
+mod server;
+
+use axum::{extract::Request, response::IntoResponse};
+use cyper::Client;
+use http::{Method, StatusCode, header};
+
+/// Echo everything on the upgraded connection.
+async fn echo(mut req: Request) -> axum::response::Response {
+    let on_upgrade = hyper::upgrade::on(&mut req);
+    let status = if req.method() == Method::CONNECT {
+        StatusCode::OK
+    } else {
+        StatusCode::SWITCHING_PROTOCOLS
+    };
+    compio::runtime::spawn(async move { echo_io(on_upgrade.await.unwrap()).await.ok() }).detach();
+    (status, [(header::UPGRADE, "echo")]).into_response()
+}
+
+async fn echo_io(mut io: hyper::upgrade::Upgraded) -> std::io::Result<()> {
+    use std::{future::poll_fn, pin::Pin};
+
+    use hyper::rt::{Read, ReadBuf, Write};
+
+    let mut buf = [0u8; 1024];
+    loop {
+        let mut read_buf = ReadBuf::new(&mut buf);
+        poll_fn(|cx| Pin::new(&mut io).poll_read(cx, read_buf.unfilled())).await?;
+        let len = read_buf.filled().len();
+        if len == 0 {
+            return Ok(());
+        }
+        let mut data = &buf[..len];
+        while !data.is_empty() {
+            let written = poll_fn(|cx| Pin::new(&mut io).poll_write(cx, data)).await?;
+            data = &data[written..];
+        }
+        poll_fn(|cx| Pin::new(&mut io).poll_flush(cx)).await?;
+    }
+}
+
+#[compio::test]
+async fn upgrade_switching_protocols() {
+    use compio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+    let server = server::http(echo).await;
+
+    let client = Client::new().unwrap();
+    let res = client
+        .get(format!("http://{}/", server.addr()))
+        .unwrap()
+        .header(header::CONNECTION, "upgrade")
+        .unwrap()
+        .header(header::UPGRADE, "echo")
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::SWITCHING_PROTOCOLS);
+
+    let mut upgraded = res.upgrade().await.unwrap();
+    upgraded.write_all("hello").await.0.unwrap();
+    upgraded.flush().await.unwrap();
+    let (_, buf) = upgraded.read_exact(Vec::with_capacity(5)).await.unwrap();
+    assert_eq!(buf, b"hello");
+}
+
+#[compio::test]
+async fn upgrade_connect_tunnel() {
+    use futures_util::{AsyncReadExt, AsyncWriteExt};
+
+    let server = server::http(echo).await;
+
+    let client = Client::new().unwrap();
+    let res = client
+        .request(Method::CONNECT, format!("http://{}", server.addr()))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let mut upgraded = res.upgrade().await.unwrap();
+    upgraded.write_all(b"tunnel").await.unwrap();
+    upgraded.flush().await.unwrap();
+    let mut buf = [0; 6];
+    upgraded.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"tunnel");
+}
+
+#[compio::test]
+async fn upgrade_not_upgradable() {
+    let server = server::http(|_: Request| async { "plain" }).await;
+
+    let client = Client::new().unwrap();
+    let res = client
+        .get(format!("http://{}/", server.addr()))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert!(matches!(
+        res.upgrade().await.unwrap_err(),
+        cyper::Error::Hyper(_)
+    ));
+}
@@ -0,0 +1,127 @@
+mod server;
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use axum::response::IntoResponse;
+use cyper::{
+    Client,
+    vcr::{Cassette, Mode},
+};
+use http::{StatusCode, header};
+
+#[compio::test]
+async fn record_replay() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = server::http({
+        let hits = hits.clone();
+        move |req: axum::extract::Request| async move {
+            let count = hits.fetch_add(1, Ordering::Relaxed) + 1;
+            match req.uri().path() {
+                "/redirect" => (StatusCode::FOUND, [(header::LOCATION, "/count")]).into_response(),
+                "/echo" => axum::body::to_bytes(req.into_body(), usize::MAX)
+                    .await
+                    .unwrap()
+                    .into_response(),
+                _ => count.to_string().into_response(),
+            }
+        }
+    })
+    .await;
+    let url = format!("http://{}/", server.addr());
+
+    let cassette = Cassette::new().mode(Mode::Record);
+    let client = Client::builder()
+        .cassette(cassette.clone())
+        .build()
+        .unwrap();
+    let res = client
+        .get(format!("{url}redirect"))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.url().path(), "/count");
+    assert_eq!(res.text().await.unwrap(), "2");
+    for body in ["a", "b"] {
+        let res = client
+            .post(format!("{url}echo"))
+            .unwrap()
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.text().await.unwrap(), body);
+    }
+    assert_eq!(cassette.len(), 4);
+    assert_eq!(hits.load(Ordering::Relaxed), 4);
+
+    let path = std::env::temp_dir().join(format!("cyper-vcr-{}.json", std::process::id()));
+    cassette.save(&path).await.unwrap();
+    let cassette = Cassette::load(&path).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(cassette.len(), 4);
+
+    // The recorded exchanges are replayed without touching the server.
+    let client = Client::builder().cassette(cassette).build().unwrap();
+    let res = client
+        .get(format!("{url}redirect"))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.url().path(), "/count");
+    assert_eq!(res.redirect_history().len(), 1);
+    assert_eq!(res.text().await.unwrap(), "2");
+    let res = client
+        .post(format!("{url}echo"))
+        .unwrap()
+        .body("b")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "b");
+    let err = client
+        .post(format!("{url}echo"))
+        .unwrap()
+        .body("c")
+        .send()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, cyper::Error::Cassette(_)), "{err:?}");
+    assert_eq!(hits.load(Ordering::Relaxed), 4);
+}
+
+#[compio::test]
+async fn replay_or_record() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = server::http({
+        let hits = hits.clone();
+        move |_req: axum::extract::Request| async move {
+            (hits.fetch_add(1, Ordering::Relaxed) + 1).to_string()
+        }
+    })
+    .await;
+    let url = format!("http://{}/", server.addr());
+
+    let cassette = Cassette::new().mode(Mode::ReplayOrRecord);
+    let client = Client::builder()
+        .cassette(cassette.clone())
+        .build()
+        .unwrap();
+    for expected in ["1", "1", "1"] {
+        let res = client.get(&url).unwrap().send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), expected);
+    }
+    let res = client
+        .get(format!("{url}other"))
+        .unwrap()
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "2");
+    assert_eq!(cassette.len(), 2);
+    assert_eq!(hits.load(Ordering::Relaxed), 2);
+}
@@ -0,0 +1,236 @@
+mod server;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use axum::{extract::FromRequestParts, response::IntoResponse};
+use cyper::{
+    Client,
+    auth::Authenticator,
+    ws::{Message, WebSocket},
+};
+use cyper_axum::ws::WebSocketUpgrade;
+use http::{HeaderMap, HeaderValue, StatusCode, header};
+
+async fn echo(req: axum::extract::Request) -> axum::response::Response {
+    let (mut parts, _) = req.into_parts();
+    let ws = match WebSocketUpgrade::from_request_parts(&mut parts, &()).await {
+        Ok(ws) => ws,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let cookie = parts
+        .headers
+        .get(header::COOKIE)
+        .map(|v| v.to_str().unwrap().to_string());
+    ws.protocols(["chat"])
+        .on_upgrade(move |mut socket: cyper_axum::ws::WebSocket| async move {
+            if let Some(cookie) = cookie {
+                socket
+                    .send(cyper_axum::ws::Message::text(cookie))
+                    .await
+                    .ok();
+            }
+            while let Some(Ok(msg)) = socket.recv().await {
+                if msg.is_close() || socket.send(msg).await.is_err() {
+                    break;
+                }
+            }
+        })
+}
+
+async fn assert_echo(ws: &mut WebSocket) {
+    ws.send(Message::text("hello")).await.unwrap();
+    assert_eq!(ws.recv().await.unwrap().unwrap(), Message::text("hello"));
+    ws.send(Message::binary(vec![1, 2, 3])).await.unwrap();
+    assert_eq!(
+        ws.recv().await.unwrap().unwrap(),
+        Message::binary(vec![1, 2, 3])
+    );
+}
+
+#[compio::test]
+async fn ws_http1_upgrade() {
+    let server = server::http(echo).await;
+
+    let client = Client::new().unwrap();
+    let mut ws = client
+        .get(format!("ws://{}/ws", server.addr()))
+        .unwrap()
+        .websocket()
+        .await
+        .unwrap();
+    assert!(ws.protocol().is_none());
+    assert_echo(&mut ws).await;
+    ws.close(None).await.unwrap();
+}
+
+#[compio::test]
+async fn ws_subprotocol_and_headers() {
+    let server = server::http(echo).await;
+
+    let client = Client::builder()
+        .default_headers(HeaderMap::from_iter([(
+            header::COOKIE,
+            "session=1".parse().unwrap(),
+        )]))
+        .build()
+        .unwrap();
+    let mut ws = client
+        .get(format!("http://{}/ws", server.addr()))
+        .unwrap()
+        .header(header::SEC_WEBSOCKET_PROTOCOL, "mqtt, chat")
+        .unwrap()
+        .websocket()
+        .await
+        .unwrap();
+    assert_eq!(ws.protocol().unwrap(), "chat");
+    assert_eq!(
+        ws.recv().await.unwrap().unwrap(),
+        Message::text("session=1")
+    );
+    assert_echo(&mut ws).await;
+}
+
+#[cfg(feature = "http2")]
+#[compio::test]
+async fn ws_http2_extended_connect() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let server = server::http({
+        let requests = requests.clone();
+        move |req: axum::extract::Request| async move {
+            requests.fetch_add(1, Ordering::Relaxed);
+            echo(req).await
+        }
+    })
+    .await;
+
+    // The version is detected from the connection, and only the extended
+    // CONNECT is sent.
+    let client = Client::builder().http2_prior_knowledge().build().unwrap();
+    let mut ws = client
+        .get(format!("ws://{}/ws", server.addr()))
+        .unwrap()
+        .websocket()
+        .await
+        .unwrap();
+    assert_echo(&mut ws).await;
+    assert_eq!(requests.load(Ordering::Relaxed), 1);
+
+    // Extended CONNECT is used directly with HTTP/2.
+    let mut ws = client
+        .get(format!("ws://{}/ws", server.addr()))
+        .unwrap()
+        .version(http::Version::HTTP_2)
+        .websocket()
+        .await
+        .unwrap();
+    assert_echo(&mut ws).await;
+}
+
+#[compio::test]
+async fn ws_rejected() {
+    let server =
+        server::http(|_: axum::extract::Request| async { (StatusCode::FORBIDDEN, "no websocket") })
+            .await;
+
+    let client = Client::new().unwrap();
+    let err = client
+        .get(format!("ws://{}/ws", server.addr()))
+        .unwrap()
+        .websocket()
+        .await
+        .unwrap_err();
+    let cyper::Error::WebSocket(async_tungstenite::tungstenite::Error::Http(res)) = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    assert_eq!(res.body().as_deref(), Some(&b"no websocket"[..]));
+}
+
+#[compio::test]
+async fn ws_redirect_policy() {
+    let server = server::http(|req: axum::extract::Request| async move {
+        match req.uri().path() {
+            "/old" => (StatusCode::FOUND, [(header::LOCATION, "/ws")]).into_response(),
+            _ => echo(req).await,
+        }
+    })
+    .await;
+
+    let client = Client::new().unwrap();
+    let mut ws = client
+        .get(format!("ws://{}/old", server.addr()))
+        .unwrap()
+        .websocket()
+        .await
+        .unwrap();
+    assert_echo(&mut ws).await;
+
+    // The policy of the request overrides the one of the client.
+    let err = client
+        .get(format!("ws://{}/old", server.addr()))
+        .unwrap()
+        .redirect(cyper::redirect::Policy::none())
+        .websocket()
+        .await
+        .unwrap_err();
+    let cyper::Error::WebSocket(async_tungstenite::tungstenite::Error::Http(res)) = err else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert_eq!(res.status(), StatusCode::FOUND);
+}
+
+struct Token(AtomicUsize);
+
+impl Authenticator for Token {
+    type Err = cyper::Error;
+
+    async fn authorize(
+        &self,
+        _client: &Client,
+        _request: &cyper::Request,
+    ) -> cyper::Result<Option<HeaderValue>> {
+        let token = self.0.load(Ordering::SeqCst);
+        Ok(Some(
+            HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+        ))
+    }
+
+    async fn challenge(
+        &self,
+        _client: &Client,
+        _request: &cyper::Request,
+        _response: &cyper::Response,
+    ) -> cyper::Result<bool> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Ok(true)
+    }
+}
+
+#[compio::test]
+async fn ws_authenticator() {
+    let server = server::http(|req: axum::extract::Request| async move {
+        match req.headers().get(header::AUTHORIZATION) {
+            Some(auth) if auth == "Bearer 1" => echo(req).await,
+            _ => StatusCode::UNAUTHORIZED.into_response(),
+        }
+    })
+    .await;
+
+    // The stale token is refreshed after the challenge.
+    let client = Client::builder()
+        .authenticator(Token(AtomicUsize::new(0)))
+        .build()
+        .unwrap();
+    let mut ws = client
+        .get(format!("ws://{}/ws", server.addr()))
+        .unwrap()
+        .websocket()
+        .await
+        .unwrap();
+    assert_echo(&mut ws).await;
+}